@@ -0,0 +1,542 @@
+pub mod json;
+
+use std::cell::RefCell;
+use std::io::{self, BufRead, Read, Write};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+use crate::dap::json::Json;
+use crate::parsing::parse_file;
+use crate::runtime::debug::{describe, evaluate, flatten, Breakpoints, CallFrame, DebugFrontend, Debugger, Resume};
+use crate::runtime::error::{ErrorKind, RuntimeError};
+use crate::runtime::std_lib::{d_format, FormatOptions};
+use crate::runtime::util::dynamic_native_function;
+use crate::runtime::{Function, Runtime, RuntimeObject, Type};
+
+/*
+
+Debug adapter: serves the Debug Adapter Protocol, one thread that is the entry function and
+everything it calls, paused through the same debugger hook as the command line debugger
+
+Functions parsed from a file are shown at their lines in it and breakpoints set on the file bind to
+the first instruction at or after their line. Functions without a file, and clients naming a function
+as source, get a source of its own whose line n is instruction n - 1, the `source` request returns
+its instruction listing
+
+ */
+
+//the program runs on a single thread
+const THREAD: i64 = 1;
+//requests are small, longer messages are skipped
+const MAX_MESSAGE: usize = 16 * 1024 * 1024;
+
+/// Writes protocol messages, shared with the `std/io/print` replacement that sends output events.
+struct Output {
+    writer: Box<dyn Write + Send>,
+    seq: i64
+}
+
+impl Output {
+
+    fn send(&mut self, kind: &str, mut message: Vec<(&str, Json)>) {
+        self.seq += 1;
+        message.insert(0, ("type", kind.into()));
+        message.insert(0, ("seq", self.seq.into()));
+        let body = Json::object(message).to_string();
+        let _ = write!(self.writer, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+        let _ = self.writer.flush();
+    }
+
+    fn event(&mut self, event: &str, body: Json) {
+        self.send("event", vec![("event", event.into()), ("body", body)])
+    }
+
+    fn respond(&mut self, request: &Json, body: Json) {
+        self.send("response", vec![
+            ("request_seq", request.get("seq").cloned().unwrap_or(Json::Null)),
+            ("success", true.into()),
+            ("command", request.get("command").cloned().unwrap_or(Json::Null)),
+            ("body", body)
+        ])
+    }
+
+    fn fail(&mut self, request: &Json, message: String) {
+        self.send("response", vec![
+            ("request_seq", request.get("seq").cloned().unwrap_or(Json::Null)),
+            ("success", false.into()),
+            ("command", request.get("command").cloned().unwrap_or(Json::Null)),
+            ("message", message.into())
+        ])
+    }
+}
+
+struct Session {
+    input: Box<dyn BufRead>,
+    output: Arc<Mutex<Output>>,
+    //source reference n is the function sources[n - 1]
+    sources: Vec<String>,
+    //the first pause is reported as entry instead of step
+    stop_on_entry: bool,
+    //the client left while the program was paused, serving ends once the program unwound
+    disconnected: bool
+}
+
+impl Session {
+
+    fn output(&self) -> std::sync::MutexGuard<'_, Output> {
+        self.output.lock().unwrap()
+    }
+
+    /// Next request, `None` once the client closed the stream, invalid messages are reported and skipped.
+    fn receive(&mut self) -> Option<Json> {
+        loop {
+            let mut length = None;
+            loop {
+                let mut line = String::new();
+                if self.input.read_line(&mut line).ok()? == 0 {
+                    return None
+                }
+                let line = line.trim_end();
+                if line.is_empty() {
+                    break
+                }
+                if let Some(value) = line.strip_prefix("Content-Length:") {
+                    length = value.trim().parse::<usize>().ok();
+                }
+            }
+            let error = match length {
+                None => "Invalid message: missing Content-Length".to_string(),
+                //skipped without buffering, a bad header must not make the adapter allocate it
+                Some(length) if length > MAX_MESSAGE => {
+                    io::copy(&mut (&mut self.input).take(length as u64), &mut io::sink()).ok()?;
+                    format!("Invalid message: {} bytes is more than {}", length, MAX_MESSAGE)
+                }
+                Some(length) => {
+                    let mut body = vec![0; length];
+                    self.input.read_exact(&mut body).ok()?;
+                    match Json::parse(&String::from_utf8_lossy(&body)) {
+                        Ok(message) => return Some(message),
+                        Err(e) => format!("Invalid message: {}", e)
+                    }
+                }
+            };
+            self.output().event("output", Json::object(vec![("category", "stderr".into()), ("output", format!("{}\n", error).into())]));
+        }
+    }
+
+    fn source(&mut self, signature: &str) -> Json {
+        let reference = match self.sources.iter().position(|it| it == signature) {
+            Some(i) => i + 1,
+            None => {
+                self.sources.push(signature.to_string());
+                self.sources.len()
+            }
+        };
+        Json::object(vec![("name", signature.into()), ("sourceReference", reference.into())])
+    }
+
+    //function a client source stands for, by reference or by name
+    fn source_function(&self, source: Option<&Json>) -> Option<String> {
+        let source = source?;
+        match source.get("sourceReference").and_then(|it| it.as_i64()) {
+            Some(reference) if reference > 0 => self.sources.get(reference as usize - 1).cloned(),
+            _ => source.get("name").and_then(|it| it.as_str()).map(|it| it.to_string())
+        }
+    }
+
+    //the file and line a client is shown for an instruction, a listing of the function if it has no file
+    fn position(&mut self, function: Option<&Function>, signature: &str, index: usize) -> (Json, usize) {
+        let source = function.and_then(|it| it.source.as_ref());
+        match source.and_then(|it| it.path.as_ref().zip(it.lines.get(index))) {
+            Some((path, line)) => (file_source(path), *line),
+            None => (self.source(signature), index + 1)
+        }
+    }
+
+    /// Replaces the breakpoints of the functions parsed from `path`, each binds to the first instruction at or after its line.
+    fn set_file_breakpoints(&mut self, runtime: &Runtime, request: &Json, path: &str, breakpoints: &mut Breakpoints) {
+        let functions: Vec<&Function> = runtime.functions()
+            .filter(|function| function.source.as_ref().and_then(|it| it.path.as_deref()).is_some_and(|it| same_file(it, path)))
+            .collect();
+        functions.iter().for_each(|function| breakpoints.clear(&function.signature));
+        let source = file_source(path);
+        let lines = request.get("arguments").and_then(|it| it.get("breakpoints")).and_then(|it| it.as_array()).unwrap_or(&[]);
+        let results: Vec<Json> = lines.iter().map(|breakpoint| {
+            let line = breakpoint.get("line").and_then(|it| it.as_i64()).unwrap_or(0).max(0) as usize;
+            let bound = functions.iter()
+                .filter_map(|function| {
+                    let lines = &function.source.as_ref()?.lines;
+                    lines.iter().position(|it| *it >= line).map(|index| (lines[index], function.signature.as_str(), index))
+                })
+                .min();
+            match bound {
+                Some((actual, signature, index)) => {
+                    breakpoints.add(signature, index);
+                    Json::object(vec![("verified", true.into()), ("line", actual.into()), ("source", source.clone())])
+                }
+                None => Json::object(vec![
+                    ("verified", false.into()),
+                    ("line", line.into()),
+                    ("source", source.clone()),
+                    ("message", format!("No instruction at or after line {}", line).into())
+                ])
+            }
+        }).collect();
+        self.output().respond(request, Json::object(vec![("breakpoints", results.into())]))
+    }
+
+    /// Answers the requests that work whether or not the program is paused, returns false for unknown ones.
+    fn handle_common(&mut self, runtime: &Runtime, request: &Json, breakpoints: &mut Breakpoints) -> bool {
+        let arguments = request.get("arguments").cloned().unwrap_or(Json::Object(vec![]));
+        match request.get("command").and_then(|it| it.as_str()).unwrap_or("") {
+            "threads" => self.output().respond(request, Json::object(vec![
+                ("threads", vec![Json::object(vec![("id", THREAD.into()), ("name", "main".into())])].into())
+            ])),
+            "setBreakpoints" if arguments.get("source").and_then(|it| it.get("path")).and_then(|it| it.as_str()).is_some() => {
+                let path = arguments.get("source").and_then(|it| it.get("path")).and_then(|it| it.as_str()).unwrap();
+                self.set_file_breakpoints(runtime, request, path, breakpoints)
+            }
+            "setBreakpoints" => {
+                let signature = match self.source_function(arguments.get("source")) {
+                    Some(signature) => signature,
+                    None => {
+                        self.output().fail(request, "Expected a source naming a function".to_string());
+                        return true
+                    }
+                };
+                let count = runtime.get_function(&signature).map(|it| flatten(&it.instructions).len());
+                let source = self.source(&signature);
+                breakpoints.clear(&signature);
+                let lines = arguments.get("breakpoints").and_then(|it| it.as_array()).unwrap_or(&[]);
+                let results: Vec<Json> = lines.iter().map(|breakpoint| {
+                    let line = breakpoint.get("line").and_then(|it| it.as_i64()).unwrap_or(0);
+                    let message = match count {
+                        None => Some(format!("No function {}", signature)),
+                        Some(count) if line < 1 || line as usize > count => Some(format!("{} has no instruction {}", signature, line - 1)),
+                        Some(_) => None
+                    };
+                    if message.is_none() {
+                        breakpoints.add(&signature, line as usize - 1);
+                    }
+                    let mut result = vec![("verified", message.is_none().into()), ("line", line.into()), ("source", source.clone())];
+                    if let Some(message) = message {
+                        result.push(("message", message.into()));
+                    }
+                    Json::object(result)
+                }).collect();
+                self.output().respond(request, Json::object(vec![("breakpoints", results.into())]))
+            }
+            "source" => {
+                let signature = arguments.get("sourceReference").and_then(|it| it.as_i64())
+                    .filter(|it| *it > 0)
+                    .and_then(|it| self.sources.get(it as usize - 1).cloned());
+                match signature.and_then(|it| runtime.get_function(&it)) {
+                    Some(function) => {
+                        let content: String = flatten(&function.instructions).iter()
+                            .map(|(depth, instruction)| format!("{}{}\n", "  ".repeat(*depth), describe(instruction)))
+                            .collect();
+                        self.output().respond(request, Json::object(vec![("content", content.into())]))
+                    }
+                    None => self.output().fail(request, "Unknown source reference".to_string())
+                }
+            }
+            _ => return false
+        }
+        true
+    }
+}
+
+fn file_source(path: &str) -> Json {
+    let name = std::path::Path::new(path).file_name().map(|it| it.to_string_lossy().to_string()).unwrap_or_else(|| path.to_string());
+    Json::object(vec![("name", name.into()), ("path", path.into())])
+}
+
+//clients may send the path in another form than the program was launched with
+fn same_file(a: &str, b: &str) -> bool {
+    match (std::fs::canonicalize(a), std::fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b
+    }
+}
+
+//a variable reference expands to one of these while the program is paused
+#[derive(Clone)]
+enum Container {
+    Arguments(usize),
+    Locals(usize),
+    Stack(usize),
+    Value(RuntimeObject)
+}
+
+struct Paused {
+    session: Rc<RefCell<Session>>
+}
+
+impl Paused {
+
+    fn variable(name: String, value: &RuntimeObject, containers: &mut Vec<Container>) -> Json {
+        let expandable = matches!(value, RuntimeObject::Object(_) | RuntimeObject::List(_) | RuntimeObject::Map(_));
+        let reference = if expandable {
+            containers.push(Container::Value(value.clone()));
+            containers.len()
+        } else {
+            0
+        };
+        let shown = match value {
+            RuntimeObject::Str(s) => format!("{:?}", s),
+            RuntimeObject::List(items) => format!("List({})", items.len()),
+            RuntimeObject::Map(map) => format!("Map({})", map.len()),
+            other => other.to_string()
+        };
+        Json::object(vec![
+            ("name", name.into()),
+            ("value", shown.into()),
+            ("type", value.get_type().to_string().into()),
+            ("variablesReference", reference.into())
+        ])
+    }
+
+    fn variables(runtime: &Runtime, calls: &[CallFrame], container: &Container, containers: &mut Vec<Container>) -> Vec<Json> {
+        let values: Vec<(String, RuntimeObject)> = match container {
+            Container::Arguments(frame) => calls[*frame].args.iter().enumerate().map(|(i, value)| (i.to_string(), value.clone())).collect(),
+            Container::Locals(frame) => calls[*frame].variables.clone(),
+            Container::Stack(frame) => calls[*frame].stack.iter().enumerate().map(|(i, value)| (i.to_string(), value.clone())).collect(),
+            Container::Value(RuntimeObject::Object(o)) => {
+                let mut fields: Vec<(String, RuntimeObject)> = runtime.storage.borow_fields(o).iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+                fields.sort_by(|a, b| a.0.cmp(&b.0));
+                fields
+            }
+            Container::Value(RuntimeObject::List(items)) => items.iter().enumerate().map(|(i, value)| (i.to_string(), value.clone())).collect(),
+            Container::Value(RuntimeObject::Map(map)) => map.iter().map(|(key, value)| (key.to_string(), value.clone())).collect(),
+            Container::Value(_) => vec![]
+        };
+        values.iter().map(|(name, value)| Paused::variable(name.clone(), value, containers)).collect()
+    }
+}
+
+impl DebugFrontend for Paused {
+    fn paused(&mut self, runtime: &mut Runtime, calls: &[CallFrame], breakpoints: &mut Breakpoints) -> Resume {
+        let mut session = self.session.borrow_mut();
+        let call = calls.last().unwrap();
+        let reason = if session.stop_on_entry {
+            "entry"
+        } else if breakpoints.contains(&call.signature, call.index) {
+            "breakpoint"
+        } else {
+            "step"
+        };
+        session.stop_on_entry = false;
+        session.output().event("stopped", Json::object(vec![("reason", reason.into()), ("threadId", THREAD.into()), ("allThreadsStopped", true.into())]));
+
+        //frame ids are the position in `calls` plus one, references index `containers` plus one
+        let mut containers: Vec<Container> = vec![];
+        while let Some(request) = session.receive() {
+            let arguments = request.get("arguments").cloned().unwrap_or(Json::Object(vec![]));
+            let frame = arguments.get("frameId").and_then(|it| it.as_i64())
+                .filter(|it| *it > 0 && *it as usize <= calls.len())
+                .map(|it| it as usize - 1);
+            let resume = match request.get("command").and_then(|it| it.as_str()).unwrap_or("") {
+                "continue" => Some(Resume::Continue),
+                "next" => Some(Resume::StepOver),
+                "stepIn" => Some(Resume::StepIn),
+                "stepOut" => Some(Resume::StepOut),
+                _ => None
+            };
+            if let Some(resume) = resume {
+                let body = match resume {
+                    Resume::Continue => Json::object(vec![("allThreadsContinued", true.into())]),
+                    _ => Json::Null
+                };
+                session.output().respond(&request, body);
+                return resume
+            }
+
+            match request.get("command").and_then(|it| it.as_str()).unwrap_or("") {
+                "stackTrace" => {
+                    let frames: Vec<Json> = calls.iter().enumerate().rev().map(|(i, call)| {
+                        let (source, line) = session.position(runtime.get_function(&call.signature).as_deref(), &call.signature, call.index);
+                        Json::object(vec![
+                            ("id", (i + 1).into()),
+                            ("name", call.signature.as_str().into()),
+                            ("source", source),
+                            ("line", line.into()),
+                            ("column", 1i64.into())
+                        ])
+                    }).collect();
+                    let total = frames.len();
+                    session.output().respond(&request, Json::object(vec![("stackFrames", frames.into()), ("totalFrames", total.into())]))
+                }
+                "scopes" => match frame {
+                    Some(frame) => {
+                        let mut scopes = vec![];
+                        for (name, container) in [("Locals", Container::Locals(frame)), ("Arguments", Container::Arguments(frame)), ("Stack", Container::Stack(frame))] {
+                            containers.push(container);
+                            scopes.push(Json::object(vec![("name", name.into()), ("variablesReference", containers.len().into()), ("expensive", false.into())]));
+                        }
+                        session.output().respond(&request, Json::object(vec![("scopes", scopes.into())]))
+                    }
+                    None => session.output().fail(&request, "Unknown frame".to_string())
+                },
+                "variables" => {
+                    let reference = arguments.get("variablesReference").and_then(|it| it.as_i64()).unwrap_or(0);
+                    if reference < 1 || reference as usize > containers.len() {
+                        session.output().fail(&request, "Unknown variables reference".to_string());
+                        continue
+                    }
+                    let container = containers[reference as usize - 1].clone();
+                    let variables = Paused::variables(runtime, calls, &container, &mut containers);
+                    session.output().respond(&request, Json::object(vec![("variables", variables.into())]))
+                }
+                "evaluate" => {
+                    let call = &calls[frame.unwrap_or(calls.len() - 1)];
+                    let expression = arguments.get("expression").and_then(|it| it.as_str()).unwrap_or("");
+                    match evaluate(&runtime.storage, call, expression) {
+                        Ok(value) => {
+                            let variable = Paused::variable(String::new(), &value, &mut containers);
+                            let body = Json::object(vec![
+                                ("result", variable.get("value").cloned().unwrap_or(Json::Null)),
+                                ("type", variable.get("type").cloned().unwrap_or(Json::Null)),
+                                ("variablesReference", variable.get("variablesReference").cloned().unwrap_or(Json::Null))
+                            ]);
+                            session.output().respond(&request, body)
+                        }
+                        Err(e) => session.output().fail(&request, e)
+                    }
+                }
+                "disconnect" => {
+                    session.output().respond(&request, Json::Null);
+                    session.disconnected = true;
+                    return Resume::Terminate
+                }
+                "terminate" => {
+                    session.output().respond(&request, Json::Null);
+                    return Resume::Terminate
+                }
+                command => if !session.handle_common(runtime, &request, breakpoints) {
+                    session.output().fail(&request, format!("Unsupported request {} while paused", command))
+                }
+            }
+        }
+        //the client is gone, nobody can resume
+        session.disconnected = true;
+        Resume::Terminate
+    }
+}
+
+/// Serves debug adapter requests from `input` until the client disconnects.
+///
+/// `launch` loads `program` and takes the optional `entry`, `args` and `stopOnEntry`,
+/// the entry function starts on `configurationDone` and `exit_code` turns its result into the exit code.
+pub fn serve(
+    mut runtime: Runtime,
+    entry: &str,
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write + Send>,
+    exit_code: fn(&Result<RuntimeObject, RuntimeError>) -> i64
+) {
+    let output = Arc::new(Mutex::new(Output { writer: output, seq: 0 }));
+    //stdout and stdin carry the protocol, scripts print through output events
+    let events = output.clone();
+    runtime.register(dynamic_native_function("std/io/print", move |runtime, args| {
+        let mut output = events.lock().unwrap();
+        for arg in args {
+            let text = format!("{}\n", d_format(arg, &runtime.storage, &FormatOptions::default()));
+            output.event("output", Json::object(vec![("category", "stdout".into()), ("output", text.into())]));
+        }
+        Ok(RuntimeObject::Void)
+    }, Type::Void));
+    runtime.register(dynamic_native_function("std/io/read", |_, _| {
+        Err(RuntimeError::new(ErrorKind::Runtime, "Cannot read stdin while it is used by the debug adapter".to_string()))
+    }, Type::Str));
+
+    let session = Rc::new(RefCell::new(Session { input, output, sources: vec![], stop_on_entry: false, disconnected: false }));
+    let mut breakpoints = Breakpoints::new();
+    let mut entry = entry.to_string();
+    let mut launched = false;
+
+    loop {
+        let request = match session.borrow_mut().receive() {
+            Some(request) => request,
+            None => return
+        };
+        let mut session_ref = session.borrow_mut();
+        let arguments = request.get("arguments").cloned().unwrap_or(Json::Object(vec![]));
+        match request.get("command").and_then(|it| it.as_str()).unwrap_or("") {
+            "initialize" => {
+                let mut output = session_ref.output();
+                output.respond(&request, Json::object(vec![("supportsConfigurationDoneRequest", true.into())]));
+                output.event("initialized", Json::Null);
+            }
+            "launch" => {
+                //a program that fails to load leaves the functions as they were
+                let functions = match arguments.get("program").and_then(|it| it.as_str()) {
+                    Some(program) => match parse_file(program.to_string()) {
+                        Ok(functions) => functions,
+                        Err(e) => {
+                            session_ref.output().fail(&request, format!("Cannot load {}: {}", program, e));
+                            continue
+                        }
+                    },
+                    None => vec![]
+                };
+                if let Err(e) = runtime.register_verified(functions) {
+                    session_ref.output().fail(&request, e.to_string());
+                    continue
+                }
+                if let Some(signature) = arguments.get("entry").and_then(|it| it.as_str()) {
+                    entry = signature.to_string();
+                }
+                if runtime.get_function(&entry).is_none() {
+                    session_ref.output().fail(&request, format!("No entry function {}", entry));
+                    continue
+                }
+                if let Some(args) = arguments.get("args").and_then(|it| it.as_array()) {
+                    runtime.script_args = args.iter().filter_map(|it| it.as_str()).map(|it| it.to_string()).collect();
+                }
+                session_ref.stop_on_entry = arguments.get("stopOnEntry").and_then(|it| it.as_bool()).unwrap_or(false);
+                launched = true;
+                session_ref.output().respond(&request, Json::Null);
+            }
+            "configurationDone" if !launched => session_ref.output().fail(&request, "Expected launch before configurationDone".to_string()),
+            "configurationDone" => {
+                session_ref.output().respond(&request, Json::Null);
+                launched = false;
+                let resume = if session_ref.stop_on_entry { Resume::StepIn } else { Resume::Continue };
+                drop(session_ref);
+
+                //the entry function either takes no arguments or the script arguments as a list
+                let args = match runtime.get_function(&entry).and_then(|it| it.args.clone()) {
+                    Some(args) if args.len() == 1 => vec![RuntimeObject::List(runtime.script_args.iter().cloned().map(RuntimeObject::Str).collect())],
+                    _ => vec![]
+                };
+                runtime.attach_debugger(Debugger::new(Box::new(Paused { session: session.clone() }), breakpoints, resume));
+                let result = runtime.call(&entry, args).and_then(|value| {
+                    runtime.run_event_loop()?;
+                    Ok(value)
+                });
+                breakpoints = runtime.detach_debugger().map(|it| it.breakpoints).unwrap_or_default();
+
+                let session = session.borrow();
+                if session.disconnected {
+                    return
+                }
+                let mut output = session.output();
+                if let Some(e) = result.as_ref().err().filter(|e| !matches!(e.kind, ErrorKind::Exit(_) | ErrorKind::Terminated)) {
+                    output.event("output", Json::object(vec![("category", "stderr".into()), ("output", format!("Uncaught {}\n", e).into())]));
+                }
+                //after a terminate request the program has no exit code
+                if !result.as_ref().is_err_and(|e| e.kind == ErrorKind::Terminated) {
+                    output.event("exited", Json::object(vec![("exitCode", exit_code(&result).into())]));
+                }
+                output.event("terminated", Json::Null);
+            }
+            "disconnect" | "terminate" => {
+                session_ref.output().respond(&request, Json::Null);
+                return
+            }
+            command => if !session_ref.handle_common(&runtime, &request, &mut breakpoints) {
+                session_ref.output().fail(&request, format!("Unsupported request {}", command))
+            }
+        }
+    }
+}
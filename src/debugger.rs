@@ -1,46 +1,155 @@
-use std::{io::stdin, collections::HashMap};
-use crate::{Function, runtime::{RuntimeObject, execute_std, Runtime}, RuntimeConfig};
+use std::io::{stdin, stdout, Write};
+use dscript_runtime::runtime::debug::{describe, evaluate, flatten, Breakpoints, CallFrame, DebugFrontend, Debugger, Resume};
+use dscript_runtime::runtime::error::{ErrorKind, RuntimeError};
+use dscript_runtime::runtime::std_lib::{d_format, FormatOptions};
+use dscript_runtime::runtime::{RuntimeObject, Runtime};
 
-pub fn debug_shell(functions: Vec<Function>) {
-    let stdin = stdin();
-    let debugger = Debugger {functions, runtime: Runtime::new(), variables: HashMap::new() };
-    loop {
-        let mut raw = String::new();
-        stdin.read_line(&mut raw);
-        let words: Vec<&str> = raw.replace("(", " ( ").replace(")", " ) ").split(" ").collect();
+const HELP: &str = "\
+break <signature> <index>   pause before an instruction, see list for the indices
+delete <signature> <index>  remove a breakpoint
+breakpoints                 list the breakpoints
+list [signature]            show the instructions of a function, the paused one by default
+run                         start the entry function, step starts it paused
+continue, c                 run to the next breakpoint
+step, s                     run one instruction, entering called functions
+next, n                     run one instruction of this function
+out, o                      run until this function returned
+stack                       operand stack of the paused function, top last
+locals                      arguments and variables of the paused function
+backtrace, bt               running functions, innermost first
+print <expression>          evaluate e.g. `load x getProp name` or `loadArg 0`
+quit, q                     stop the program";
+
+/// Command line debugger, breakpoints can be set before the entry function is started with `run`.
+/// Returns once the input ends or on `quit`, which stops a running program through the debugger instead of exiting.
+pub fn debug_shell(mut runtime: Runtime, entry: &str, args: Vec<RuntimeObject>) {
+    println!("Debugging {}, type help for the commands", entry);
+    let mut breakpoints = Breakpoints::new();
+    while let Some(words) = read_command() {
+        let resume = match words[0].as_str() {
+            "run" => Resume::Continue,
+            "step" | "s" => Resume::StepIn,
+            "quit" | "q" => return,
+            _ => {
+                command(&runtime, &[], &mut breakpoints, &words);
+                continue
+            }
+        };
 
-        let mut i: usize = 0;
-        
+        runtime.attach_debugger(Debugger::new(Box::new(Shell), breakpoints, resume));
+        let result = runtime.call(entry, args.clone()).and_then(|value| {
+            runtime.run_event_loop()?;
+            Ok(value)
+        });
+        breakpoints = runtime.detach_debugger().map(|it| it.breakpoints).unwrap_or_default();
+        match result {
+            Ok(value) => println!("{} returned {}", entry, value),
+            Err(RuntimeError { kind: ErrorKind::Exit(code), .. }) => println!("{} exited with code {}", entry, code),
+            Err(RuntimeError { kind: ErrorKind::Terminated, .. }) => return println!("{} was stopped", entry),
+            Err(e) => println!("Uncaught {}", e)
+        }
     }
 }
 
-struct Debugger {
-    functions: Vec<Function>,
-    variables: HashMap<String, RuntimeObject>,
-    runtime: Runtime
-}
+struct Shell;
 
-impl Debugger {
-    fn parse_args(&mut self, i: usize, words: &Vec<&str>) -> (usize, RuntimeObject) {
-        match words[i] {
-            "store" => {
-                i+=1;
-                let name = words[i];
-                (i,_) = self.parse_args(i, words);
-                (i, RuntimeObject::Void)
+impl DebugFrontend for Shell {
+    fn paused(&mut self, runtime: &mut Runtime, calls: &[CallFrame], breakpoints: &mut Breakpoints) -> Resume {
+        let call = calls.last().unwrap();
+        let instruction = runtime.get_function(&call.signature)
+            .and_then(|function| flatten(&function.instructions).get(call.index).map(|(_, instruction)| describe(instruction)))
+            .unwrap_or_default();
+        println!("Paused in {} at {}: {}", call.signature, call.index, instruction);
+
+        while let Some(words) = read_command() {
+            match words[0].as_str() {
+                "continue" | "c" => return Resume::Continue,
+                "step" | "s" => return Resume::StepIn,
+                "next" | "n" => return Resume::StepOver,
+                "out" | "o" => return Resume::StepOut,
+                "quit" | "q" => return Resume::Terminate,
+                "run" => println!("Already running"),
+                _ => command(runtime, calls, breakpoints, &words)
             }
-            "exec" => {
-                i+=1;
-                let sig = words[i];
-                match self.runtime.execute(&self.functions, sig, vec![]) {
-                    Ok(v) => (i, v),
-                    Err(e) => {
-                        println!("Error: {}", e);
-                        (i, RuntimeObject::Void)
-                    }
-                }
+        }
+        Resume::Continue
+    }
+}
+
+fn read_command() -> Option<Vec<String>> {
+    loop {
+        print!("> ");
+        let _ = stdout().flush();
+        let mut raw = String::new();
+        if stdin().read_line(&mut raw).unwrap_or(0) == 0 {
+            return None
+        }
+        let words: Vec<String> = raw.split_whitespace().map(|it| it.to_string()).collect();
+        if !words.is_empty() {
+            return Some(words)
+        }
+    }
+}
+
+//commands that do not resume, `calls` is empty while the program is not running
+fn command(runtime: &Runtime, calls: &[CallFrame], breakpoints: &mut Breakpoints, words: &[String]) {
+    let paused = calls.last();
+    match (words[0].as_str(), paused) {
+        ("break" | "delete", _) => match (words.get(1), words.get(2).and_then(|it| it.parse::<usize>().ok())) {
+            (Some(signature), Some(index)) if words[0] == "break" => match runtime.get_function(signature) {
+                Some(function) if index < flatten(&function.instructions).len() => breakpoints.add(signature, index),
+                Some(_) => println!("{} has no instruction {}", signature, index),
+                None => println!("No function {}", signature)
+            },
+            (Some(signature), Some(index)) => if !breakpoints.remove(signature, index) {
+                println!("No breakpoint at {} {}", signature, index)
+            },
+            _ => println!("Expected {} <signature> <index>", words[0])
+        },
+        ("breakpoints", _) => breakpoints.list().iter().for_each(|(signature, index)| println!("{} {}", signature, index)),
+        ("list", _) => {
+            let signature = match (words.get(1), paused) {
+                (Some(signature), _) => signature.as_str(),
+                (None, Some(call)) => call.signature.as_str(),
+                (None, None) => return println!("Expected list <signature>")
+            };
+            let function = match runtime.get_function(signature) {
+                Some(function) => function,
+                None => return println!("No function {}", signature)
+            };
+            for (index, (depth, instruction)) in flatten(&function.instructions).into_iter().enumerate() {
+                let current = paused.is_some_and(|it| it.signature == signature && it.index == index);
+                let breakpoint = breakpoints.contains(signature, index);
+                println!(
+                    "{}{} {:>3} {}{}",
+                    if current { ">" } else { " " },
+                    if breakpoint { "*" } else { " " },
+                    index,
+                    "  ".repeat(depth),
+                    describe(instruction)
+                );
             }
         }
+        ("help", _) => println!("{}", HELP),
+        ("stack" | "locals" | "backtrace" | "bt" | "print" | "p", None) => println!("The program is not paused"),
+        ("stack", Some(call)) => call.stack.iter().for_each(|value| println!("{}", show(runtime, value))),
+        ("locals", Some(call)) => {
+            call.args.iter().enumerate().for_each(|(i, value)| println!("arg {} = {}", i, show(runtime, value)));
+            call.variables.iter().for_each(|(name, value)| println!("{} = {}", name, show(runtime, value)));
+        }
+        ("backtrace" | "bt", Some(_)) => calls.iter().rev().for_each(|call| println!("{} at {}", call.signature, call.index)),
+        ("print" | "p", Some(call)) => match evaluate(&runtime.storage, call, &words[1..].join(" ")) {
+            Ok(value) => println!("{}", show(runtime, &value)),
+            Err(e) => println!("{}", e)
+        },
+        (other, _) => println!("Unknown command {}, type help for the commands", other)
     }
 }
 
+//containers one level deep on one line, strings quoted so "1" and 1 look different
+fn show(runtime: &Runtime, value: &RuntimeObject) -> String {
+    match value {
+        RuntimeObject::Str(s) => format!("{:?}", s),
+        other => d_format(other, &runtime.storage, &FormatOptions { max_depth: 1, one_line: true })
+    }
+}
@@ -0,0 +1,126 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+
+use crate::parsing::{parse_bytecode, parse_file, parse_source};
+use crate::runtime::error::RuntimeError;
+use crate::runtime::filesystem::FileSystem;
+use crate::runtime::std_lib::get_std_library;
+use crate::runtime::convert::NativeFn;
+use crate::runtime::util::{native_function, typed_function};
+use crate::runtime::{Function, Runtime, RuntimeObject, Type};
+
+#[derive(Debug, PartialEq)]
+pub enum EngineError {
+    Parse(String),
+    FunctionNotFound(String),
+    Runtime(RuntimeError)
+}
+
+impl Display for EngineError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EngineError::Parse(e) => f.write_fmt(format_args!("Parse error: {}", e)),
+            EngineError::FunctionNotFound(sig) => f.write_fmt(format_args!("No function found with name {}", sig)),
+            EngineError::Runtime(e) => f.write_fmt(format_args!("Runtime error: {}", e))
+        }
+    }
+}
+
+impl Error for EngineError {}
+
+impl From<RuntimeError> for EngineError {
+    fn from(e: RuntimeError) -> Self {
+        EngineError::Runtime(e)
+    }
+}
+
+/*
+
+Engine: owns the loaded functions and one isolated runtime
+
+ */
+
+pub struct Engine {
+    runtime: Runtime
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new()
+    }
+}
+
+impl Engine {
+
+    pub fn new() -> Engine {
+        let mut runtime = Runtime::new();
+        runtime.register_all(get_std_library());
+        Engine { runtime }
+    }
+
+    pub fn without_std() -> Engine {
+        Engine { runtime: Runtime::new() }
+    }
+
+    /// Parses and verifies `code` together with the loaded functions, on an error the engine keeps the functions it had.
+    pub fn load_source(&mut self, code: &str) -> Result<(), EngineError> {
+        let functions = parse_source(code).map_err(EngineError::Parse)?;
+        Ok(self.runtime.register_verified(functions)?)
+    }
+
+    pub fn load_file(&mut self, path: &str) -> Result<(), EngineError> {
+        let functions = parse_file(path.to_string()).map_err(EngineError::Parse)?;
+        Ok(self.runtime.register_verified(functions)?)
+    }
+
+    pub fn load_bytecode(&mut self, bytes: &[u8]) -> Result<(), EngineError> {
+        let functions = parse_bytecode(bytes).map_err(EngineError::Parse)?;
+        Ok(self.runtime.register_verified(functions)?)
+    }
+
+    /// Adds a function, replacing any function that already uses its signature.
+    pub fn register(&mut self, function: Function) {
+        self.runtime.register(function)
+    }
+
+    pub fn register_all(&mut self, functions: Vec<Function>) {
+        self.runtime.register_all(functions)
+    }
+
+    /// Registers a host closure, it may capture state and receives the runtime it is called from.
+    pub fn register_native<F>(&mut self, signature: &str, args: Vec<Type>, callback: F, return_type: Type)
+        where F: Fn(&mut Runtime, &[RuntimeObject]) -> Result<RuntimeObject, RuntimeError> + Send + Sync + 'static {
+        self.register(native_function(signature, args, callback, return_type))
+    }
+
+    /// Registers a plain rust function, e.g. `engine.register_fn("host/add", |a: f64, b: f64| a + b)`.
+    pub fn register_fn<Args, F: NativeFn<Args>>(&mut self, signature: &str, callback: F) {
+        self.register(typed_function(signature, callback))
+    }
+
+    pub fn has_function(&self, signature: &str) -> bool {
+        self.runtime.get_function(signature).is_some()
+    }
+
+    /// Routes the file natives through `filesystem`, e.g. a `MemoryFileSystem` the host keeps a clone of.
+    pub fn set_filesystem(&mut self, filesystem: impl FileSystem + 'static) {
+        self.runtime.filesystem = Arc::new(filesystem)
+    }
+
+    pub fn runtime(&mut self) -> &mut Runtime {
+        &mut self.runtime
+    }
+
+    pub fn call(&mut self, signature: &str, args: Vec<RuntimeObject>) -> Result<RuntimeObject, EngineError> {
+        if !self.has_function(signature) {
+            return Err(EngineError::FunctionNotFound(signature.to_string()))
+        }
+        Ok(self.runtime.call(signature, args)?)
+    }
+
+    /// Runs timers and promise callbacks the scripts scheduled until none are left.
+    pub fn run_event_loop(&mut self) -> Result<(), EngineError> {
+        Ok(self.runtime.run_event_loop()?)
+    }
+}
@@ -0,0 +1,10 @@
+pub mod runtime;
+pub mod parsing;
+pub mod dap;
+mod engine;
+
+pub use engine::{Engine, EngineError};
+pub use runtime::{Function, Object, Operation, Runtime, RuntimeObject, Type};
+pub use runtime::error::{ErrorKind, RuntimeError};
+pub use runtime::handle::Handle;
+pub use runtime::object_storage::ObjectStorage;
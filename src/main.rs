@@ -1,50 +1,130 @@
 use std::{process::exit, env};
+use std::io::{stdin, stdout, BufReader, Write};
 
-use parsing::parse_file;
-use runtime::execute_std;
+use dscript_runtime::dap;
+use dscript_runtime::parsing::parse_file;
+use dscript_runtime::runtime::error::{ErrorKind, RuntimeError};
+use dscript_runtime::runtime::{self, Function, Runtime, RuntimeObject};
+use dscript_runtime::runtime::filesystem::{DiskFileSystem, FileSystem, MemoryFileSystem, OverlayFileSystem};
+use dscript_runtime::runtime::permissions::Permissions;
+use dscript_runtime::runtime::verifier::verify;
+use std::sync::Arc;
+use debugger::debug_shell;
 
-use crate::runtime::{EqualityCheck, Function, Operation, Type};
 
-
-mod runtime;
-mod parsing;
 mod debugger;
 
+/*
+
+Exit codes:
+  0    the entry function returned, unless it returned an Integer or whole Number which is used instead
+       or the script called std/process/exit with a code
+  1    such a code was outside 0 to 255, the operating system only keeps the lowest 8 bits
+       so it would otherwise wrap around, e.g. 256 would look like success
+  64   invalid command line
+  65   the script could not be loaded, e.g. a parse or verifier error or a missing entry function
+  70   the script failed with an uncaught error, it is printed to stderr
+  101  the interpreter itself crashed
+
+Permissions: scripts get none by default, a denied call fails with a PermissionDenied error
+naming the flag that grants it, --allow-all runs scripts like before permissions existed
+  --allow-read[=path]   reading files, everywhere or below path, can be repeated
+  --allow-write[=path]  creating, writing, renaming and removing files
+  --allow-env           std/env/get and std/env/set
+  --allow-net           network access, no std library function uses the network yet
+  --allow-run           starting programs through std/process
+
+ */
+
+const EXIT_USAGE: i32 = 64;
+const EXIT_LOAD: i32 = 65;
+const EXIT_SCRIPT_ERROR: i32 = 70;
+const EXIT_OUT_OF_RANGE: i32 = 1;
+
 struct RuntimeConfig {
     debug_log: bool,
     debugger: bool,
-    functions: Vec<Function>
+    //serve the debug adapter protocol on stdio instead of running
+    dap: bool,
+    permissions: Permissions,
+    filesystem: Arc<dyn FileSystem>,
+    functions: Vec<Function>,
+    //function to run, main unless --entry is given
+    entry: String,
+    //everything after `--`, passed to the entry function
+    script_args: Vec<String>
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect::<Vec<String>>()[1..].to_vec();
     let config = match parse_command_args(&args) {
         Ok(v) => v,
-        Err(s) => handle_error(s)
+        Err((code, s)) => handle_error(code, s)
     };
     interpret(config);
 }
 
-fn parse_command_args(args: &Vec<String>) -> Result<RuntimeConfig, String> {
+fn parse_command_args(args: &[String]) -> Result<RuntimeConfig, (i32, String)> {
     let mut debug_log = false;
     let mut debugger = false;
+    let mut dap = false;
+    //scripts get no I/O capabilities unless granted with --allow-* flags
+    let mut permissions = Permissions::none();
+    let mut filesystem: Arc<dyn FileSystem> = Arc::new(DiskFileSystem);
     let mut functions = runtime::std_lib::get_std_library();
-    for arg in args {
+    let mut entry = "main".to_string();
+    let (args, script_args) = match args.iter().position(|it| it == "--") {
+        Some(i) => (&args[..i], args[i + 1..].to_vec()),
+        None => (args, vec![])
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
         match arg.as_str() {
             "--debugLog" => debug_log=true,
             "--debug" => debugger = true,
+            "--dap" => dap = true,
+            "--allow-all" => permissions = Permissions::allow_all(),
+            "--allow-read" => permissions.allow_read(None),
+            "--allow-write" => permissions.allow_write(None),
+            "--allow-env" => permissions.env = true,
+            "--allow-net" => permissions.net = true,
+            "--allow-run" => permissions.run = true,
+            flag if flag.starts_with("--allow-read=") => permissions.allow_read(Some(&flag["--allow-read=".len()..])),
+            flag if flag.starts_with("--allow-write=") => permissions.allow_write(Some(&flag["--allow-write=".len()..])),
+            "--fs=disk" => filesystem = Arc::new(DiskFileSystem),
+            //writes stay in memory while reads still see the disk
+            "--fs=overlay" => filesystem = Arc::new(OverlayFileSystem::new()),
+            "--fs=memory" => filesystem = Arc::new(MemoryFileSystem::new()),
+            "--entry" => match args.next() {
+                Some(signature) => entry = signature.to_string(),
+                None => return Err((EXIT_USAGE, "Expected a function signature after --entry".to_string()))
+            },
+            flag if flag.starts_with("--") => return Err((EXIT_USAGE, format!("Unknown flag {}", flag))),
             source => {
-                functions.extend(parse_file(source.to_string())?)
+                functions.extend(parse_file(source.to_string()).map_err(|e| (EXIT_LOAD, e))?)
             }
         };
     };
 
-    Ok(RuntimeConfig {debug_log, debugger, functions})
+    Ok(RuntimeConfig {debug_log, debugger, dap, permissions, filesystem, functions, entry, script_args})
 }
 
-fn handle_error(e: String) -> ! {
-    println!("{}", e);
-    exit(1)
+fn handle_error(code: i32, e: String) -> ! {
+    eprintln!("{}", e);
+    exit(code)
+}
+
+/// Integers and whole Numbers returned by the entry function become the exit code, other values mean success.
+fn exit_code(result: &Result<RuntimeObject, RuntimeError>) -> i32 {
+    let code = match result {
+        Ok(RuntimeObject::Int(code)) => *code,
+        //saturates, huge values end up out of range as well
+        Ok(RuntimeObject::Num(code)) if code.fract() == 0.0 => *code as i64,
+        Ok(_) => 0,
+        Err(RuntimeError { kind: ErrorKind::Exit(code), .. }) => *code as i64,
+        Err(_) => EXIT_SCRIPT_ERROR as i64
+    };
+    u8::try_from(code).map(i32::from).unwrap_or(EXIT_OUT_OF_RANGE)
 }
 
 fn interpret(config: RuntimeConfig) {
@@ -59,10 +139,44 @@ fn interpret(config: RuntimeConfig) {
         println!("\n\n[Start Execution]\n\n");
     }
 
-    if !config.debugger {
-        return execute_std(config.functions, "main");
+    let mut runtime = Runtime::new();
+    runtime.permissions = config.permissions;
+    runtime.filesystem = config.filesystem;
+    runtime.script_args = config.script_args;
+    runtime.register_all(config.functions);
+    if let Err(e) = verify(&runtime) {
+        handle_error(EXIT_LOAD, e.to_string())
+    }
+
+    //the program is loaded by the launch request
+    if config.dap {
+        let code: fn(&Result<RuntimeObject, RuntimeError>) -> i64 = |result| exit_code(result) as i64;
+        return dap::serve(runtime, &config.entry, Box::new(BufReader::new(stdin())), Box::new(stdout()), code)
+    }
+
+    //the entry function either takes no arguments or the script arguments as a list
+    let args = match runtime.get_function(&config.entry) {
+        Some(function) => match &function.args {
+            Some(args) if args.len() == 1 => vec![RuntimeObject::List(runtime.script_args.iter().cloned().map(RuntimeObject::Str).collect())],
+            _ => vec![]
+        },
+        None => handle_error(EXIT_LOAD, format!("No entry function {}", config.entry))
+    };
+    if config.debugger {
+        return debug_shell(runtime, &config.entry, args)
+    }
+
+    //timers and promises scheduled by the entry function keep the script alive
+    let result = runtime.call(&config.entry, args).and_then(|value| {
+        runtime.run_event_loop()?;
+        Ok(value)
+    });
+    match &result {
+        Err(e) if !matches!(e.kind, ErrorKind::Exit(_)) => eprintln!("Uncaught {}", e),
+        _ => {}
     }
-    debug_shell()
+    let _ = stdout().flush();
+    exit(exit_code(&result))
 
 }
 
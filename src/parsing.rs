@@ -1,14 +1,44 @@
 use crate::runtime::Function;
 
-use self::files::{parse_sections, open_text_file};
+use self::files::{parse_sections, open_text_file, open_binary_file, validate_header};
 
 mod files;
 
+/// Functions of a source file, they remember `file` as the path of their source.
 pub fn parse_file(file: String) -> Result<Vec<Function>, String> {
-    let code = open_text_file(file)?;
-    let words = code.replace("\r\n", "\n").replace("\n", " ").split(" ").map(|it| it.to_owned()).collect::<Vec<String>>();
-    match parse_sections(&words) {
+    let code = open_text_file(file.clone())?;
+    let mut functions = parse_source(&code)?;
+    for source in functions.iter_mut().filter_map(|it| it.source.as_mut()) {
+        source.path = Some(file.clone());
+    }
+    Ok(functions)
+}
+
+pub fn parse_source(code: &str) -> Result<Vec<Function>, String> {
+    let mut words = vec![];
+    let mut lines = vec![];
+    for (line, text) in code.lines().enumerate() {
+        for word in text.split(' ').filter(|it| !it.is_empty()) {
+            words.push(word.to_owned());
+            lines.push(line + 1);
+        }
+    }
+    match parse_sections(&words, &lines) {
         Ok(functions) => Ok(functions),
-        Err((message, i)) => Err(format!("{}, occured in {} ({})", message, i, words[i]))
+        Err((message, i)) => Err(format!("{}, occured in {} ({})", message, i, words.get(i).map(|it| it.as_str()).unwrap_or("<eof>")))
     }
-}
\ No newline at end of file
+}
+
+pub fn parse_bytecode(bytes: &[u8]) -> Result<Vec<Function>, String> {
+    if !validate_header(bytes) {
+        return Err("Missing DSCRIPT header in bytecode".to_string())
+    }
+    match String::from_utf8(bytes[7..].to_vec()) {
+        Ok(code) => parse_source(&code),
+        Err(_) => Err("Bytecode is not valid utf-8".to_string())
+    }
+}
+
+pub fn parse_bytecode_file(file: String) -> Result<Vec<Function>, String> {
+    parse_bytecode(&open_binary_file(file)?)
+}
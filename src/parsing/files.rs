@@ -1,18 +1,22 @@
 use std::fs::File;
 use std::io::{BufReader, Read};
 use std::str::FromStr;
-use crate::{EqualityCheck, Operation, Type};
-use crate::runtime::{BinaryOpCode, Function};
+use crate::runtime::{declare_enum, BinaryOpCode, EqualityCheck, Function, MatchCase, MethodSignature, Operation, SourceLines, Type, UnaryOpCode, Variant};
 
 
-#[no_mangle]
-fn open_binary_file(path: String) -> Vec<u8> {
-    BufReader::new(File::open(path).unwrap()).buffer().iter().map(|it| it.clone()).collect()
+pub fn open_binary_file(path: String) -> Result<Vec<u8>, String> {
+    let mut bytes = vec![];
+    match File::open(path) {
+        Ok(f) => {
+            BufReader::new(f).read_to_end(&mut bytes).expect("Cannot read file");
+        },
+        Err(_) => return Err("cannot open file".to_string())
+    };
+    Ok(bytes)
 }
 
-#[no_mangle]
-fn validate_header(bytes: Vec<u8>) -> bool {
-    String::from_utf8(bytes[0..7].to_owned()).unwrap() == "DSCRIPT"
+pub fn validate_header(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"DSCRIPT")
 }
 
 pub fn open_text_file(path: String) -> Result<String, String> {
@@ -30,63 +34,120 @@ fn parse_type_args(str: &str) -> Type {
     match str {
         "std/str" => Type::Str,
         "std/num" => Type::Num,
+        "std/int" => Type::Int,
         "std/bool" => Type::Bool,
-        "std/any" => Type::Void,
-        "std/list" => Type::List(Box::new(Type::Void)),
-        _ => Type::Complex(vec![])
+        "std/any" => Type::Any,
+        "std/void" => Type::Void,
+        "std/list" => Type::List(Box::new(Type::Any)),
+        "std/handle" => Type::Handle(String::new()),
+        "std/map" => Type::Map,
+        "std/result" => Type::Struct("std/result".to_string()),
+        generic if generic.starts_with("std/list<") && generic.ends_with('>') => {
+            Type::List(Box::new(parse_type_args(&generic["std/list<".len()..generic.len() - 1])))
+        }
+        std if std.starts_with("std/") => Type::Complex(vec![]),
+        //resolved when registered, names no loaded source declares accept any object
+        name => Type::Struct(name.to_string())
     }
 }
 
+/// Decimal, or hexadecimal and binary with a `0x` / `0b` prefix, e.g. `-0xff`.
+fn parse_int(word: &str) -> Option<i64> {
+    let (negative, digits) = match word.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, word)
+    };
+    let (radix, digits) = match digits.get(..2) {
+        Some("0x") => (16, &digits[2..]),
+        Some("0b") => (2, &digits[2..]),
+        _ => (10, digits)
+    };
+    //parse with the sign attached so i64::MIN fits
+    let signed = if negative { format!("-{}", digits) } else { digits.to_string() };
+    i64::from_str_radix(&signed, radix).ok()
+}
+
 fn parse_error(message: &str, i: usize) -> (String, usize) {
     (message.to_string(), i)
 }
 
-fn parse_instructions(mut i: usize, words: &Vec<String>) -> Result<(Vec<Operation>, usize), (String, usize)> {
+/// Word `i`, cut off sources end with an error instead of running past the last word.
+fn word(words: &[String], i: usize) -> Result<&str, (String, usize)> {
+    words.get(i).map(|it| it.as_str()).ok_or_else(|| parse_error("unexpected end of input", i))
+}
+
+/// Parses up to the `end` of a block, `positions` gets the word every instruction starts at, nested ones after their parent.
+fn parse_instructions(mut i: usize, words: &[String], positions: &mut Vec<usize>) -> Result<(Vec<Operation>, usize), (String, usize)> {
     let mut instructions = vec![];
 
     loop {
-        let word = words[i.clone()].to_owned();
-        instructions.push(match word.as_str() {
-            "end" => break,
+        positions.push(i);
+        instructions.push(match word(words, i)? {
+            "end" => {
+                positions.pop();
+                break
+            }
             "load" => {
-                let name = words[i+1].to_string();
+                let name = word(words, i+1)?.to_string();
                 i+=1;
                 Operation::LoadVar(name)
             }
             "set" => {
-                let name = words[i+1].to_string();
+                let name = word(words, i+1)?.to_string();
                 i+=1;
                 Operation::SetVar(name)
             }
             "dup" => Operation::Dup,
             "setProp" => {
-                let name = words[i+1].to_string();
+                let name = word(words, i+1)?.to_string();
                 i+=1;
                 Operation::SetProperty(name)
             }
             "getProp" => {
-                let name = words[i+1].to_string();
+                let name = word(words, i+1)?.to_string();
                 i+=1;
                 Operation::GetProperty(name)
             }
             "return" => Operation::Return,
             "binary" => {
-                let op = match words[i+1].as_str() {
+                let op = match word(words, i+1)? {
                     "add" => BinaryOpCode::Add,
                     "sub" => BinaryOpCode::Sub,
                     "mul" => BinaryOpCode::Mul,
                     "div" => BinaryOpCode::Div,
+                    "mod" => BinaryOpCode::Mod,
+                    "pow" => BinaryOpCode::Pow,
+                    "bitAnd" => BinaryOpCode::BitAnd,
+                    "bitOr" => BinaryOpCode::BitOr,
+                    "bitXor" => BinaryOpCode::BitXor,
+                    "shl" => BinaryOpCode::Shl,
+                    "shr" => BinaryOpCode::Shr,
+                    "and" => BinaryOpCode::And,
+                    "or" => BinaryOpCode::Or,
+                    "xor" => BinaryOpCode::Xor,
                     _ => return Err(parse_error("Invalid Binary token", i))
                 };
                 i+=1;
                 Operation::BinaryOp(op)
             },
+            "unary" => {
+                let op = match word(words, i+1)? {
+                    "bitNot" => UnaryOpCode::BitNot,
+                    "not" => UnaryOpCode::Not,
+                    _ => return Err(parse_error("Invalid Unary token", i))
+                };
+                i+=1;
+                Operation::UnaryOp(op)
+            },
             "equality" => {
-                let op = match words[i+1].as_str() {
+                let op = match word(words, i+1)? {
                     "eq" => EqualityCheck::Eq,
                     "neq" => EqualityCheck::Neq,
                     "gt" => EqualityCheck::Gt,
                     "st" => EqualityCheck::St,
+                    "gte" => EqualityCheck::Gte,
+                    "lte" => EqualityCheck::Lte,
+                    "deepEq" => EqualityCheck::DeepEq,
                     _ => return Err(parse_error("Invalid Equality token token", i))
                 };
                 i+=1;
@@ -94,14 +155,21 @@ fn parse_instructions(mut i: usize, words: &Vec<String>) -> Result<(Vec<Operatio
             },
             "loadNum" => {
                 i+=1;
-                match f64::from_str(words[i].as_str()) {
+                match f64::from_str(word(words, i)?) {
                     Ok(f) => Operation::LoadConstNum(f),
                     Err(_) => return Err(parse_error("Expected number after loadNum", i))
                 }
             },
+            "loadInt" => {
+                i+=1;
+                match parse_int(word(words, i)?) {
+                    Some(int) => Operation::LoadConstInt(int),
+                    None => return Err(parse_error("Expected 64 bit integer after loadInt", i))
+                }
+            },
             "loadBool" => {
                 i+=1;
-                Operation::LoadConstBool(match words[i].as_str() {
+                Operation::LoadConstBool(match word(words, i)? {
                     "true" => true,
                     "false" => false,
                     _ => return Err(parse_error("expected bool after loadBool", i))
@@ -109,37 +177,58 @@ fn parse_instructions(mut i: usize, words: &Vec<String>) -> Result<(Vec<Operatio
             },
             "loadString" => {
                 i+=1;
-                let mut str = String::new();
-                while !words[i].ends_with("'") {
-                    str+=words[i].as_str();
+                let mut parts = vec![];
+                while !word(words, i)?.ends_with('\'') {
+                    parts.push(word(words, i)?);
                     i+=1;
                 }
-                str=format!("{} {}", str, words[i].to_string().strip_suffix("'").unwrap());
+                parts.push(word(words, i)?.strip_suffix('\'').unwrap());
+                let str = parts.join(" ");
 
                 Operation::LoadConstString(str)
             }
             "call" => {
                 i+=1;
-                let identifier = words[i].to_string();
+                let identifier = word(words, i)?.to_string();
                 i+=1;
-                match u32::from_str(words[i].as_str()) {
+                match u32::from_str(word(words, i)?) {
                     Ok(v) => Operation::CallFunction {signature: identifier, argc: v},
                     Err(_) => return Err(parse_error("Expected Argc after call signature", i))
                 }
             },
+            "coroutine" => {
+                i+=1;
+                let signature = word(words, i)?.to_string();
+                i+=1;
+                match u32::from_str(word(words, i)?) {
+                    Ok(v) => Operation::NewCoroutine {signature, argc: v},
+                    Err(_) => return Err(parse_error("Expected Argc after coroutine signature", i))
+                }
+            },
+            "yield" => Operation::Yield,
+            "resume" => Operation::Resume,
+            "callMethod" => {
+                i+=1;
+                let name = word(words, i)?.to_string();
+                i+=1;
+                match u32::from_str(word(words, i)?) {
+                    Ok(v) => Operation::CallMethod {name, argc: v},
+                    Err(_) => return Err(parse_error("Expected Argc after method name", i))
+                }
+            },
             "mapArg" => {
                 i+=1;
-                let arg = match u32::from_str(words[i].as_str()) {
+                let arg = match u32::from_str(word(words, i)?) {
                     Ok(v) => v as usize,
                     Err(_) => return Err(parse_error("Expected arg index", i))
                 };
                 i+=1;
-                let name = words[i].to_string();
+                let name = word(words, i)?.to_string();
                 Operation::MapArgTo {arg, name}
             },
             "loadArg" => {
                 i+=1;
-                let arg = match u32::from_str(words[i].as_str()) {
+                let arg = match u32::from_str(word(words, i)?) {
                     Ok(v) => v as usize,
                     Err(_) => return Err(parse_error("Expected arg index", i))
                 };
@@ -147,7 +236,7 @@ fn parse_instructions(mut i: usize, words: &Vec<String>) -> Result<(Vec<Operatio
             },
             "@List" => {
                 i+=1;
-                let arg = match u32::from_str(words[i].as_str()) {
+                let arg = match u32::from_str(word(words, i)?) {
                     Ok(v) => v,
                     Err(_) => return Err(parse_error("Expected arg index", i))
                 };
@@ -157,7 +246,7 @@ fn parse_instructions(mut i: usize, words: &Vec<String>) -> Result<(Vec<Operatio
                 let mut names = vec![];
                 loop {
                     i+=1;
-                    let word2 = words[i].clone();
+                    let word2 = word(words, i)?.to_string();
                     if word2 == "#" {
                         break;
                     }
@@ -166,7 +255,7 @@ fn parse_instructions(mut i: usize, words: &Vec<String>) -> Result<(Vec<Operatio
                 Operation::InitObject { keys: names, template: None }
             },
             "if" => {
-                match parse_scope(i, words) {
+                match parse_scope(i, words, positions) {
                     Ok((ins, j)) => {
                         i=j;
                         Operation::If(ins)
@@ -174,8 +263,26 @@ fn parse_instructions(mut i: usize, words: &Vec<String>) -> Result<(Vec<Operatio
                     Err(e) => return Err(e)
                 }
             },
+            "and" => {
+                match parse_scope(i, words, positions) {
+                    Ok((ins, j)) => {
+                        i=j;
+                        Operation::AndThen(ins)
+                    }
+                    Err(e) => return Err(e)
+                }
+            },
+            "or" => {
+                match parse_scope(i, words, positions) {
+                    Ok((ins, j)) => {
+                        i=j;
+                        Operation::OrElse(ins)
+                    }
+                    Err(e) => return Err(e)
+                }
+            },
             "else" => {
-                match parse_scope(i, words) {
+                match parse_scope(i, words, positions) {
                     Ok((ins, j)) => {
                         i=j;
                         Operation::Else(ins)
@@ -183,14 +290,64 @@ fn parse_instructions(mut i: usize, words: &Vec<String>) -> Result<(Vec<Operatio
                     Err(e) => return Err(e)
                 }
             },
+            "try" => {
+                match parse_scope(i, words, positions) {
+                    Ok((content, j)) => {
+                        i=j+1;
+                        if word(words, i)? != "catch" {
+                            return Err(parse_error("Expected catch after try block", i))
+                        }
+                        i+=1;
+                        let name = word(words, i)?.to_string();
+                        match parse_scope(i, words, positions) {
+                            Ok((catch, j)) => {
+                                i=j;
+                                Operation::Try { content, name, catch }
+                            }
+                            Err(e) => return Err(e)
+                        }
+                    }
+                    Err(e) => return Err(e)
+                }
+            }
+            "match" => {
+                //match Enum case Variant binding ... do ... end ... end, `case _ do` matches the rest
+                let name = word(words, i+1)?.to_string();
+                i+=2;
+                let mut cases = vec![];
+                while words.get(i).map(|it| it.as_str()) == Some("case") {
+                    let variant = match words.get(i+1).map(|it| it.as_str()) {
+                        Some("_") => None,
+                        Some(variant) => Some(variant.to_string()),
+                        None => return Err(parse_error("Expected variant after case", i+1))
+                    };
+                    i+=1;
+                    let mut bindings = vec![];
+                    while words.get(i+1).is_some_and(|it| it != "do") {
+                        i+=1;
+                        bindings.push(word(words, i)?.to_string());
+                    }
+                    match parse_scope(i, words, positions) {
+                        Ok((content, j)) => {
+                            i=j+1;
+                            cases.push(MatchCase { variant, bindings, content })
+                        }
+                        Err(e) => return Err(e)
+                    }
+                }
+                if words.get(i).map(|it| it.as_str()) != Some("end") {
+                    return Err(parse_error("Expected case or end in match", i))
+                }
+                Operation::Match { name, cases }
+            }
             "while" => {
-                match parse_scope(i, words) {
+                match parse_scope(i, words, positions) {
                     Ok((cond, j)) => {
                         i=j;
-                        match parse_scope(i, words) {
+                        match parse_scope(i, words, positions) {
                             Ok((content, j)) => {
                                 i=j;
-                                Operation::While { condition: cond, content: content }
+                                Operation::While { condition: cond, content }
                             }
                             Err(e) => return Err(e)
                         }
@@ -206,50 +363,136 @@ fn parse_instructions(mut i: usize, words: &Vec<String>) -> Result<(Vec<Operatio
     Ok((instructions, i))
 }
 
-fn parse_scope(mut i: usize, words: &Vec<String>) -> Result<(Vec<Operation>, usize), (String, usize)> {
+fn parse_scope(mut i: usize, words: &[String], positions: &mut Vec<usize>) -> Result<(Vec<Operation>, usize), (String, usize)> {
     i+=1;
-    assert_eq!(words[i].to_string(), "do");
+    if word(words, i)? != "do" {
+        return Err(parse_error("Expected do to start a block", i))
+    }
     i+=1;
-    match parse_instructions(i, words) {
+    match parse_instructions(i, words, positions) {
         Ok((ins, j)) => Ok((ins, j)),
-        Err(e) => Err((e.0.to_owned(), e.1.clone()))
+        Err(e) => Err((e.0.to_owned(), e.1))
     }
 }
 
-pub fn parse_sections(words: &Vec<String>) -> Result<Vec<Function>, (String, usize)> {
+//declarations compile to a few instructions that all stand on the line of their keyword
+fn declared_at(line: usize, instructions: usize) -> Option<SourceLines> {
+    Some(SourceLines { path: None, lines: vec![line; instructions] })
+}
+
+/// Sections of a source, `lines` holds the line of every word.
+pub fn parse_sections(words: &[String], lines: &[usize]) -> Result<Vec<Function>, (String, usize)> {
     let mut i: usize = 0;
     let mut functions = vec![];
     while i < words.len() {
-        match words[i].as_str() {
+        let line = lines[i];
+        match word(words, i)? {
             "func" => {
 
                 //signature
-                let signature = words[i+1].clone();
+                let signature = word(words, i+1)?.to_string();
                 i+=2;
 
                 //args
                 let mut args = vec![];
-                while words[i] != "endArgs" {
-                    args.push(parse_type_args(words[i].as_str()));
+                while word(words, i)? != "endArgs" {
+                    args.push(parse_type_args(word(words, i)?));
                     i+=1;
                 }
 
                 i+=1;
 
                 //return type
-                let return_type = parse_type_args(words[i+1].as_str());
+                let return_type = parse_type_args(word(words, i)?);
                 i+=1;
 
                 //instructions
-                match parse_instructions(i, &words) {
+                let mut positions = vec![];
+                match parse_instructions(i, words, &mut positions) {
                     Ok((instructions, j)) => {
                         i=j;
-                        functions.push(Function {signature,args: Some(args), instructions, return_type })
+                        let source = Some(SourceLines { path: None, lines: positions.iter().map(|it| lines[*it]).collect() });
+                        functions.push(Function {signature,args: Some(args), instructions, return_type, source })
                     }
                     Err(e) => return Err(e)
                 }
 
             }
+            "struct" => {
+                //struct Name [implements Interface ...] field type ... end, compiled into a constructor taking the fields in order
+                let name = word(words, i+1)?.to_string();
+                i+=2;
+                let mut interfaces = vec![];
+                let mut fields = vec![];
+                let mut types = vec![];
+                while words.get(i).map(|it| it.as_str()) != Some("end") {
+                    match words.get(i+1) {
+                        Some(interface) if words[i] == "implements" && interface != "end" => interfaces.push(interface.clone()),
+                        Some(tp) if tp != "end" => {
+                            fields.push(words[i].clone());
+                            types.push(parse_type_args(tp));
+                        }
+                        _ => return Err(parse_error("Expected field name and type in struct", i))
+                    }
+                    i+=2;
+                }
+                functions.push(Function {
+                    signature: name.clone(),
+                    args: Some(types),
+                    instructions: vec![Operation::InitStruct { name: name.clone(), fields, interfaces }, Operation::Return],
+                    return_type: Type::Struct(name),
+                    source: declared_at(line, 2)
+                })
+            }
+            "enum" => {
+                //enum Name variant Variant field type ... end, one constructor Name/Variant per variant
+                let name = word(words, i+1)?.to_string();
+                i+=2;
+                let mut variants: Vec<Variant> = vec![];
+                while words.get(i).map(|it| it.as_str()) != Some("end") {
+                    match (word(words, i)?, words.get(i+1), variants.last_mut()) {
+                        ("variant", Some(variant), _) if variant != "end" => variants.push(Variant { name: variant.clone(), fields: vec![] }),
+                        (field, Some(tp), Some(variant)) if tp != "end" => variant.fields.push((field.to_string(), parse_type_args(tp))),
+                        _ => return Err(parse_error("Expected variant or field name and type in enum", i))
+                    }
+                    i+=2;
+                }
+                functions.extend(declare_enum(&name, variants).into_iter().map(|function| Function {
+                    source: declared_at(line, function.instructions.len()),
+                    ..function
+                }));
+            }
+            "interface" => {
+                //interface Name method argtype ... endArgs returntype ... end, the receiver is not listed
+                let name = word(words, i+1)?.to_string();
+                i+=2;
+                let mut methods = vec![];
+                while words.get(i).map(|it| it.as_str()) != Some("end") {
+                    let method = word(words, i)?.to_string();
+                    i+=1;
+                    let mut args = vec![];
+                    while words.get(i).map(|it| it.as_str()) != Some("endArgs") {
+                        match words.get(i) {
+                            Some(tp) if tp != "end" => args.push(parse_type_args(tp)),
+                            _ => return Err(parse_error("Expected endArgs in interface method", i))
+                        }
+                        i+=1;
+                    }
+                    match words.get(i+1) {
+                        Some(tp) if tp != "end" => methods.push(MethodSignature { name: method, args, return_type: parse_type_args(tp) }),
+                        _ => return Err(parse_error("Expected return type of interface method", i+1))
+                    }
+                    i+=2;
+                }
+                //callable as a conformance check, `call Name 1` pushes whether the argument implements it
+                functions.push(Function {
+                    signature: name.clone(),
+                    args: Some(vec![Type::Any]),
+                    instructions: vec![Operation::CheckInterface { name, methods }, Operation::Return],
+                    return_type: Type::Bool,
+                    source: declared_at(line, 2)
+                })
+            }
             _ => return Err(parse_error("Invalid token outside of function)", i))
         }
         i+=1;
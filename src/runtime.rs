@@ -1,17 +1,114 @@
-pub(crate) mod std_lib;
-mod object_storage;
-mod util;
-
+pub mod std_lib;
+pub mod object_storage;
+pub mod util;
+pub mod error;
+pub mod convert;
+pub mod handle;
+pub mod permissions;
+pub mod filesystem;
+pub mod verifier;
+pub mod value;
+pub mod coroutine;
+pub mod event_loop;
+pub mod worker;
+pub mod debug;
+
+use std::cmp::Ordering;
 use std::collections::HashMap;
-use std::fmt::{Display, Formatter, Pointer};
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+use std::sync::Arc;
+use crate::runtime::coroutine::{Coroutine, CoroutineState};
+use crate::runtime::debug::{instruction_index, CallFrame, Debugger};
+use crate::runtime::error::{ErrorKind, RuntimeError};
+use crate::runtime::event_loop::EventLoop;
+use crate::runtime::filesystem::{DiskFileSystem, FileSystem};
+use crate::runtime::handle::Handle;
 use crate::runtime::object_storage::ObjectStorage;
+use crate::runtime::permissions::Permissions;
 use crate::runtime::std_lib::get_std_library;
+use crate::runtime::value::{deep_eq, ValueMap};
+use crate::runtime::verifier::verify;
+
+/// Plain native used by the std library, it only sees its arguments and the object storage.
+pub type LibraryCallback = fn(&[RuntimeObject], &mut ObjectStorage) -> Result<RuntimeObject, String>;
+
+/// Native that may capture host state and gets the whole runtime to allocate objects or call script functions.
+pub type NativeCallback = Arc<dyn Fn(&mut Runtime, &[RuntimeObject]) -> Result<RuntimeObject, RuntimeError> + Send + Sync>;
 
 pub enum BinaryOpCode {
     Add,
     Sub,
     Mul,
-    Div
+    Div,
+    Mod,
+    Pow,
+    //integer only
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    //bool only
+    And,
+    Or,
+    Xor
+}
+
+impl Display for BinaryOpCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            BinaryOpCode::Add => "add",
+            BinaryOpCode::Sub => "sub",
+            BinaryOpCode::Mul => "mul",
+            BinaryOpCode::Div => "div",
+            BinaryOpCode::Mod => "mod",
+            BinaryOpCode::Pow => "pow",
+            BinaryOpCode::BitAnd => "bitAnd",
+            BinaryOpCode::BitOr => "bitOr",
+            BinaryOpCode::BitXor => "bitXor",
+            BinaryOpCode::Shl => "shl",
+            BinaryOpCode::Shr => "shr",
+            BinaryOpCode::And => "and",
+            BinaryOpCode::Or => "or",
+            BinaryOpCode::Xor => "xor"
+        })
+    }
+}
+
+impl BinaryOpCode {
+    fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOpCode::Add => "+",
+            BinaryOpCode::Sub => "-",
+            BinaryOpCode::Mul => "*",
+            BinaryOpCode::Div => "/",
+            BinaryOpCode::Mod => "mod",
+            BinaryOpCode::Pow => "**",
+            BinaryOpCode::BitAnd => "&",
+            BinaryOpCode::BitOr => "|",
+            BinaryOpCode::BitXor => "^",
+            BinaryOpCode::Shl => "<<",
+            BinaryOpCode::Shr => ">>",
+            BinaryOpCode::And => "&&",
+            BinaryOpCode::Or => "||",
+            BinaryOpCode::Xor => "xor"
+        }
+    }
+}
+
+pub enum UnaryOpCode {
+    BitNot,
+    Not
+}
+
+impl Display for UnaryOpCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            UnaryOpCode::BitNot => "bitNot",
+            UnaryOpCode::Not => "not"
+        })
+    }
 }
 
 
@@ -19,23 +116,79 @@ pub enum EqualityCheck {
     Eq,
     Neq,
     Gt,
-    St
+    St,
+    Gte,
+    Lte,
+    //compares objects by their fields instead of their identity
+    DeepEq
+}
+
+impl Display for EqualityCheck {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            EqualityCheck::Eq => "eq",
+            EqualityCheck::Neq => "neq",
+            EqualityCheck::Gt => "gt",
+            EqualityCheck::St => "st",
+            EqualityCheck::Gte => "gte",
+            EqualityCheck::Lte => "lte",
+            EqualityCheck::DeepEq => "deepEq"
+        })
+    }
+}
+
+impl EqualityCheck {
+    fn symbol(&self) -> &'static str {
+        match self {
+            EqualityCheck::Eq => "==",
+            EqualityCheck::Neq => "!=",
+            EqualityCheck::Gt => ">",
+            EqualityCheck::St => "<",
+            EqualityCheck::Gte => ">=",
+            EqualityCheck::Lte => "<=",
+            EqualityCheck::DeepEq => "~="
+        }
+    }
 }
 
 pub enum Operation {
     LoadConstNum(f64),      //d
+    LoadConstInt(i64),
     LoadConstString(String),//TODO
     LoadConstBool(bool),    //d
     CallFunction {signature: String, argc: u32},    //D
+    //calls `<receiver type>:name`, the receiver being the top argument
+    CallMethod {name: String, argc: u32},
     Return,                 //d
     Dup,                    //d
     BinaryOp(BinaryOpCode), //d
+    UnaryOp(UnaryOpCode),
     EqualityCheck(EqualityCheck),   //d
-    Native { callback: fn(&Vec<RuntimeObject>, &mut ObjectStorage) -> Result<RuntimeObject, String> },
+    Native { callback: NativeCallback },
     If(Vec<Operation>),     //d
+    //short-circuit forms, the block only runs if the popped bool does not decide the result
+    AndThen(Vec<Operation>),
+    OrElse(Vec<Operation>),
     Else(Vec<Operation>),   //d
     While { condition: Vec<Operation>, content: Vec<Operation> },             //d
+    Try { content: Vec<Operation>, name: String, catch: Vec<Operation> },
     InitObject { keys: Vec<String>, template: Option<HashMap<String, Type>> },//d
+    //body of a struct constructor, fills the fields from the arguments in order
+    InitStruct { name: String, fields: Vec<String>, interfaces: Vec<String> },
+    //pops the arguments like a call and pushes a coroutine handle of the function
+    NewCoroutine { signature: String, argc: u32 },
+    //suspends the running coroutine, the popped value is the result of its `resume`
+    Yield,
+    //pops a coroutine and runs it to its next yield
+    Resume,
+    //body of an interface, pushes whether the first argument conforms to it
+    CheckInterface { name: String, methods: Vec<MethodSignature> },
+    //body of a variant constructor, like InitStruct with the variant as tag
+    InitVariant { name: String, variant: String, fields: Vec<String> },
+    //body of an enum, pushes the variant name of the first argument
+    VariantOf { name: String, variants: Vec<Variant> },
+    //pops an enum value and runs the block of its variant, `None` is the `_` case
+    Match { name: String, cases: Vec<MatchCase> },
     InitList { init_push: u32 },                                              //d
     SetProperty(String),    //d
     GetProperty(String),    //d
@@ -49,23 +202,44 @@ impl Display for Operation {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Operation::LoadConstNum(n) => f.write_fmt(format_args!("LoadConstNum({})", n)),
+            Operation::LoadConstInt(n) => f.write_fmt(format_args!("LoadConstInt({})", n)),
             Operation::LoadConstString(_) => f.write_str("LoadConstString"),
             Operation::LoadConstBool(_) => f.write_str("LoadConstBool"),
             Operation::CallFunction { signature, argc } => f.write_fmt(format_args!("FunctionCall({}, {})", signature, argc)),
+            Operation::CallMethod { name, argc } => f.write_fmt(format_args!("MethodCall({}, {})", name, argc)),
             Operation::Return => f.write_str("Return"),
             Operation::Dup => f.write_str("Dup"),
-            Operation::BinaryOp(_) => f.write_str("Binary"),
-            Operation::EqualityCheck(_) => f.write_str("EqualityCheck"),
+            //binary operations put the top of the stack on the left, comparisons the value below it
+            Operation::BinaryOp(op) => f.write_fmt(format_args!("Binary({}: top {} below)", op, op.symbol())),
+            Operation::UnaryOp(op) => f.write_fmt(format_args!("Unary({})", op)),
+            Operation::EqualityCheck(op) => f.write_fmt(format_args!("EqualityCheck({}: below {} top)", op, op.symbol())),
             Operation::Native { callback: _callback } => f.write_str("Native"),
             Operation::If(content) => f.write_fmt(format_args!("IfDo({})", content.iter().fold(String::new(), |a, b| format!("{}, {}", a, b)))),
+            Operation::AndThen(content) => f.write_fmt(format_args!("AndDo({})", content.iter().fold(String::new(), |a, b| format!("{}, {}", a, b)))),
+            Operation::OrElse(content) => f.write_fmt(format_args!("OrDo({})", content.iter().fold(String::new(), |a, b| format!("{}, {}", a, b)))),
             Operation::Else(content) => f.write_fmt(format_args!("ElseDo({})", content.iter().fold(String::new(), |a, b| format!("{}, {}", a, b)))),
             Operation::While { condition, content } => f.write_fmt(
                 format_args!(
-                    "While({})do({})", 
-                    condition.iter().fold(String::new(), |first, a| format!("{}, {}", first, a)), 
+                    "While({})do({})",
+                    condition.iter().fold(String::new(), |first, a| format!("{}, {}", first, a)),
                     content.iter().fold(String::new(), |first, a| format!("{}, {}", first, a))
                 )),
+            Operation::Try { content, name, catch } => f.write_fmt(
+                format_args!(
+                    "Try({})catch {}({})",
+                    content.iter().fold(String::new(), |first, a| format!("{}, {}", first, a)),
+                    name,
+                    catch.iter().fold(String::new(), |first, a| format!("{}, {}", first, a))
+                )),
             Operation::InitObject { keys: _, template: _ } => f.write_fmt(format_args!("InitObject")),
+            Operation::InitStruct { name, fields, interfaces } => f.write_fmt(format_args!("InitStruct({} {} implements [{}])", name, fields.join(" "), interfaces.join(", "))),
+            Operation::InitVariant { name, variant, fields } => f.write_fmt(format_args!("InitVariant({}/{} {})", name, variant, fields.join(" "))),
+            Operation::VariantOf { name, variants } => f.write_fmt(format_args!("VariantOf({} {})", name, variants.iter().map(|it| it.name.as_str()).collect::<Vec<_>>().join(" | "))),
+            Operation::Match { name, cases } => f.write_fmt(format_args!("Match({} {})", name, cases.iter().map(|it| it.variant.as_deref().unwrap_or("_")).collect::<Vec<_>>().join(" | "))),
+            Operation::NewCoroutine { signature, argc } => f.write_fmt(format_args!("NewCoroutine({}, {})", signature, argc)),
+            Operation::Yield => f.write_str("Yield"),
+            Operation::Resume => f.write_str("Resume"),
+            Operation::CheckInterface { name, methods } => f.write_fmt(format_args!("CheckInterface({} {})", name, methods.iter().map(|it| it.to_string()).collect::<Vec<_>>().join(", "))),
             Operation::InitList { init_push } => f.write_fmt(format_args!("List({})", init_push)),
             Operation::SetProperty(s) => f.write_fmt(format_args!("SetProperty({})", s)),
             Operation::GetProperty(s) => f.write_fmt(format_args!("GetProperty({})", s)),
@@ -77,34 +251,30 @@ impl Display for Operation {
     }
 }
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone)]
 #[derive(Debug)]
 pub enum Type {
     Num,
+    Int,
     Str,
     Bool,
     List(Box<Type>),
+    Map,
     Complex(Vec<Type>),
+    //opaque host data, an empty tag accepts any handle
+    Handle(String),
+    //object created by the constructor of a declared struct
+    Struct(String),
+    //std/any, accepts every value and is never checked
+    Any,
     Void
 }
 
-impl Type {
-    fn to_string(&self) -> String {
-        match self {
-            Type::Num => "Number".to_string(),
-            Type::Str => "String".to_string(),
-            Type::Bool => "Boolean".to_string(),
-            Type::List(tp) => format!("List<{}>", tp.as_ref().to_string()),
-            Type::Complex(_) => "Complex".to_string(),
-            Type::Void => "Void".to_string()
-        }
-    }
-}
-
 impl Display for Type {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Type::Num => f.write_str("Number"),
+            Type::Int => f.write_str("Integer"),
             Type::Str => f.write_str("String"),
             Type::Bool => f.write_str("Boolean"),
             Type::List(tp) => {
@@ -112,19 +282,103 @@ impl Display for Type {
                 tp.as_ref().fmt(f).unwrap();
                 f.write_str(">")
             },
+            Type::Map => f.write_str("Map"),
             Type::Complex(_) => {
                 f.write_str("Complex")
             }
+            Type::Handle(tag) => f.write_fmt(format_args!("Handle<{}>", tag)),
+            Type::Struct(name) => f.write_str(name),
+            Type::Any => f.write_str("Any"),
             Type::Void => f.write_str("Void")
         }
     }
 }
 
 pub struct Function {
-    pub(crate) signature: String,
-    pub(crate) args: Option<Vec<Type>>,
-    pub(crate) instructions: Vec<Operation>,
-    pub(crate) return_type: Type
+    pub signature: String,
+    pub args: Option<Vec<Type>>,
+    pub instructions: Vec<Operation>,
+    pub return_type: Type,
+    //where the parser found the function, None for functions built in rust
+    pub source: Option<SourceLines>
+}
+
+/// Position of a parsed function, for debuggers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SourceLines {
+    //file the function was loaded from, None for sources given as text
+    pub path: Option<String>,
+    //1-based line of every instruction, in the order of `debug::flatten`
+    pub lines: Vec<usize>
+}
+
+impl Function {
+
+    pub fn is_native(&self) -> bool {
+        matches!(self.instructions.first(), Some(Operation::Native { .. }))
+    }
+
+    /// Type values of `declared` are checked against, host natives keep using Void for any value as they did before std/any.
+    pub fn checked_type(&self, declared: &Type) -> Type {
+        match declared {
+            Type::Void if self.is_native() => Type::Any,
+            Type::List(element) if self.is_native() => Type::List(Box::new(self.checked_type(element))),
+            other => other.clone()
+        }
+    }
+}
+
+/// Method an interface requires, the receiver is implicit and not part of `args`.
+pub struct MethodSignature {
+    pub name: String,
+    pub args: Vec<Type>,
+    pub return_type: Type
+}
+
+impl Display for MethodSignature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let args: Vec<String> = self.args.iter().map(|it| it.to_string()).collect();
+        f.write_fmt(format_args!("{}({}) -> {}", self.name, args.join(", "), self.return_type))
+    }
+}
+
+pub struct Variant {
+    pub name: String,
+    pub fields: Vec<(String, Type)>
+}
+
+pub struct MatchCase {
+    //None matches every variant
+    pub variant: Option<String>,
+    //locals the payload fields are bound to, in declaration order
+    pub bindings: Vec<String>,
+    pub content: Vec<Operation>
+}
+
+/// An enum is a function `name` returning the variant of a value and a constructor `name/variant` per variant.
+pub fn declare_enum(name: &str, variants: Vec<Variant>) -> Vec<Function> {
+    let mut functions: Vec<Function> = variants.iter().map(|variant| Function {
+        signature: format!("{}/{}", name, variant.name),
+        args: Some(variant.fields.iter().map(|(_, tp)| tp.clone()).collect()),
+        instructions: vec![
+            Operation::InitVariant {
+                name: name.to_string(),
+                variant: variant.name.clone(),
+                fields: variant.fields.iter().map(|(field, _)| field.clone()).collect()
+            },
+            Operation::Return
+        ],
+        return_type: Type::Struct(name.to_string()),
+        source: None
+    }).collect();
+    functions.push(Function {
+        signature: name.to_string(),
+        args: Some(vec![Type::Struct(name.to_string())]),
+        instructions: vec![Operation::VariantOf { name: name.to_string(), variants }, Operation::Return],
+        return_type: Type::Str,
+        source: None
+    });
+    functions
 }
 
 impl PartialEq for Function {
@@ -135,13 +389,68 @@ impl PartialEq for Function {
 
 /*
 
+Frames: blocks run in frames on an explicit stack instead of recursing, so they can be suspended
+
+ */
+
+/// Program counter and operand stack of a running block.
+pub struct Frame {
+    //(instruction, block) per level from the function body, e.g. the catch of a try is block 1
+    path: Vec<(usize, usize)>,
+    pc: usize,
+    stack: Vec<RuntimeObject>
+}
+
+impl Frame {
+
+    fn body() -> Frame {
+        Frame { path: vec![], pc: 0, stack: vec![] }
+    }
+
+    //block of the instruction the parent just executed
+    fn block(parent: &Frame, block: usize) -> Frame {
+        let mut path = parent.path.clone();
+        path.push((parent.pc - 1, block));
+        Frame { path, pc: 0, stack: vec![] }
+    }
+}
+
+enum Step {
+    Return(RuntimeObject),
+    Yield(RuntimeObject)
+}
+
+//what the frame machine does after an instruction
+enum Control {
+    Next,
+    Enter(usize),
+    Leave(RuntimeObject),
+    Yield(RuntimeObject)
+}
+
+fn block_at<'a>(body: &'a [Operation], path: &[(usize, usize)]) -> &'a [Operation] {
+    path.iter().fold(body, |code, (index, block)| match &code[*index] {
+        Operation::If(content) | Operation::Else(content) | Operation::AndThen(content) | Operation::OrElse(content) => content,
+        Operation::While { condition, content } => if *block == 0 { condition } else { content },
+        Operation::Try { content, catch, .. } => if *block == 0 { content } else { catch },
+        Operation::Match { cases, .. } => &cases[*block].content,
+        instruction => unreachable!("{} has no blocks", instruction)
+    })
+}
+
+/*
+
 Object definition:
 
  */
 
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct Object {
     id: usize,
+    //name of the struct or enum it was created as, untyped objects have none
+    type_name: Option<Rc<str>>,
+    //variant of an enum value
+    variant: Option<Rc<str>>
     //values: HashMap<String, RuntimeObject>,
 }
 
@@ -153,9 +462,30 @@ impl PartialEq for Object {
 
 impl Object {
 
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn type_name(&self) -> Option<&str> {
+        self.type_name.as_deref()
+    }
+
+    pub fn variant(&self) -> Option<&str> {
+        self.variant.as_deref()
+    }
+
+    /// e.g. `Point` for structs and `Shape/Circle` for enum values.
+    pub fn display_name(&self) -> String {
+        match (self.type_name(), self.variant()) {
+            (Some(name), Some(variant)) => format!("{}/{}", name, variant),
+            (name, _) => name.unwrap_or("Object").to_string()
+        }
+    }
+
     fn get_signature(&self) -> String {
         format!(
-            "Object<!{}>",
+            "{}<!{}>",
+            self.display_name(),
             /*self.values.iter().fold(
                 String::new(),
                 |str, (str2, rto)| format!("{}, {}: {}", str, str2, rto.get_type().to_string())
@@ -172,14 +502,17 @@ Runtime Object
 
  */
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Debug)]
 pub enum RuntimeObject {
     Object(Object),
     //Function(Function),
     Num(f64),
+    Int(i64),
     List(Vec<RuntimeObject>),
+    Map(ValueMap),
     Str(String),
     Bool(bool),
+    Handle(Handle),
     Void
 }
 
@@ -187,16 +520,35 @@ impl Display for RuntimeObject {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             RuntimeObject::Num(n) => f.write_str(n.to_string().as_str()),
+            RuntimeObject::Int(n) => f.write_str(n.to_string().as_str()),
             RuntimeObject::Bool(b) => f.write_str(b.to_string().as_str()),
             RuntimeObject::Str(s) => f.write_str(s.as_str()),
             RuntimeObject::Void => f.write_str("Void"),
             RuntimeObject::List(vec) => {
                 f.write_str("[")?;
-                vec.fmt(f)?;
+                for (i, item) in vec.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    item.fmt(f)?;
+                }
                 f.write_str("]")
             }
+            RuntimeObject::Map(map) => {
+                f.write_str("{")?;
+                for (i, (key, value)) in map.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    f.write_fmt(format_args!("{}: {}", key, value))?;
+                }
+                f.write_str("}")
+            }
             RuntimeObject::Object(o) => {
-                o.fmt(f)
+                f.write_str(o.get_signature().as_str())
+            }
+            RuntimeObject::Handle(h) => {
+                f.write_str(h.get_signature().as_str())
             }
             //RuntimeObject::Function(_) => f.write_str("Function(="),
         }
@@ -207,12 +559,15 @@ impl Display for RuntimeObject {
 impl Clone for RuntimeObject {
     fn clone(&self) -> Self {
         match self {
-            RuntimeObject::Num(num) => RuntimeObject::Num(num.clone()),
+            RuntimeObject::Num(num) => RuntimeObject::Num(*num),
+            RuntimeObject::Int(int) => RuntimeObject::Int(*int),
             RuntimeObject::Str(str) => RuntimeObject::Str(str.clone()),
-            RuntimeObject::Bool(bool) => RuntimeObject::Bool(bool.clone()),
+            RuntimeObject::Bool(bool) => RuntimeObject::Bool(*bool),
             RuntimeObject::Void => RuntimeObject::Void,
             RuntimeObject::Object(o) => RuntimeObject::Object(o.clone()),
-            RuntimeObject::List(l) => RuntimeObject::List(l.iter().map(|it| it.clone()).collect())
+            RuntimeObject::Handle(h) => RuntimeObject::Handle(h.clone()),
+            RuntimeObject::List(l) => RuntimeObject::List(l.to_vec()),
+            RuntimeObject::Map(m) => RuntimeObject::Map(m.clone())
             //RuntimeObject::Function(f) => RuntimeObject::Function(f.clone())
         }
     }
@@ -220,45 +575,189 @@ impl Clone for RuntimeObject {
 
 
 impl RuntimeObject {
-    fn get_type(&self) -> Type {
+    pub fn get_type(&self) -> Type {
         match self {
-            RuntimeObject::Object(_) => {
-                Type::Complex(vec![])
+            RuntimeObject::Object(o) => match o.type_name() {
+                Some(name) => Type::Struct(name.to_string()),
+                None => Type::Complex(vec![])
             }
             RuntimeObject::Num(_) => Type::Num,
+            RuntimeObject::Int(_) => Type::Int,
             RuntimeObject::Str(_) => Type::Str,
             RuntimeObject::Bool(_) => Type::Bool,
+            RuntimeObject::Handle(h) => Type::Handle(h.tag().to_string()),
             RuntimeObject::Void => Type::Void,
-            RuntimeObject::List(_) => Type::List(Box::new(Type::Void))
+            RuntimeObject::List(items) => Type::List(Box::new(element_type(items))),
+            RuntimeObject::Map(_) => Type::Map
+        }
+    }
+}
+
+impl RuntimeObject {
+    /// Prefix of the methods of this value, e.g. `str` for `str:to_upper` or the struct name for structs.
+    pub fn method_prefix(&self) -> String {
+        match self {
+            RuntimeObject::Object(o) => o.type_name().unwrap_or("object").to_string(),
+            RuntimeObject::Num(_) => "num".to_string(),
+            RuntimeObject::Int(_) => "int".to_string(),
+            RuntimeObject::Str(_) => "str".to_string(),
+            RuntimeObject::Bool(_) => "bool".to_string(),
+            RuntimeObject::List(_) => "list".to_string(),
+            RuntimeObject::Map(_) => "map".to_string(),
+            RuntimeObject::Handle(h) => h.tag().to_string(),
+            RuntimeObject::Void => "void".to_string()
+        }
+    }
+}
+
+/// Common type of all elements, Any if they differ or the list is empty.
+fn element_type(items: &[RuntimeObject]) -> Type {
+    let mut types = items.iter().map(|it| it.get_type());
+    let first = match types.next() {
+        Some(tp) => tp,
+        None => return Type::Any
+    };
+    types.try_fold(first, common_type).unwrap_or(Type::Any)
+}
+
+fn common_type(a: Type, b: Type) -> Option<Type> {
+    match (a, b) {
+        (a, b) if a == b => Some(a),
+        //an empty list fits a list of any element type
+        (Type::List(a), Type::List(b)) if *a == Type::Any => Some(Type::List(b)),
+        (Type::List(a), Type::List(b)) if *b == Type::Any => Some(Type::List(a)),
+        (Type::List(a), Type::List(b)) => common_type(*a, *b).map(|it| Type::List(Box::new(it))),
+        _ => None
+    }
+}
+
+/// Any accepts anything, list element types are only compared when both sides know them.
+pub fn type_matches(expected: &Type, received: &Type) -> bool {
+    type_conforms(expected, received, &|_, _| false)
+}
+
+/// Like `type_matches`, but a struct also fits every interface `implements(struct, interface)` reports.
+pub fn type_conforms(expected: &Type, received: &Type, implements: &dyn Fn(&str, &str) -> bool) -> bool {
+    match (expected, received) {
+        (Type::Any, _) => true,
+        (Type::List(_), Type::List(tp)) if **tp == Type::Any => true,
+        (Type::List(a), Type::List(b)) => type_conforms(a, b, implements),
+        (Type::Handle(tag), Type::Handle(_)) if tag.is_empty() => true,
+        //plain object types accept every struct
+        (Type::Complex(_), Type::Struct(_)) => true,
+        (Type::Struct(interface), Type::Struct(name)) if implements(name, interface) => true,
+        (a, b) => a == b
+    }
+}
+
+/// Finds where a value does not fit a type, the path lists the indexes of nested list elements.
+pub fn type_mismatch(expected: &Type, value: &RuntimeObject, implements: &dyn Fn(&str, &str) -> bool) -> Option<(Vec<usize>, Type)> {
+    match (expected, value) {
+        (Type::List(element), RuntimeObject::List(items)) => items.iter().enumerate().find_map(|(i, item)| {
+            type_mismatch(element, item, implements).map(|(mut path, tp)| {
+                path.insert(0, i);
+                (path, tp)
+            })
+        }),
+        (tp, value) if type_conforms(tp, &value.get_type(), implements) => None,
+        (_, value) => Some((vec![], value.get_type()))
+    }
+}
+
+/// Constructors of structs, interface checks and enum variant functions declare the type named by their signature.
+fn is_type_declaration(function: &Function) -> bool {
+    matches!(function.instructions.first(), Some(Operation::InitStruct { .. } | Operation::CheckInterface { .. } | Operation::VariantOf { .. }))
+}
+
+/// Struct names nothing declares fall back to any object, like untyped object arguments.
+fn resolve_types(function: &mut Function, declared: &dyn Fn(&str) -> bool) {
+    let mut types: Vec<&mut Type> = function.args.iter_mut().flatten().collect();
+    types.push(&mut function.return_type);
+    match function.instructions.first_mut() {
+        Some(Operation::CheckInterface { methods, .. }) => {
+            for method in methods {
+                types.extend(method.args.iter_mut());
+                types.push(&mut method.return_type);
+            }
         }
+        Some(Operation::VariantOf { variants, .. }) => types.extend(variants.iter_mut().flat_map(|it| it.fields.iter_mut().map(|(_, tp)| tp))),
+        _ => {}
+    }
+    for tp in types {
+        resolve_type(tp, declared);
     }
 }
 
+fn resolve_type(tp: &mut Type, declared: &dyn Fn(&str) -> bool) {
+    match tp {
+        Type::Struct(name) if !declared(name) => *tp = Type::Complex(vec![]),
+        Type::List(element) => resolve_type(element, declared),
+        _ => {}
+    }
+}
 
-fn map_objects_to_type(objects: &Vec<RuntimeObject>) -> Vec<Type> {
-    objects.iter().map(|it| it.get_type()).collect()
+/// e.g. "received type String" or "element [2][0] has type String".
+fn describe_mismatch(path: &[usize], tp: &Type, verb: &str) -> String {
+    if path.is_empty() {
+        return format!("{} type {}", verb, tp)
+    }
+    let index = path.iter().fold(String::new(), |a, i| format!("{}[{}]", a, i));
+    format!("element {} has type {}", index, tp)
 }
 
-fn compare_types(received: &Vec<Type>, expected: &Vec<Type>, context_sig: &str) -> Result<(), String> {
+fn compare_types(received: &[RuntimeObject], expected: &[Type], context_sig: &str, implements: &dyn Fn(&str, &str) -> bool) -> Result<(), String> {
     if received.len() != expected.len() {
         return Err(format!("Expected {} arguments but got {} args while trying to call {}!", expected.len(), received.len(), context_sig));
     }
 
     for (i, tp) in expected.iter().enumerate() {
-        if *tp == Type::Void {
-            continue
-        }
-        if *tp != received[i] {
-            return Err(format!("Expected th {} Arg of type {} while calling {} but received type {}!",i, tp, context_sig, received[i]))
+        if let Some((path, actual)) = type_mismatch(tp, &received[i], implements) {
+            return Err(format!("Expected th {} Arg of type {} while calling {} but {}!",i, tp, context_sig, describe_mismatch(&path, &actual, "received")))
         }
     }
 
     Ok(())
 }
 
-fn binary_operation(first: &RuntimeObject, second: &RuntimeObject, op: &BinaryOpCode) -> Result<RuntimeObject, String> {
+fn type_error(message: String) -> RuntimeError {
+    RuntimeError::new(ErrorKind::Type, message)
+}
+
+fn argument(args: &[RuntimeObject], index: usize, execution_signature: &str) -> Result<RuntimeObject, RuntimeError> {
+    args.get(index).cloned().ok_or_else(|| RuntimeError::new(ErrorKind::UnknownVariable, format!("Argument {} was not passed while executing {}", index, execution_signature)))
+}
+
+/// Top of the stack, instructions that find it empty fail instead of taking the host down.
+fn pop(stack: &mut Vec<RuntimeObject>, execution_signature: &str) -> Result<RuntimeObject, RuntimeError> {
+    stack.pop().ok_or_else(|| RuntimeError::new(ErrorKind::StackUnderflow, format!("Expected a value on the stack while executing {}", execution_signature)))
+}
+
+/// Struct whose operator overloads apply, only the left operand decides so e.g. `"total: " + money` stays a concatenation.
+fn overload_type(left: &RuntimeObject) -> Option<String> {
+    match left {
+        RuntimeObject::Object(o) => o.type_name().map(|name| name.to_string()),
+        _ => None
+    }
+}
+
+/// `first` is the left operand, the top of the stack.
+fn binary_operation(first: &RuntimeObject, second: &RuntimeObject, op: &BinaryOpCode) -> Result<RuntimeObject, RuntimeError> {
+    //a String on the left concatenates any value
+    if let (RuntimeObject::Str(str), BinaryOpCode::Add) = (first, op) {
+        return Ok(RuntimeObject::Str(match second {
+            RuntimeObject::Str(value) => str.to_owned()+value.as_str(),
+            RuntimeObject::Num(num) => str.to_owned()+num.to_string().as_str(),
+            RuntimeObject::Int(int) => str.to_owned()+int.to_string().as_str(),
+            RuntimeObject::Bool(bool) => str.to_owned()+bool.to_string().as_str(),
+            RuntimeObject::Void => str.to_owned()+"Void",
+            RuntimeObject::Object(o) => str.to_owned()+o.get_signature().as_str(),
+            RuntimeObject::Handle(h) => str.to_owned()+h.get_signature().as_str(),
+            RuntimeObject::List(_) => str.to_owned()+"List<>",
+            RuntimeObject::Map(_) => str.to_owned()+"Map<>"
+        }))
+    }
     if first.get_type() != second.get_type() {
-        return Err(format!("Binary Operations can only be executed on the same type... got {} and {}", first.get_type().to_string(), second.get_type().to_string()))
+        return Err(type_error(format!("Binary Operations can only be executed on the same type... got {} and {}", first.get_type(), second.get_type())))
     }
 
     match first {
@@ -268,313 +767,809 @@ fn binary_operation(first: &RuntimeObject, second: &RuntimeObject, op: &BinaryOp
                     BinaryOpCode::Add => num + num2,
                     BinaryOpCode::Sub => num - num2,
                     BinaryOpCode::Mul => num * num2,
-                    BinaryOpCode::Div => num / num2
+                    BinaryOpCode::Div => num / num2,
+                    //euclidean, so the result has the sign of the divisor like a floored modulo
+                    BinaryOpCode::Mod => num.rem_euclid(*num2),
+                    BinaryOpCode::Pow => num.powf(*num2),
+                    op => return Err(type_error(format!("Cannot do {} on Number, only arithmetic is supported", op)))
                 })),
-                obj => Err(format!("Cannot operate {} on Number while doing binary operations", obj.get_type()))
-            }
-        }
-        RuntimeObject::Str(str) => {
-            match op {
-                BinaryOpCode::Add => {
-                    Ok(RuntimeObject::Str(match second {
-                        RuntimeObject::Str(value) => str.to_owned()+value.as_str(),
-                        RuntimeObject::Num(num) => str.to_owned()+num.to_string().as_str(),
-                        RuntimeObject::Bool(bool) => str.to_owned()+bool.to_string().as_str(),
-                        RuntimeObject::Void => str.to_owned()+"Void",
-                        RuntimeObject::Object(o) => str.to_owned()+o.get_signature().as_str(),
-                        RuntimeObject::List(_) => "List<>".to_string()
-                    }))
-                }
-                _ => Err(format!("Doing Binary Operations other than add on String does not make sense"))
+                obj => Err(type_error(format!("Cannot operate {} on Number while doing binary operations", obj.get_type())))
             }
         }
-        e => Err(format!("Cannot do binary Operation on type {}", e.get_type()))
-    }
-}
-
-
-fn equality_check(first: &RuntimeObject, second: &RuntimeObject, op: &EqualityCheck) -> Result<RuntimeObject, String> {
-    match op {
-        EqualityCheck::Eq => Ok(RuntimeObject::Bool(*first == *second)),
-        EqualityCheck::Neq => Ok(RuntimeObject::Bool(*first != *second)),
-        EqualityCheck::Gt => match first {
-            RuntimeObject::Num(num) => {
-                match second {
-                    RuntimeObject::Num(num2) => Ok(RuntimeObject::Bool(num2 > num)),
-                    _ => Err(format!("Invalid"))
-                }
+        RuntimeObject::Int(int) => {
+            match second {
+                RuntimeObject::Int(int2) => integer_operation(*int, *int2, op).map(RuntimeObject::Int),
+                obj => Err(type_error(format!("Cannot operate {} on Integer while doing binary operations", obj.get_type())))
             }
-            _ => Err(format!("invalid"))
         }
-        EqualityCheck::St => match first {
-            RuntimeObject::Num(num) => {
-                match second {
-                    RuntimeObject::Num(num2) => Ok(RuntimeObject::Bool(num2 < num)),
-                    _ => Err(format!("Invalid"))
-                }
+        RuntimeObject::Bool(bool) => {
+            match (op, second) {
+                (BinaryOpCode::And, RuntimeObject::Bool(bool2)) => Ok(RuntimeObject::Bool(*bool && *bool2)),
+                (BinaryOpCode::Or, RuntimeObject::Bool(bool2)) => Ok(RuntimeObject::Bool(*bool || *bool2)),
+                (BinaryOpCode::Xor, RuntimeObject::Bool(bool2)) => Ok(RuntimeObject::Bool(bool != bool2)),
+                (op, _) => Err(type_error(format!("Cannot do {} on Boolean, only and, or and xor are supported", op)))
             }
-            _ => Err(format!("invalid"))
         }
+        RuntimeObject::Str(_) => Err(type_error("Doing Binary Operations other than add on String does not make sense".to_string())),
+        e => Err(type_error(format!("Cannot do binary Operation on type {}", e.get_type())))
     }
 }
 
-pub fn execute_std(mut functions: Vec<Function>, execution_signature: &str) {
-    let mut std = get_std_library();
-    std.append(&mut functions);
+/// Integer arithmetic is checked, overflow and division by zero are errors instead of wrapping.
+fn integer_operation(int: i64, int2: i64, op: &BinaryOpCode) -> Result<i64, RuntimeError> {
+    let result = match op {
+        BinaryOpCode::Add => int.checked_add(int2),
+        BinaryOpCode::Sub => int.checked_sub(int2),
+        BinaryOpCode::Mul => int.checked_mul(int2),
+        BinaryOpCode::Div | BinaryOpCode::Mod if int2 == 0 => return Err(RuntimeError::new(ErrorKind::Arithmetic, format!("Division by zero while doing {} on {} and {}", op, int, int2))),
+        BinaryOpCode::Div => int.checked_div(int2),
+        BinaryOpCode::Mod => int.checked_rem_euclid(int2),
+        BinaryOpCode::Pow if int2 < 0 => return Err(RuntimeError::new(ErrorKind::Arithmetic, format!("Integer pow needs a non negative exponent but got {}", int2))),
+        //only 0, 1 and -1 survive an exponent above u32::MAX, a big exponent of the same parity gives their result
+        BinaryOpCode::Pow => int.checked_pow(u32::try_from(int2).unwrap_or(u32::MAX - 1 + (int2 % 2) as u32)),
+        BinaryOpCode::BitAnd => Some(int & int2),
+        BinaryOpCode::BitOr => Some(int | int2),
+        BinaryOpCode::BitXor => Some(int ^ int2),
+        BinaryOpCode::Shl | BinaryOpCode::Shr if !(0..64).contains(&int2) => return Err(RuntimeError::new(ErrorKind::Arithmetic, format!("Integers can only be shifted by 0 to 63 bits but got {}", int2))),
+        BinaryOpCode::Shl => Some(int << int2),
+        BinaryOpCode::Shr => Some(int >> int2),
+        BinaryOpCode::And | BinaryOpCode::Or | BinaryOpCode::Xor => return Err(type_error(format!("Cannot do {} on Integer, use bitAnd, bitOr or bitXor", op)))
+    };
+    result.ok_or_else(|| RuntimeError::new(ErrorKind::Arithmetic, format!("Integer overflow while doing {} on {} and {}", op, int, int2)))
+}
+
+fn unary_operation(value: &RuntimeObject, op: &UnaryOpCode) -> Result<RuntimeObject, String> {
+    match (op, value) {
+        (UnaryOpCode::BitNot, RuntimeObject::Int(int)) => Ok(RuntimeObject::Int(!int)),
+        (UnaryOpCode::Not, RuntimeObject::Bool(bool)) => Ok(RuntimeObject::Bool(!bool)),
+        (op, value) => Err(format!("Cannot do {} on type {}", op, value.get_type()))
+    }
+}
 
-    let mut runtime = Runtime { storage: ObjectStorage::new() };
+fn equality_check(storage: &ObjectStorage, first: &RuntimeObject, second: &RuntimeObject, op: &EqualityCheck) -> Result<RuntimeObject, String> {
+    //the value below the top is the left operand, so `loadNum 2 loadNum 1 equality gt` is 2 > 1
+    let ordering = match op {
+        EqualityCheck::Eq => return Ok(RuntimeObject::Bool(*first == *second)),
+        EqualityCheck::Neq => return Ok(RuntimeObject::Bool(*first != *second)),
+        EqualityCheck::DeepEq => return Ok(RuntimeObject::Bool(deep_eq(storage, second, first))),
+        _ => compare_values(second, first)?
+    };
+    //NaN is unordered, so every comparison with it is false
+    Ok(RuntimeObject::Bool(match ordering {
+        Some(ordering) => match op {
+            EqualityCheck::Gt => ordering == Ordering::Greater,
+            EqualityCheck::St => ordering == Ordering::Less,
+            EqualityCheck::Gte => ordering != Ordering::Less,
+            _ => ordering != Ordering::Greater
+        },
+        None => false
+    }))
+}
 
-    match runtime.execute(&std, execution_signature, vec![]) {
-        Ok(_) => {},
-        Err(e) => panic!("{}", e)
+/// Numbers and integers by value, strings lexicographically by code point.
+fn compare_values(left: &RuntimeObject, right: &RuntimeObject) -> Result<Option<Ordering>, String> {
+    match (left, right) {
+        (RuntimeObject::Num(a), RuntimeObject::Num(b)) => Ok(a.partial_cmp(b)),
+        (RuntimeObject::Int(a), RuntimeObject::Int(b)) => Ok(Some(a.cmp(b))),
+        (RuntimeObject::Str(a), RuntimeObject::Str(b)) => Ok(Some(a.cmp(b))),
+        (a, b) => Err(format!("Cannot compare {} with {}, only Numbers, Integers and Strings of the same type are ordered", a.get_type(), b.get_type()))
     }
+}
 
+/// Runs a function with the std library until its event loop is done, its return value is passed back.
+pub fn execute_std(functions: Vec<Function>, execution_signature: &str) -> Result<RuntimeObject, RuntimeError> {
+    let mut runtime = Runtime::new();
+    runtime.register_all(get_std_library());
+    runtime.register_all(functions);
+
+    let value = runtime.execute(execution_signature, vec![])?;
+    runtime.run_event_loop()?;
+    Ok(value)
 }
 
-fn dec_all(storage: &mut ObjectStorage, objects: &Vec<&RuntimeObject>) {
+fn dec_all(storage: &mut ObjectStorage, objects: &[&RuntimeObject]) {
     for obj in objects {
-        match obj {
-            RuntimeObject::Object(o) => storage.dec_reference_count(o),
-            _ => {}
+        if let RuntimeObject::Object(o) = obj {
+            storage.dec_reference_count(o)
         }
     }
 }
 
 pub struct Runtime {
-    pub(crate) storage: ObjectStorage
+    pub storage: ObjectStorage,
+    pub permissions: Permissions,
+    pub filesystem: Arc<dyn FileSystem>,
+    pub event_loop: EventLoop,
+    //command line arguments after `--`
+    pub script_args: Vec<String>,
+    //variables set by std/env/set, shadowing the process environment which is never changed
+    pub env: HashMap<String, String>,
+    functions: HashMap<String, Arc<Function>>,
+    debugger: Option<Debugger>
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Runtime::new()
+    }
 }
 
 impl Runtime {
 
+    /// A runtime with every permission, embedders restrict `permissions` themselves,
+    /// unlike the command line which grants nothing unless asked with `--allow-*`.
     pub fn new() -> Runtime {
-        Runtime { storage: ObjectStorage { object_storage: vec![], allocation_table: HashMap::new() }}
+        Runtime {
+            storage: ObjectStorage::new(),
+            permissions: Permissions::allow_all(),
+            filesystem: Arc::new(DiskFileSystem),
+            event_loop: EventLoop::new(),
+            script_args: vec![],
+            env: HashMap::new(),
+            functions: HashMap::new(),
+            debugger: None
+        }
     }
 
-    pub fn execute(&mut self, functions: &Vec<Function>, execution_signature: &str, args: Vec<RuntimeObject>) -> Result<RuntimeObject, String> {
-        let function = functions
-            .iter()
-            .find(|it| it.signature==execution_signature)
-            .unwrap();
+    /// Pauses the functions run from now on as the debugger asks, replacing any debugger attached before.
+    pub fn attach_debugger(&mut self, debugger: Debugger) {
+        self.debugger = Some(debugger)
+    }
+
+    pub fn detach_debugger(&mut self) -> Option<Debugger> {
+        self.debugger.take()
+    }
+
+    /// Adds a function, replacing any function that already uses its signature.
+    pub fn register(&mut self, function: Function) {
+        self.functions.insert(function.signature.clone(), Arc::new(function));
+    }
+
+    /// Adds functions loaded together, struct names in their types may be declared by any of them or by the runtime.
+    pub fn register_all(&mut self, mut functions: Vec<Function>) {
+        let declared: Vec<String> = functions.iter().filter(|it| is_type_declaration(it)).map(|it| it.signature.clone()).collect();
+        for function in functions.iter_mut() {
+            resolve_types(function, &|name| declared.iter().any(|it| it == name) || self.declares_type(name));
+        }
+        functions.into_iter().for_each(|it| self.register(it))
+    }
+
+    /// Registers `functions` if the runtime still passes the verifier with them, otherwise leaves it as it was.
+    pub fn register_verified(&mut self, functions: Vec<Function>) -> Result<(), RuntimeError> {
+        let replaced: Vec<(String, Option<Arc<Function>>)> = functions.iter()
+            .map(|it| (it.signature.clone(), self.functions.get(&it.signature).cloned()))
+            .collect();
+        self.register_all(functions);
+        let result = verify(self);
+        if result.is_err() {
+            //in reverse so a signature loaded twice gets back what was there before the first
+            for (signature, previous) in replaced.into_iter().rev() {
+                match previous {
+                    Some(function) => self.functions.insert(signature, function),
+                    None => self.functions.remove(&signature)
+                };
+            }
+        }
+        result
+    }
+
+    pub fn get_function(&self, signature: &str) -> Option<Arc<Function>> {
+        self.functions.get(signature).cloned()
+    }
+
+    pub fn functions(&self) -> impl Iterator<Item = &Function> {
+        self.functions.values().map(|it| it.as_ref())
+    }
+
+    /// Field names and types of a declared struct, taken from its constructor.
+    pub fn struct_fields(&self, name: &str) -> Option<Vec<(String, Type)>> {
+        let constructor = self.functions.get(name)?;
+        match (constructor.instructions.first(), &constructor.args) {
+            (Some(Operation::InitStruct { fields, .. }), Some(types)) => Some(fields.iter().cloned().zip(types.iter().cloned()).collect()),
+            _ => None
+        }
+    }
+
+    /// Interfaces a struct declares conformance to.
+    pub fn struct_interfaces(&self, name: &str) -> Option<&[String]> {
+        match self.functions.get(name)?.instructions.first() {
+            Some(Operation::InitStruct { interfaces, .. }) => Some(interfaces),
+            _ => None
+        }
+    }
+
+    /// Required methods of a declared interface, taken from its check function.
+    pub fn interface_methods(&self, name: &str) -> Option<&[MethodSignature]> {
+        match self.functions.get(name)?.instructions.first() {
+            Some(Operation::CheckInterface { methods, .. }) => Some(methods),
+            _ => None
+        }
+    }
+
+    /// Variants of a declared enum, taken from its variant function.
+    pub fn enum_variants(&self, name: &str) -> Option<&[Variant]> {
+        match self.functions.get(name)?.instructions.first() {
+            Some(Operation::VariantOf { variants, .. }) => Some(variants),
+            _ => None
+        }
+    }
+
+    /// Whether `name` is a declared struct, interface or enum.
+    pub fn declares_type(&self, name: &str) -> bool {
+        self.functions.get(name).is_some_and(|it| is_type_declaration(it))
+    }
+
+    /// Whether the struct `name` declares conformance to `interface`, the declaration is verified on load.
+    pub fn implements(&self, name: &str, interface: &str) -> bool {
+        self.struct_interfaces(name).is_some_and(|it| it.iter().any(|i| i == interface))
+    }
+
+    pub fn execute(&mut self, execution_signature: &str, args: Vec<RuntimeObject>) -> Result<RuntimeObject, RuntimeError> {
+        let function = match self.get_function(execution_signature) {
+            Some(f) => f,
+            None => return Err(RuntimeError::new(ErrorKind::NotFound, format!("No function found with name {}", execution_signature)))
+        };
         let mut variables: HashMap<String, RuntimeObject> = HashMap::new();
-        return self.execute_function(functions, &function.instructions, execution_signature, &args, &mut variables)
+        let result = self.run_frames(&function, execution_signature, &args, &mut variables, &mut vec![Frame::body()], false);
+        //blocks share the variables of their function, so they are only released once it is done
+        dec_all(&mut self.storage, &variables.values().collect::<Vec<_>>());
+
+        match result {
+            Ok(Step::Return(value)) => self.check_return(&function, execution_signature, value),
+            Ok(Step::Yield(_)) => unreachable!("yield outside of a coroutine is an error"),
+            Err(e) => Err(e)
+        }
     }
 
-    fn execute_function(
-        &mut self,
-        functions: &Vec<Function>,
-        instructions: &Vec<Operation>,
-        execution_signature: &str,
-        args: &Vec<RuntimeObject>,
-        variables: &mut HashMap<String, RuntimeObject>
-    ) -> Result<RuntimeObject, String> {
-
-        let mut stack: Vec<RuntimeObject> = vec![];
-
-        for (_, instruction) in instructions.iter().enumerate() {
-            match instruction {
-                //load constants operation
-                Operation::LoadConstNum(num) => stack.push(RuntimeObject::Num(num.to_owned())),
-                Operation::LoadConstString(str) => stack.push(RuntimeObject::Str(str.to_owned())),
-                Operation::LoadConstBool(bool) => stack.push(RuntimeObject::Bool(bool.to_owned())),
-
-                //function calls
-                Operation::CallFunction { signature, argc } => {
-                    let mut args = vec![];
-                    for _ in 0..argc.to_owned() {
-                        args.push(match stack.pop().unwrap() {
-                            RuntimeObject::Object(o) => {
-                                self.storage.inc_reference_count(&o);
-                                RuntimeObject::Object(o)
-                            },
-                            obj => obj
-                        })
-                    }
+    //blocks return through the same instruction, so the declared type is checked once the function is done
+    fn check_return(&self, function: &Function, execution_signature: &str, value: RuntimeObject) -> Result<RuntimeObject, RuntimeError> {
+        match type_mismatch(&function.checked_type(&function.return_type), &value, &|name, interface| self.implements(name, interface)) {
+            Some((path, actual)) => Err(RuntimeError::new(
+                ErrorKind::Type,
+                format!("Function {} is declared to return {} but {}", execution_signature, function.return_type, describe_mismatch(&path, &actual, "returned"))
+            )),
+            None => Ok(value)
+        }
+    }
 
-                    let function = match functions.iter().find(|it| it.signature == *signature) {
-                        Some(t) => t,
-                        None => return Err(format!("No function found with name {}, while executing {}", signature, execution_signature))
-                    };
-
-                    if function.args.is_some() {
-                        match compare_types(
-                            &map_objects_to_type(&args),
-                            &function.args.as_ref().unwrap(),
-                            signature.as_str()
-                        ) {
-                            Ok(..) => {  }
-                            Err(e) => return Err(format!("{}\n Error occurred in {}", e, execution_signature))
-                        }
-                    }
+    fn checked_function(&self, signature: &str, args: &[RuntimeObject]) -> Result<Arc<Function>, RuntimeError> {
+        let function = match self.get_function(signature) {
+            Some(t) => t,
+            None => return Err(RuntimeError::new(ErrorKind::NotFound, format!("No function found with name {}", signature)))
+        };
 
+        if let Some(expected) = &function.args {
+            let expected: Vec<Type> = expected.iter().map(|it| function.checked_type(it)).collect();
+            if let Err(e) = compare_types(args, &expected, signature, &|name, interface| self.implements(name, interface)) {
+                return Err(RuntimeError::new(ErrorKind::Type, e))
+            }
+        }
+        Ok(function)
+    }
 
-                    match self.execute(functions, signature.as_str(), args) {
-                        Ok(result) => stack.push(result),
-                        Err(e) => return Err(format!("{}\n Error occurred in {}", e, execution_signature))
-                    }
-                }
+    /// Like `execute`, but checks the arguments against the declared argument types first.
+    pub fn call(&mut self, signature: &str, args: Vec<RuntimeObject>) -> Result<RuntimeObject, RuntimeError> {
+        self.checked_function(signature, &args)?;
+        self.execute(signature, args)
+    }
 
-                Operation::BinaryOp(op) => {
-                    let first = stack.pop().unwrap();
-                    let second = stack.pop().unwrap();
+    /// Creates a suspended call of `signature`, it starts running on the first `resume`.
+    pub fn coroutine(&mut self, signature: &str, args: Vec<RuntimeObject>) -> Result<Handle, RuntimeError> {
+        let function = self.checked_function(signature, &args)?;
+        Ok(Handle::new("coro", Coroutine::new(signature, function, args)))
+    }
 
-                    match binary_operation(&first, &second, &op) {
-                        Ok(result) => stack.push(result),
-                        Err(e) => return Err(format!("{}\n Error while executing {}", e, execution_signature))
-                    }
+    /// Runs a coroutine up to its next `yield` and returns the yielded value, or the return value once it finishes.
+    pub fn resume(&mut self, handle: &Handle) -> Result<RuntimeObject, RuntimeError> {
+        let coroutine = handle.expect::<Coroutine>("coro")?;
+        let (mut frames, mut variables) = match coroutine.state.replace(CoroutineState::Running) {
+            CoroutineState::Suspended { frames, variables } => (frames, variables),
+            CoroutineState::Running => return Err(RuntimeError::new(ErrorKind::Runtime, format!("Coroutine {} is already running", coroutine.signature))),
+            CoroutineState::Done => {
+                coroutine.state.replace(CoroutineState::Done);
+                return Err(RuntimeError::new(ErrorKind::Runtime, format!("Coroutine {} is done and cannot be resumed", coroutine.signature)))
+            }
+        };
+
+        let result = self.run_frames(&coroutine.function, &coroutine.signature, &coroutine.args, &mut variables, &mut frames, true);
+        if let Ok(Step::Yield(value)) = result {
+            coroutine.state.replace(CoroutineState::Suspended { frames, variables });
+            return Ok(value)
+        }
+        coroutine.state.replace(CoroutineState::Done);
+        //like `execute`, arguments are released through the variables they were mapped to
+        dec_all(&mut self.storage, &variables.values().collect::<Vec<_>>());
+        match result {
+            Ok(Step::Return(value) | Step::Yield(value)) => self.check_return(&coroutine.function, &coroutine.signature, value),
+            Err(e) => Err(e)
+        }
+    }
+
+    /// Dispatches on the type of the first argument to `<type>:name`, falling back to `*:name`.
+    pub fn call_method(&mut self, name: &str, args: Vec<RuntimeObject>) -> Result<RuntimeObject, RuntimeError> {
+        let receiver = match args.first() {
+            Some(receiver) => receiver.method_prefix(),
+            None => return Err(RuntimeError::new(ErrorKind::Runtime, format!("Method {} needs a receiver argument", name)))
+        };
+        let typed = format!("{}:{}", receiver, name);
+        if self.functions.contains_key(&typed) {
+            return self.call(&typed, args)
+        }
+        let generic = format!("*:{}", name);
+        if self.functions.contains_key(&generic) {
+            return self.call(&generic, args)
+        }
+        Err(RuntimeError::new(ErrorKind::NotFound, format!("No method {} for type {}, neither {} nor {} exist", name, receiver, typed, generic)))
+    }
+
+    /// Calls the overload `<name>:<op>` with the left operand first.
+    fn call_operator(&mut self, name: &str, op: &str, operands: Vec<RuntimeObject>) -> Result<RuntimeObject, RuntimeError> {
+        let signature = format!("{}:{}", name, op);
+        if !self.functions.contains_key(&signature) {
+            return Err(type_error(format!("Cannot do {} on {} objects, no overload {} is defined", op, name, signature)))
+        }
+        //the operands keep the references they had on the stack, the overload releases them like its arguments
+        self.call(&signature, operands)
+    }
+
+    /// `eq` and `neq` use the `eq` overload if there is one and fall back to identity,
+    /// orderings need a `cmp` overload returning a negative, zero or positive number.
+    fn overloaded_equality(&mut self, name: &str, left: RuntimeObject, right: RuntimeObject, op: &EqualityCheck) -> Result<RuntimeObject, RuntimeError> {
+        match op {
+            EqualityCheck::Eq | EqualityCheck::Neq => {
+                if !self.functions.contains_key(&format!("{}:eq", name)) {
+                    return Ok(RuntimeObject::Bool((left == right) == matches!(op, EqualityCheck::Eq)))
+                }
+                match self.call_operator(name, "eq", vec![left, right])? {
+                    RuntimeObject::Bool(equal) => Ok(RuntimeObject::Bool(equal == matches!(op, EqualityCheck::Eq))),
+                    other => Err(type_error(format!("{}:eq has to return a Boolean, got {}", name, other.get_type())))
                 }
+            }
+            _ => {
+                let ordering = match self.call_operator(name, "cmp", vec![left, right])? {
+                    RuntimeObject::Int(n) => n.cmp(&0),
+                    RuntimeObject::Num(n) => match n.partial_cmp(&0.0) {
+                        Some(ordering) => ordering,
+                        None => return Ok(RuntimeObject::Bool(false))
+                    },
+                    other => return Err(type_error(format!("{}:cmp has to return a Number or Integer, got {}", name, other.get_type())))
+                };
+                Ok(RuntimeObject::Bool(match op {
+                    EqualityCheck::Gt => ordering == Ordering::Greater,
+                    EqualityCheck::St => ordering == Ordering::Less,
+                    EqualityCheck::Gte => ordering != Ordering::Less,
+                    _ => ordering != Ordering::Greater
+                }))
+            }
+        }
+    }
 
-                Operation::EqualityCheck(op) => {
-                    let first = stack.pop().unwrap();
-                    let second = stack.pop().unwrap();
+    /// Script representation of an error, as bound by `catch`.
+    pub fn error_object(&mut self, error: &RuntimeError) -> RuntimeObject {
+        let object = self.storage.allocate_object();
+        self.storage.set_field(&object, "kind".to_string(), RuntimeObject::Str(error.kind.to_string()));
+        self.storage.set_field(&object, "message".to_string(), RuntimeObject::Str(error.message.clone()));
+        RuntimeObject::Object(object)
+    }
 
-                    match equality_check(&first, &second, &op) {
-                        Ok(result) => stack.push(result),
-                        Err(r) => return Err(format!("{}\n Error while executing {}", r, execution_signature))
-                    }
+    fn run_frames(
+        &mut self,
+        function: &Function,
+        execution_signature: &str,
+        args: &[RuntimeObject],
+        variables: &mut HashMap<String, RuntimeObject>,
+        frames: &mut Vec<Frame>,
+        coroutine: bool
+    ) -> Result<Step, RuntimeError> {
+        if let Some(debugger) = &mut self.debugger {
+            debugger.calls.push(CallFrame { signature: execution_signature.to_string(), index: 0, args: args.to_vec(), variables: vec![], stack: vec![] });
+        }
+        let result = self.run_blocks(function, execution_signature, args, variables, frames, coroutine);
+        if let Some(debugger) = &mut self.debugger {
+            debugger.calls.pop();
+        }
+        result
+    }
+
+    fn run_blocks(
+        &mut self,
+        function: &Function,
+        execution_signature: &str,
+        args: &[RuntimeObject],
+        variables: &mut HashMap<String, RuntimeObject>,
+        frames: &mut Vec<Frame>,
+        coroutine: bool
+    ) -> Result<Step, RuntimeError> {
+        let mut code = block_at(&function.instructions, &frames.last().unwrap().path);
+
+        loop {
+            let terminated = self.debugger.is_some() && !self.debug_hook(function, code, execution_signature, variables, frames);
+            let frame = frames.last_mut().unwrap();
+            let mut control = match code.get(frame.pc) {
+                _ if terminated => Err(RuntimeError::new(ErrorKind::Terminated, "The debugger ended the program".to_string())),
+                Some(instruction) => {
+                    frame.pc += 1;
+                    self.step(instruction, execution_signature, args, variables, &mut frame.stack, coroutine)
                 }
+                None => Err(format!("Function did not return but ran out of tokens ({})", execution_signature).into())
+            };
 
-                Operation::Native {callback} => {
-                    match callback(&args, &mut self.storage) {
-                        Ok(result) => stack.push(result),
-                        Err(e) => return Err(format!("{}\n Error while executing {}", e, execution_signature))
+            //leaving a block can enter the next one, e.g. the body of a while after its condition
+            loop {
+                match control {
+                    Ok(Control::Next) => break,
+                    Ok(Control::Enter(block)) => {
+                        let frame = Frame::block(frames.last().unwrap(), block);
+                        code = block_at(&function.instructions, &frame.path);
+                        frames.push(frame);
+                        break
+                    }
+                    Ok(Control::Leave(value)) => {
+                        let finished = frames.pop().unwrap();
+                        let (index, block) = match finished.path.last() {
+                            Some(it) => *it,
+                            None => return Ok(Step::Return(value))
+                        };
+                        let parent = frames.last_mut().unwrap();
+                        code = block_at(&function.instructions, &parent.path);
+                        control = self.complete(&code[index], block, value, &mut parent.stack, execution_signature);
+                    }
+                    Ok(Control::Yield(value)) => return Ok(Step::Yield(value)),
+                    Err(mut e) => {
+                        //unwind to the innermost try whose content failed
+                        control = loop {
+                            let finished = frames.pop().unwrap();
+                            let index = match finished.path.last() {
+                                Some(&(index, block)) => (block == 0).then_some(index),
+                                None => return Err(e)
+                            };
+                            let parent = block_at(&function.instructions, &frames.last().unwrap().path);
+                            let catchable = !matches!(e.kind, ErrorKind::Exit(_) | ErrorKind::Terminated);
+                            if let Some(Operation::Try { name, .. }) = index.map(|index| &parent[index]).filter(|_| catchable) {
+                                let error = self.error_object(&e);
+                                variables.insert(name.to_string(), error);
+                                code = parent;
+                                break Ok(Control::Enter(1))
+                            }
+                            e = e.trace(format!("Error while executing {}", execution_signature));
+                        }
                     }
                 }
+            }
+        }
+    }
 
-                Operation::Return => {
-                    let return_value = match stack.pop().unwrap() {
+    //keeps the call stack of the debugger up to date and lets it pause before the next instruction,
+    //false once the debugger ended the program
+    fn debug_hook(&mut self, function: &Function, code: &[Operation], execution_signature: &str, variables: &HashMap<String, RuntimeObject>, frames: &[Frame]) -> bool {
+        let mut debugger = self.debugger.take().unwrap();
+        let frame = frames.last().unwrap();
+        //natives are stepped over, they have no instructions to show
+        if frame.pc < code.len() && !function.is_native() {
+            let index = instruction_index(&function.instructions, &frame.path, frame.pc);
+            let call = debugger.calls.last_mut().unwrap();
+            call.index = index;
+            call.variables = variables.iter().map(|(name, value)| (name.clone(), value.clone())).collect();
+            call.variables.sort_by(|a, b| a.0.cmp(&b.0));
+            call.stack = frames.iter().flat_map(|it| it.stack.iter().cloned()).collect();
+            if debugger.should_pause(execution_signature, index) {
+                debugger.pause(self);
+            }
+        }
+        let terminated = debugger.terminated();
+        self.debugger = Some(debugger);
+        !terminated
+    }
+
+    /// Handles a block returning `value` to the frame of `instruction`, may ask to enter another of its blocks.
+    fn complete(&mut self, instruction: &Operation, block: usize, value: RuntimeObject, stack: &mut Vec<RuntimeObject>, execution_signature: &str) -> Result<Control, RuntimeError> {
+        match instruction {
+            Operation::If(_) | Operation::Else(_) => {
+                stack.push(value);
+                stack.push(RuntimeObject::Bool(matches!(instruction, Operation::If(_))));
+            }
+            Operation::AndThen(_) | Operation::OrElse(_) => match value {
+                RuntimeObject::Bool(result) => stack.push(RuntimeObject::Bool(result)),
+                other => return Err(RuntimeError::new(ErrorKind::Type, format!("Expected Bool from {} block but got {} while executing {}", instruction, other.get_type(), execution_signature)))
+            },
+            Operation::While { .. } if block == 0 => return match value {
+                RuntimeObject::Bool(true) => Ok(Control::Enter(1)),
+                RuntimeObject::Bool(false) => Ok(Control::Next),
+                _ => Err(format!("Expected boolean in while condition while executing {}", execution_signature).into())
+            },
+            //the body returns true to break
+            Operation::While { .. } => return Ok(if let RuntimeObject::Bool(true) = value { Control::Next } else { Control::Enter(0) }),
+            _ => stack.push(value)
+        }
+        Ok(Control::Next)
+    }
+
+    fn step(
+        &mut self,
+        instruction: &Operation,
+        execution_signature: &str,
+        args: &[RuntimeObject],
+        variables: &mut HashMap<String, RuntimeObject>,
+        stack: &mut Vec<RuntimeObject>,
+        coroutine: bool
+    ) -> Result<Control, RuntimeError> {
+        match instruction {
+            //load constants operation
+            Operation::LoadConstNum(num) => stack.push(RuntimeObject::Num(num.to_owned())),
+            Operation::LoadConstInt(int) => stack.push(RuntimeObject::Int(*int)),
+            Operation::LoadConstString(str) => stack.push(RuntimeObject::Str(str.to_owned())),
+            Operation::LoadConstBool(bool) => stack.push(RuntimeObject::Bool(bool.to_owned())),
+
+            //function calls
+            Operation::CallFunction { signature, argc } | Operation::CallMethod { name: signature, argc } | Operation::NewCoroutine { signature, argc } => {
+                let mut args = vec![];
+                for _ in 0..argc.to_owned() {
+                    args.push(match pop(stack, execution_signature)? {
                         RuntimeObject::Object(o) => {
                             self.storage.inc_reference_count(&o);
                             RuntimeObject::Object(o)
                         },
                         obj => obj
-                    };
-                    dec_all(&mut self.storage, &variables.values().collect());
-                    dec_all(&mut self.storage, &stack.iter().collect());
-                    return Ok(return_value)
-                }
-                Operation::If(content) => {
-                    match stack.pop().unwrap() {
-                        RuntimeObject::Bool(val) => {
-                            if val {
-                                match self.execute_function(functions, content, execution_signature, args, variables) {
-                                    Ok(result) => stack.push(result),
-                                    Err(e) => return Err(format!("{}\n Error while executing {}", e, execution_signature))
-                                }
-                            }
-                            stack.push(RuntimeObject::Bool(val))
-                        }
-                        _ => {return Err(format!("Expected Bool on stack (If) while executing {}", execution_signature))}
-                    }
+                    })
                 }
-                Operation::Else(content) => {
-                    match stack.pop().unwrap() {
-                        RuntimeObject::Bool(val) => {
-                            if !val {
-                                match self.execute_function(functions, content, execution_signature, args, variables) {
-                                    Ok(result) => stack.push(result),
-                                    Err(e) => return Err(format!("{}\n Error while executing {}", e, execution_signature))
-                                }
-                            }
-                            stack.push(RuntimeObject::Bool(val))
-                        }
-                        _ => {return Err(format!("Expected Bool on stack (Else) while executing {}", execution_signature))}
-                    }
+
+                let result = match instruction {
+                    Operation::CallMethod { .. } => self.call_method(signature, args),
+                    Operation::NewCoroutine { .. } => self.coroutine(signature, args).map(RuntimeObject::Handle),
+                    _ => self.call(signature.as_str(), args)
+                };
+                match result {
+                    Ok(result) => stack.push(result),
+                    Err(e) => return Err(e.trace(format!("Error occurred in {}", execution_signature)))
                 }
-                Operation::While {content, condition} => {
+            }
 
-                    while match self.execute_function(functions, condition, execution_signature, args, variables) {
-                        Ok(result) => match result {
-                            RuntimeObject::Bool(val) => val,
-                            _ => return Err(format!("Expected boolean in while condition while executing {}", execution_signature))
-                        },
-                        Err(e) => return Err(format!("{}\n Error while executing {}", e, execution_signature))
-                    } {
-                        match self.execute_function(functions, content, execution_signature, args, variables) {
-                            Ok(result) => match result {
-                                RuntimeObject::Bool(val) => {if val {
-                                    break
-                                }}
-                                _ => {}
-                            },
-                            Err(e) => return Err(format!("{}\n Error while executing {}", e, execution_signature))
-                        }
-                    }
+            Operation::Yield => {
+                if !coroutine {
+                    return Err(RuntimeError::new(ErrorKind::Runtime, format!("Cannot yield outside of a coroutine while executing {}", execution_signature)))
                 }
-                Operation::SetVar(name) => {
-                    let data = stack.pop().unwrap();
-                    variables.insert(name.to_string(), data);                }
-                Operation::LoadVar(name) => {
-                    let var = variables[name.as_str()].clone();
-                    stack.push(var);
+                let value = pop(stack, execution_signature)?;
+                if let RuntimeObject::Object(o) = &value {
+                    self.storage.inc_reference_count(o)
                 }
-                Operation::Dup => {
-                    let var = stack.pop().unwrap();
-                    stack.push(var.clone());
-                    stack.push(var)
+                return Ok(Control::Yield(value))
+            }
+
+            Operation::Resume => {
+                let result = match pop(stack, execution_signature)? {
+                    RuntimeObject::Handle(handle) => self.resume(&handle),
+                    other => Err(type_error(format!("Expected a coroutine to resume but got {}", other.get_type())))
+                };
+                match result {
+                    Ok(result) => stack.push(result),
+                    Err(e) => return Err(e.trace(format!("Error while executing {}", execution_signature)))
                 }
+            }
 
-                Operation::InitObject {keys, template} => {
-                    let mut error_flag = false;
-                    let object = self.storage.allocate_object();
-                    self.storage.replace_fields(&object, keys.iter().map(|key| {
-                        let value = stack.pop().unwrap();
-                        if template.is_some() {
-                            if !template.as_ref().unwrap().contains_key(key) || value.get_type() != *template.as_ref().unwrap().get(key).unwrap().clone(){
-                                error_flag = true;
-                            }
-                        }
-                        (key.to_string(), value)
-                    }).collect());
+            Operation::BinaryOp(op) => {
+                let first = pop(stack, execution_signature)?;
+                let second = pop(stack, execution_signature)?;
+
+                //the top is the left operand, for overloads like for built in types
+                let result = match overload_type(&first) {
+                    Some(name) => self.call_operator(&name, &op.to_string(), vec![first, second]),
+                    None => binary_operation(&first, &second, op)
+                };
+                match result {
+                    Ok(result) => stack.push(result),
+                    Err(e) => return Err(e.trace(format!("Error while executing {}", execution_signature)))
+                }
+            }
 
-                    if error_flag {
-                        return Err(format!("Invalid types while object creation {}", execution_signature))
-                    }
+            Operation::UnaryOp(op) => {
+                let value = pop(stack, execution_signature)?;
 
-                    stack.push(RuntimeObject::Object(object))
+                match unary_operation(&value, op) {
+                    Ok(result) => stack.push(result),
+                    Err(e) => return Err(RuntimeError::new(ErrorKind::Type, e).trace(format!("Error while executing {}", execution_signature)))
                 }
+            }
 
-                Operation::InitList { init_push } => {
-                    let values: Vec<RuntimeObject> = (0..*init_push).into_iter().map(|_| stack.pop().expect(&*format!("Expected {} elements on stack for list init while executing {}", init_push, execution_signature))).collect();
-                    stack.push(RuntimeObject::List(values))
+            Operation::EqualityCheck(op) => {
+                let first = pop(stack, execution_signature)?;
+                let second = pop(stack, execution_signature)?;
+
+                let result = match overload_type(&second) {
+                    Some(name) if !matches!(op, EqualityCheck::DeepEq) => self.overloaded_equality(&name, second, first, op),
+                    _ => equality_check(&self.storage, &first, &second, op).map_err(|r| RuntimeError::new(ErrorKind::Type, r))
+                };
+                match result {
+                    Ok(result) => stack.push(result),
+                    Err(e) => return Err(e.trace(format!("Error while executing {}", execution_signature)))
                 }
+            }
 
-                Operation::SetProperty(name) => {
-                    match stack.pop().unwrap() {
-                        RuntimeObject::Object(o) => {
-                            let item = stack.pop().unwrap();
-                            match &item {
-                                RuntimeObject::Object(i) => self.storage.inc_reference_count(&i),
-                                _ => {}
-                            }
-                            self.storage.set_field(&o, name.to_string(), item);
-                            stack.push(RuntimeObject::Object(o));
-                        }
-                        e => return Err(format!("Expected Object on the stack for set property op, while executing {}, {}", execution_signature, e))
-                    };
+            Operation::Native {callback} => {
+                match callback(self, args) {
+                    Ok(result) => stack.push(result),
+                    Err(e) => return Err(e.trace(format!("Error while executing {}", execution_signature)))
                 }
+            }
 
-                Operation::GetProperty(name) => {
-                    match stack.pop().expect("Expected Value on stack while calling getProperty") {
-                        RuntimeObject::Object(o) => {
-                            let item = self.storage.get_field(&o, name.to_string()).expect(format!("Property {} does not exist on object\n while executing {}", name, execution_signature).as_str());
-                            stack.push(item.clone())
+            Operation::Return => {
+                let return_value = match pop(stack, execution_signature)? {
+                    RuntimeObject::Object(o) => {
+                        self.storage.inc_reference_count(&o);
+                        RuntimeObject::Object(o)
+                    },
+                    obj => obj
+                };
+                dec_all(&mut self.storage, &stack.iter().collect::<Vec<_>>());
+                return Ok(Control::Leave(return_value))
+            }
+            Operation::If(_) | Operation::Else(_) => {
+                let runs = matches!(instruction, Operation::If(_));
+                match pop(stack, execution_signature)? {
+                    RuntimeObject::Bool(val) if val == runs => return Ok(Control::Enter(0)),
+                    RuntimeObject::Bool(val) => stack.push(RuntimeObject::Bool(val)),
+                    _ => return Err(format!("Expected Bool on stack ({}) while executing {}", if runs { "If" } else { "Else" }, execution_signature).into())
+                }
+            }
+            Operation::AndThen(_) | Operation::OrElse(_) => {
+                let decides = matches!(instruction, Operation::OrElse(_));
+                match pop(stack, execution_signature)? {
+                    RuntimeObject::Bool(val) if val == decides => stack.push(RuntimeObject::Bool(val)),
+                    RuntimeObject::Bool(_) => return Ok(Control::Enter(0)),
+                    _ => return Err(RuntimeError::new(ErrorKind::Type, format!("Expected Bool on stack ({}) while executing {}", instruction, execution_signature)))
+                }
+            }
+            //the condition is block 0, the body block 1
+            Operation::While { .. } => return Ok(Control::Enter(0)),
+            //the content is block 0, the catch block 1
+            Operation::Try { .. } => return Ok(Control::Enter(0)),
+            Operation::SetVar(name) => {
+                let data = pop(stack, execution_signature)?;
+                variables.insert(name.to_string(), data);                }
+            Operation::LoadVar(name) => match variables.get(name.as_str()) {
+                Some(var) => stack.push(var.clone()),
+                None => return Err(RuntimeError::new(ErrorKind::UnknownVariable, format!("Variable {} is not set while executing {}", name, execution_signature)))
+            }
+            Operation::Dup => {
+                let var = pop(stack, execution_signature)?;
+                stack.push(var.clone());
+                stack.push(var)
+            }
+
+            Operation::InitObject {keys, template} => {
+                let mut error_flag = false;
+                let mut values = vec![];
+                for _ in keys {
+                    values.push(pop(stack, execution_signature)?);
+                }
+                let object = self.storage.allocate_object();
+                self.storage.replace_fields(&object, keys.iter().zip(values).map(|(key, value)| {
+                    if let Some(template) = template {
+                        if template.get(key) != Some(&value.get_type()) {
+                            error_flag = true;
                         }
-                        _ => return Err(format!("Expected Object on the stack for set property op, while executing {}", execution_signature))
-                    };
+                    }
+                    (key.to_string(), value)
+                }).collect());
+
+                if error_flag {
+                    return Err(format!("Invalid types while object creation {}", execution_signature).into())
                 }
 
-                Operation::MapArgTo {arg, name} => {
-                    variables.insert(name.to_string(), args[arg.clone()].clone());
+                stack.push(RuntimeObject::Object(object))
+            }
+
+            Operation::InitStruct { name, fields, .. } => {
+                let object = self.storage.allocate_typed_object(name);
+                for (field, value) in fields.iter().zip(args) {
+                    if let RuntimeObject::Object(o) = value {
+                        self.storage.inc_reference_count(o)
+                    }
+                    self.storage.set_field(&object, field.to_string(), value.clone());
                 }
+                stack.push(RuntimeObject::Object(object))
+            }
 
-                Operation::LoadArg(arg) => {
-                    stack.push(args[arg.clone()].clone())
+            Operation::InitVariant { name, variant, fields } => {
+                let object = self.storage.allocate_variant_object(name, variant);
+                for (field, value) in fields.iter().zip(args) {
+                    if let RuntimeObject::Object(o) = value {
+                        self.storage.inc_reference_count(o)
+                    }
+                    self.storage.set_field(&object, field.to_string(), value.clone());
                 }
-            };
-        }
+                stack.push(RuntimeObject::Object(object))
+            }
 
-        Err(format!("Function did not return but ran out of tokens ({})", execution_signature))
-    }
-}
+            Operation::VariantOf { .. } => {
+                //the argument type is checked by the call
+                let variant = match args.first() {
+                    Some(RuntimeObject::Object(o)) => o.variant().unwrap_or_default().to_string(),
+                    _ => String::new()
+                };
+                stack.push(RuntimeObject::Str(variant))
+            }
 
+            Operation::Match { name, cases } => {
+                let value = pop(stack, execution_signature)?;
+                let (object, variant) = match &value {
+                    RuntimeObject::Object(o) if o.type_name() == Some(name.as_str()) && o.variant().is_some() => (o, o.variant().unwrap()),
+                    value => return Err(type_error(format!("Cannot match {} on {}", value.get_type(), name)).trace(format!("Error while executing {}", execution_signature)))
+                };
+                let (index, case) = match cases.iter().enumerate().find(|(_, it)| it.variant.as_deref().is_none_or(|it| it == variant)) {
+                    Some(case) => case,
+                    None => return Err(RuntimeError::new(ErrorKind::Runtime, format!("No case for {}/{} in match", name, variant)).trace(format!("Error while executing {}", execution_signature)))
+                };
+
+                //payload fields in declaration order, bound to the names of the case
+                let fields = self.enum_variants(name)
+                    .and_then(|variants| variants.iter().find(|it| it.name == variant))
+                    .map(|it| it.fields.iter().map(|(field, _)| field.clone()).collect::<Vec<_>>())
+                    .unwrap_or_default();
+                for (binding, field) in case.bindings.iter().zip(fields) {
+                    let value = self.storage.get_field(object, field).unwrap_or(RuntimeObject::Void);
+                    if let RuntimeObject::Object(o) = &value {
+                        self.storage.inc_reference_count(o)
+                    }
+                    variables.insert(binding.to_string(), value);
+                }
+
+                //each case is a block of the match
+                return Ok(Control::Enter(index))
+            }
 
+            Operation::CheckInterface { name, .. } => {
+                let conforms = match args.first() {
+                    Some(RuntimeObject::Object(o)) => o.type_name().is_some_and(|it| self.implements(it, name)),
+                    _ => false
+                };
+                stack.push(RuntimeObject::Bool(conforms))
+            }
+
+            Operation::InitList { init_push } => {
+                let mut values: Vec<RuntimeObject> = vec![];
+                for _ in 0..*init_push {
+                    match stack.pop() {
+                        Some(value) => values.push(value),
+                        None => return Err(format!("Expected {} elements on stack for list init while executing {}", init_push, execution_signature).into())
+                    }
+                }
+                stack.push(RuntimeObject::List(values))
+            }
+
+            Operation::SetProperty(name) => {
+                match pop(stack, execution_signature)? {
+                    RuntimeObject::Object(o) => {
+                        let item = pop(stack, execution_signature)?;
+                        if let RuntimeObject::Object(i) = &item {
+                            self.storage.inc_reference_count(i)
+                        }
+                        self.storage.set_field(&o, name.to_string(), item);
+                        stack.push(RuntimeObject::Object(o));
+                    }
+                    e => return Err(format!("Expected Object on the stack for set property op, while executing {}, {}", execution_signature, e).into())
+                };
+            }
+
+            Operation::GetProperty(name) => {
+                match pop(stack, execution_signature)? {
+                    RuntimeObject::Object(o) => {
+                        match self.storage.get_field(&o, name.to_string()) {
+                            Some(item) => stack.push(item),
+                            None => return Err(format!("Property {} does not exist on object\n while executing {}", name, execution_signature).into())
+                        }
+                    }
+                    _ => return Err(format!("Expected Object on the stack for set property op, while executing {}", execution_signature).into())
+                };
+            }
+
+            Operation::MapArgTo {arg, name} => {
+                variables.insert(name.to_string(), argument(args, *arg, execution_signature)?);
+            }
+
+            Operation::LoadArg(arg) => {
+                stack.push(argument(args, *arg, execution_signature)?)
+            }
+        };
+        Ok(Control::Next)
+    }
+}
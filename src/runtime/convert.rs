@@ -0,0 +1,420 @@
+use crate::runtime::error::{ErrorKind, RuntimeError};
+use crate::runtime::handle::Handle;
+use crate::runtime::object_storage::ObjectStorage;
+use crate::runtime::value::ValueMap;
+use crate::runtime::{Object, Runtime, RuntimeObject, Type};
+
+/*
+
+Conversion between rust values and runtime objects
+
+ */
+
+pub trait FromRuntime: Sized {
+    fn runtime_type() -> Type;
+    fn from_runtime(value: &RuntimeObject, storage: &ObjectStorage) -> Result<Self, String>;
+}
+
+pub trait IntoRuntime {
+    fn runtime_type() -> Type;
+    fn into_runtime(self, storage: &mut ObjectStorage) -> RuntimeObject;
+}
+
+fn expected<T>(tp: Type, value: &RuntimeObject) -> Result<T, String> {
+    Err(format!("Expected {} but got {}", tp, value.get_type()))
+}
+
+impl FromRuntime for RuntimeObject {
+    fn runtime_type() -> Type { Type::Any }
+
+    fn from_runtime(value: &RuntimeObject, _: &ObjectStorage) -> Result<Self, String> {
+        Ok(value.clone())
+    }
+}
+
+impl IntoRuntime for RuntimeObject {
+    fn runtime_type() -> Type { Type::Any }
+
+    fn into_runtime(self, _: &mut ObjectStorage) -> RuntimeObject {
+        self
+    }
+}
+
+impl IntoRuntime for () {
+    fn runtime_type() -> Type { Type::Void }
+
+    fn into_runtime(self, _: &mut ObjectStorage) -> RuntimeObject {
+        RuntimeObject::Void
+    }
+}
+
+impl FromRuntime for f64 {
+    fn runtime_type() -> Type { Type::Num }
+
+    fn from_runtime(value: &RuntimeObject, _: &ObjectStorage) -> Result<Self, String> {
+        match value {
+            RuntimeObject::Num(n) => Ok(*n),
+            _ => expected(Type::Num, value)
+        }
+    }
+}
+
+impl IntoRuntime for f64 {
+    fn runtime_type() -> Type { Type::Num }
+
+    fn into_runtime(self, _: &mut ObjectStorage) -> RuntimeObject {
+        RuntimeObject::Num(self)
+    }
+}
+
+impl FromRuntime for i64 {
+    fn runtime_type() -> Type { Type::Int }
+
+    fn from_runtime(value: &RuntimeObject, _: &ObjectStorage) -> Result<Self, String> {
+        match value {
+            RuntimeObject::Int(n) => Ok(*n),
+            _ => expected(Type::Int, value)
+        }
+    }
+}
+
+impl IntoRuntime for i64 {
+    fn runtime_type() -> Type { Type::Int }
+
+    fn into_runtime(self, _: &mut ObjectStorage) -> RuntimeObject {
+        RuntimeObject::Int(self)
+    }
+}
+
+// narrower integers are range checked, so e.g. a negative index never reaches a usize
+macro_rules! integer_conversion {
+    ($($int:ty),*) => {$(
+        impl FromRuntime for $int {
+            fn runtime_type() -> Type { Type::Int }
+
+            fn from_runtime(value: &RuntimeObject, _: &ObjectStorage) -> Result<Self, String> {
+                match value {
+                    RuntimeObject::Int(n) => <$int>::try_from(*n).map_err(|_| format!("Expected integer in range of {} but got {}", stringify!($int), n)),
+                    _ => expected(Type::Int, value)
+                }
+            }
+        }
+    )*};
+}
+
+integer_conversion!(i32, u32, u64, usize);
+
+// only types that always fit into an i64 can be returned
+macro_rules! integer_into_runtime {
+    ($($int:ty),*) => {$(
+        impl IntoRuntime for $int {
+            fn runtime_type() -> Type { Type::Int }
+
+            fn into_runtime(self, _: &mut ObjectStorage) -> RuntimeObject {
+                RuntimeObject::Int(self as i64)
+            }
+        }
+    )*};
+}
+
+integer_into_runtime!(i32, u32);
+
+impl FromRuntime for bool {
+    fn runtime_type() -> Type { Type::Bool }
+
+    fn from_runtime(value: &RuntimeObject, _: &ObjectStorage) -> Result<Self, String> {
+        match value {
+            RuntimeObject::Bool(b) => Ok(*b),
+            _ => expected(Type::Bool, value)
+        }
+    }
+}
+
+impl IntoRuntime for bool {
+    fn runtime_type() -> Type { Type::Bool }
+
+    fn into_runtime(self, _: &mut ObjectStorage) -> RuntimeObject {
+        RuntimeObject::Bool(self)
+    }
+}
+
+impl FromRuntime for String {
+    fn runtime_type() -> Type { Type::Str }
+
+    fn from_runtime(value: &RuntimeObject, _: &ObjectStorage) -> Result<Self, String> {
+        match value {
+            RuntimeObject::Str(s) => Ok(s.to_string()),
+            _ => expected(Type::Str, value)
+        }
+    }
+}
+
+impl IntoRuntime for String {
+    fn runtime_type() -> Type { Type::Str }
+
+    fn into_runtime(self, _: &mut ObjectStorage) -> RuntimeObject {
+        RuntimeObject::Str(self)
+    }
+}
+
+impl IntoRuntime for &str {
+    fn runtime_type() -> Type { Type::Str }
+
+    fn into_runtime(self, _: &mut ObjectStorage) -> RuntimeObject {
+        RuntimeObject::Str(self.to_string())
+    }
+}
+
+impl FromRuntime for Object {
+    fn runtime_type() -> Type { Type::Complex(vec![]) }
+
+    fn from_runtime(value: &RuntimeObject, _: &ObjectStorage) -> Result<Self, String> {
+        match value {
+            RuntimeObject::Object(o) => Ok(o.clone()),
+            _ => expected(Type::Complex(vec![]), value)
+        }
+    }
+}
+
+impl IntoRuntime for Object {
+    fn runtime_type() -> Type { Type::Complex(vec![]) }
+
+    fn into_runtime(self, _: &mut ObjectStorage) -> RuntimeObject {
+        RuntimeObject::Object(self)
+    }
+}
+
+impl FromRuntime for Handle {
+    fn runtime_type() -> Type { Type::Handle(String::new()) }
+
+    fn from_runtime(value: &RuntimeObject, _: &ObjectStorage) -> Result<Self, String> {
+        match value {
+            RuntimeObject::Handle(h) => Ok(h.clone()),
+            _ => expected(Type::Handle(String::new()), value)
+        }
+    }
+}
+
+impl IntoRuntime for Handle {
+    fn runtime_type() -> Type { Type::Handle(String::new()) }
+
+    fn into_runtime(self, _: &mut ObjectStorage) -> RuntimeObject {
+        RuntimeObject::Handle(self)
+    }
+}
+
+impl FromRuntime for ValueMap {
+    fn runtime_type() -> Type { Type::Map }
+
+    fn from_runtime(value: &RuntimeObject, _: &ObjectStorage) -> Result<Self, String> {
+        match value {
+            RuntimeObject::Map(map) => Ok(map.clone()),
+            _ => expected(Type::Map, value)
+        }
+    }
+}
+
+impl IntoRuntime for ValueMap {
+    fn runtime_type() -> Type { Type::Map }
+
+    fn into_runtime(self, _: &mut ObjectStorage) -> RuntimeObject {
+        RuntimeObject::Map(self)
+    }
+}
+
+impl<T: FromRuntime> FromRuntime for Vec<T> {
+    fn runtime_type() -> Type { Type::List(Box::new(T::runtime_type())) }
+
+    fn from_runtime(value: &RuntimeObject, storage: &ObjectStorage) -> Result<Self, String> {
+        match value {
+            RuntimeObject::List(list) => list
+                .iter()
+                .enumerate()
+                .map(|(i, it)| T::from_runtime(it, storage).map_err(|e| format!("{} at index {}", e, i)))
+                .collect(),
+            _ => expected(Self::runtime_type(), value)
+        }
+    }
+}
+
+impl<T: IntoRuntime> IntoRuntime for Vec<T> {
+    fn runtime_type() -> Type { Type::List(Box::new(T::runtime_type())) }
+
+    fn into_runtime(self, storage: &mut ObjectStorage) -> RuntimeObject {
+        RuntimeObject::List(self.into_iter().map(|it| it.into_runtime(storage)).collect())
+    }
+}
+
+/// `None` is represented by `Void`, so optional arguments are not type checked.
+impl<T: FromRuntime> FromRuntime for Option<T> {
+    fn runtime_type() -> Type { Type::Any }
+
+    fn from_runtime(value: &RuntimeObject, storage: &ObjectStorage) -> Result<Self, String> {
+        match value {
+            RuntimeObject::Void => Ok(None),
+            _ => Ok(Some(T::from_runtime(value, storage)?))
+        }
+    }
+}
+
+impl<T: IntoRuntime> IntoRuntime for Option<T> {
+    fn runtime_type() -> Type { Type::Any }
+
+    fn into_runtime(self, storage: &mut ObjectStorage) -> RuntimeObject {
+        match self {
+            Some(value) => value.into_runtime(storage),
+            None => RuntimeObject::Void
+        }
+    }
+}
+
+pub fn get_field_as<T: FromRuntime>(storage: &ObjectStorage, obj: &Object, name: &str) -> Result<T, String> {
+    match storage.get_field(obj, name.to_string()) {
+        Some(value) => T::from_runtime(&value, storage).map_err(|e| format!("{} in field {}", e, name)),
+        None => Err(format!("Missing field {}", name))
+    }
+}
+
+/// Implements `FromRuntime` and `IntoRuntime` for a host struct by mapping its fields to object fields.
+#[macro_export]
+macro_rules! runtime_struct {
+    ($name:ident { $($field:ident),* $(,)? }) => {
+        impl $crate::runtime::convert::FromRuntime for $name {
+            fn runtime_type() -> $crate::runtime::Type { $crate::runtime::Type::Complex(vec![]) }
+
+            fn from_runtime(value: &$crate::runtime::RuntimeObject, storage: &$crate::runtime::object_storage::ObjectStorage) -> Result<Self, String> {
+                let obj = <$crate::runtime::Object as $crate::runtime::convert::FromRuntime>::from_runtime(value, storage)?;
+                Ok($name {
+                    $($field: $crate::runtime::convert::get_field_as(storage, &obj, stringify!($field))?,)*
+                })
+            }
+        }
+
+        impl $crate::runtime::convert::IntoRuntime for $name {
+            fn runtime_type() -> $crate::runtime::Type { $crate::runtime::Type::Complex(vec![]) }
+
+            fn into_runtime(self, storage: &mut $crate::runtime::object_storage::ObjectStorage) -> $crate::runtime::RuntimeObject {
+                let obj = storage.allocate_object();
+                $(
+                    let value = $crate::runtime::convert::IntoRuntime::into_runtime(self.$field, storage);
+                    storage.set_field(&obj, stringify!($field).to_string(), value);
+                )*
+                $crate::runtime::RuntimeObject::Object(obj)
+            }
+        }
+    };
+}
+
+/*
+
+Typed natives
+
+ */
+
+/// Return values of typed natives, either a plain value or a `Result` with an error.
+pub trait IntoNativeResult {
+    fn runtime_type() -> Type;
+    fn into_native_result(self, storage: &mut ObjectStorage) -> Result<RuntimeObject, RuntimeError>;
+}
+
+impl<T: IntoRuntime> IntoNativeResult for T {
+    fn runtime_type() -> Type { T::runtime_type() }
+
+    fn into_native_result(self, storage: &mut ObjectStorage) -> Result<RuntimeObject, RuntimeError> {
+        Ok(self.into_runtime(storage))
+    }
+}
+
+impl<T: IntoRuntime, E: Into<RuntimeError>> IntoNativeResult for Result<T, E> {
+    fn runtime_type() -> Type { T::runtime_type() }
+
+    fn into_native_result(self, storage: &mut ObjectStorage) -> Result<RuntimeObject, RuntimeError> {
+        match self {
+            Ok(value) => Ok(value.into_runtime(storage)),
+            Err(e) => Err(e.into())
+        }
+    }
+}
+
+/// Rust functions and closures that can be registered as natives, the argument types come from the signature.
+pub trait NativeFn<Args>: Send + Sync + 'static {
+    fn arg_types() -> Vec<Type>;
+    fn return_type() -> Type;
+    fn invoke(&self, storage: &mut ObjectStorage, args: &[RuntimeObject]) -> Result<RuntimeObject, RuntimeError>;
+}
+
+/// Like `NativeFn`, for functions that take the calling runtime as first parameter.
+pub trait RuntimeNativeFn<Args>: Send + Sync + 'static {
+    fn arg_types() -> Vec<Type>;
+    fn return_type() -> Type;
+    fn invoke(&self, runtime: &mut Runtime, args: &[RuntimeObject]) -> Result<RuntimeObject, RuntimeError>;
+}
+
+fn check_arg_count(args: &[RuntimeObject], expected: usize) -> Result<(), RuntimeError> {
+    if args.len() != expected {
+        return Err(RuntimeError::new(ErrorKind::Type, format!("Expected {} arg but got {}", expected, args.len())))
+    }
+    Ok(())
+}
+
+fn convert_arg<T: FromRuntime>(args: &[RuntimeObject], i: usize, storage: &ObjectStorage) -> Result<T, RuntimeError> {
+    T::from_runtime(&args[i], storage).map_err(|e| RuntimeError::new(ErrorKind::Type, format!("{} at arg {}", e, i)))
+}
+
+macro_rules! native_fn {
+    ($($arg:ident),*) => {
+        #[allow(non_snake_case, unused_variables, unused_mut, unused_assignments)]
+        impl<Func, Ret, $($arg,)*> NativeFn<($($arg,)*)> for Func
+            where Func: Fn($($arg),*) -> Ret + Send + Sync + 'static, Ret: IntoNativeResult, $($arg: FromRuntime,)* {
+
+            fn arg_types() -> Vec<Type> {
+                vec![$($arg::runtime_type()),*]
+            }
+
+            fn return_type() -> Type {
+                Ret::runtime_type()
+            }
+
+            fn invoke(&self, storage: &mut ObjectStorage, args: &[RuntimeObject]) -> Result<RuntimeObject, RuntimeError> {
+                check_arg_count(args, Self::arg_types().len())?;
+                let mut i = 0;
+                $(
+                    let $arg = convert_arg::<$arg>(args, i, storage)?;
+                    i += 1;
+                )*
+                self($($arg),*).into_native_result(storage)
+            }
+        }
+
+        #[allow(non_snake_case, unused_variables, unused_mut, unused_assignments)]
+        impl<Func, Ret, $($arg,)*> RuntimeNativeFn<($($arg,)*)> for Func
+            where Func: Fn(&mut Runtime, $($arg),*) -> Ret + Send + Sync + 'static, Ret: IntoNativeResult, $($arg: FromRuntime,)* {
+
+            fn arg_types() -> Vec<Type> {
+                vec![$($arg::runtime_type()),*]
+            }
+
+            fn return_type() -> Type {
+                Ret::runtime_type()
+            }
+
+            fn invoke(&self, runtime: &mut Runtime, args: &[RuntimeObject]) -> Result<RuntimeObject, RuntimeError> {
+                check_arg_count(args, Self::arg_types().len())?;
+                let mut i = 0;
+                $(
+                    let $arg = convert_arg::<$arg>(args, i, &runtime.storage)?;
+                    i += 1;
+                )*
+                self(runtime, $($arg),*).into_native_result(&mut runtime.storage)
+            }
+        }
+    };
+}
+
+native_fn!();
+native_fn!(A);
+native_fn!(A, B);
+native_fn!(A, B, C);
+native_fn!(A, B, C, D);
+native_fn!(A, B, C, D, E);
+native_fn!(A, B, C, D, E, F);
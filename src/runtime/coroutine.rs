@@ -0,0 +1,41 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::runtime::{Frame, Function, RuntimeObject};
+
+/*
+
+Coroutine: a call whose frames are kept between resumes
+
+ */
+
+pub struct Coroutine {
+    pub signature: String,
+    pub(crate) function: Arc<Function>,
+    pub(crate) args: Vec<RuntimeObject>,
+    pub(crate) state: RefCell<CoroutineState>
+}
+
+pub(crate) enum CoroutineState {
+    Suspended { frames: Vec<Frame>, variables: HashMap<String, RuntimeObject> },
+    Running,
+    Done
+}
+
+impl Coroutine {
+
+    pub fn new(signature: &str, function: Arc<Function>, args: Vec<RuntimeObject>) -> Coroutine {
+        Coroutine {
+            signature: signature.to_string(),
+            function,
+            args,
+            state: RefCell::new(CoroutineState::Suspended { frames: vec![Frame::body()], variables: HashMap::new() })
+        }
+    }
+
+    /// Whether the function returned or failed, a done coroutine cannot be resumed.
+    pub fn is_done(&self) -> bool {
+        matches!(*self.state.borrow(), CoroutineState::Done)
+    }
+}
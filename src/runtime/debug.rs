@@ -0,0 +1,207 @@
+use std::collections::{BTreeSet, HashMap};
+
+use crate::runtime::object_storage::ObjectStorage;
+use crate::runtime::{Operation, Runtime, RuntimeObject};
+
+/*
+
+Debugging: an attached debugger is asked before every instruction whether to pause,
+without one the frame machine only pays for a single check
+
+Instructions are numbered per function in the order they appear in the source, blocks included,
+e.g. the first instruction inside an `if` at index 3 is index 4
+
+ */
+
+/// How the program goes on after a pause.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Resume {
+    Continue,
+    //pause at the next instruction, also inside called functions
+    StepIn,
+    //pause at the next instruction of this or a calling function
+    StepOver,
+    //pause once this function returned
+    StepOut,
+    //end the program, every running function unwinds with `ErrorKind::Terminated`
+    Terminate
+}
+
+/// A running function as seen from a pause.
+pub struct CallFrame {
+    pub signature: String,
+    //index of the instruction that runs next
+    pub index: usize,
+    pub args: Vec<RuntimeObject>,
+    //sorted by name
+    pub variables: Vec<(String, RuntimeObject)>,
+    //operand stack of the function, outer blocks first
+    pub stack: Vec<RuntimeObject>
+}
+
+/// Breakpoints by function signature and instruction index.
+#[derive(Default)]
+pub struct Breakpoints {
+    functions: HashMap<String, BTreeSet<usize>>
+}
+
+impl Breakpoints {
+
+    pub fn new() -> Breakpoints {
+        Breakpoints::default()
+    }
+
+    pub fn add(&mut self, signature: &str, index: usize) {
+        self.functions.entry(signature.to_string()).or_default().insert(index);
+    }
+
+    /// Returns whether there was a breakpoint to remove.
+    pub fn remove(&mut self, signature: &str, index: usize) -> bool {
+        self.functions.get_mut(signature).is_some_and(|it| it.remove(&index))
+    }
+
+    pub fn clear(&mut self, signature: &str) {
+        self.functions.remove(signature);
+    }
+
+    pub fn contains(&self, signature: &str, index: usize) -> bool {
+        self.functions.get(signature).is_some_and(|it| it.contains(&index))
+    }
+
+    pub fn has_function(&self, signature: &str) -> bool {
+        self.functions.get(signature).is_some_and(|it| !it.is_empty())
+    }
+
+    /// All breakpoints sorted by signature and index.
+    pub fn list(&self) -> Vec<(&str, usize)> {
+        let mut list: Vec<(&str, usize)> = self.functions.iter()
+            .flat_map(|(signature, indices)| indices.iter().map(|index| (signature.as_str(), *index)))
+            .collect();
+        list.sort();
+        list
+    }
+}
+
+/// Talks to the user while the program is paused, e.g. a command line shell or an editor.
+pub trait DebugFrontend {
+    /// Blocks until the user resumes, the paused function is the last of `calls`.
+    fn paused(&mut self, runtime: &mut Runtime, calls: &[CallFrame], breakpoints: &mut Breakpoints) -> Resume;
+}
+
+pub struct Debugger {
+    pub breakpoints: Breakpoints,
+    frontend: Box<dyn DebugFrontend>,
+    pub(crate) calls: Vec<CallFrame>,
+    resume: Resume,
+    //number of running functions when the user resumed, steps are relative to it
+    depth: usize
+}
+
+impl Debugger {
+
+    /// Runs until the first breakpoint, or pauses at the first instruction with `Resume::StepIn`.
+    pub fn new(frontend: Box<dyn DebugFrontend>, breakpoints: Breakpoints, resume: Resume) -> Debugger {
+        Debugger { breakpoints, frontend, calls: vec![], resume, depth: 0 }
+    }
+
+    pub(crate) fn should_pause(&self, signature: &str, index: usize) -> bool {
+        let depth = self.calls.len();
+        let stepped = match self.resume {
+            Resume::Continue => false,
+            Resume::StepIn => true,
+            Resume::StepOver => depth <= self.depth,
+            Resume::StepOut => depth < self.depth,
+            Resume::Terminate => false
+        };
+        stepped || self.breakpoints.contains(signature, index)
+    }
+
+    pub(crate) fn terminated(&self) -> bool {
+        self.resume == Resume::Terminate
+    }
+
+    pub(crate) fn pause(&mut self, runtime: &mut Runtime) {
+        self.resume = self.frontend.paused(runtime, &self.calls, &mut self.breakpoints);
+        self.depth = self.calls.len();
+    }
+}
+
+/// Runs `load`, `loadArg` and `getProp` against a paused function without changing it, e.g. `load p getProp x`.
+pub fn evaluate(storage: &ObjectStorage, call: &CallFrame, expression: &str) -> Result<RuntimeObject, String> {
+    let mut value = None;
+    let mut words = expression.split_whitespace();
+    while let Some(word) = words.next() {
+        let operand = words.next().ok_or(format!("Expected an operand after {}", word))?;
+        value = Some(match (word, value) {
+            ("load", None) => call.variables.iter()
+                .find(|(name, _)| name == operand)
+                .map(|(_, value)| value.clone())
+                .ok_or(format!("No variable {}", operand))?,
+            ("loadArg", None) => operand.parse::<usize>().ok()
+                .and_then(|arg| call.args.get(arg).cloned())
+                .ok_or(format!("No argument {}", operand))?,
+            ("getProp", Some(RuntimeObject::Object(o))) => storage.get_field(&o, operand.to_string())
+                .ok_or(format!("Property {} does not exist on {}", operand, o.display_name()))?,
+            ("getProp", Some(other)) => return Err(format!("Expected an object for getProp but got {}", other.get_type())),
+            ("load" | "loadArg", Some(_)) => return Err(format!("{} has to come first", word)),
+            (other, _) => return Err(format!("Cannot evaluate {}, only load, loadArg and getProp", other))
+        });
+    }
+    value.ok_or("Expected an expression, e.g. load x getProp name".to_string())
+}
+
+/// Blocks of an instruction, in the numbering of the frame machine.
+pub fn blocks(instruction: &Operation) -> Vec<&[Operation]> {
+    match instruction {
+        Operation::If(content) | Operation::Else(content) | Operation::AndThen(content) | Operation::OrElse(content) => vec![content],
+        Operation::While { condition, content } => vec![condition, content],
+        Operation::Try { content, catch, .. } => vec![content, catch],
+        Operation::Match { cases, .. } => cases.iter().map(|it| it.content.as_slice()).collect(),
+        _ => vec![]
+    }
+}
+
+fn flat_len(code: &[Operation]) -> usize {
+    code.iter().map(|it| 1 + blocks(it).iter().map(|block| flat_len(block)).sum::<usize>()).sum()
+}
+
+/// Index of instruction `pc` in the block at `path` of a function body.
+pub fn instruction_index(body: &[Operation], path: &[(usize, usize)], pc: usize) -> usize {
+    let mut code = body;
+    let mut index = 0;
+    for (instruction, block) in path {
+        let blocks = blocks(&code[*instruction]);
+        index += flat_len(&code[..*instruction]) + 1 + blocks[..*block].iter().map(|it| flat_len(it)).sum::<usize>();
+        code = blocks[*block];
+    }
+    index + flat_len(&code[..pc])
+}
+
+/// Instructions of a function body in index order with their block depth.
+pub fn flatten(body: &[Operation]) -> Vec<(usize, &Operation)> {
+    fn walk<'a>(code: &'a [Operation], depth: usize, out: &mut Vec<(usize, &'a Operation)>) {
+        for instruction in code {
+            out.push((depth, instruction));
+            blocks(instruction).into_iter().for_each(|block| walk(block, depth + 1, out));
+        }
+    }
+    let mut out = vec![];
+    walk(body, 0, &mut out);
+    out
+}
+
+/// One line description of an instruction, without the contents of its blocks.
+pub fn describe(instruction: &Operation) -> String {
+    match instruction {
+        Operation::If(_) => "IfDo".to_string(),
+        Operation::Else(_) => "ElseDo".to_string(),
+        Operation::AndThen(_) => "AndDo".to_string(),
+        Operation::OrElse(_) => "OrDo".to_string(),
+        Operation::While { .. } => "While".to_string(),
+        Operation::Try { name, .. } => format!("Try catch {}", name),
+        Operation::LoadConstString(s) => format!("LoadConstString({})", s),
+        Operation::LoadConstBool(b) => format!("LoadConstBool({})", b),
+        Operation::LoadArg(arg) => format!("LoadArg({})", arg),
+        other => other.to_string()
+    }
+}
@@ -0,0 +1,89 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ErrorKind {
+    Runtime,
+    Type,
+    //integer overflow and division by zero
+    Arithmetic,
+    NotFound,
+    Io,
+    PermissionDenied,
+    //an instruction needed more values than the stack of its block holds
+    StackUnderflow,
+    //load of a variable that was never set or of an argument that was not passed
+    UnknownVariable,
+    //std/process/exit, unwinds past every catch so the host decides what to do with the code
+    Exit(i32),
+    //the debugger ended the program, unwinds past every catch as well
+    Terminated,
+    //kind raised by host natives, named by the host
+    Native(String)
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ErrorKind::Runtime => f.write_str("RuntimeError"),
+            ErrorKind::Type => f.write_str("TypeError"),
+            ErrorKind::Arithmetic => f.write_str("ArithmeticError"),
+            ErrorKind::NotFound => f.write_str("NotFoundError"),
+            ErrorKind::Io => f.write_str("IoError"),
+            ErrorKind::PermissionDenied => f.write_str("PermissionDenied"),
+            ErrorKind::StackUnderflow => f.write_str("StackUnderflowError"),
+            ErrorKind::UnknownVariable => f.write_str("UnknownVariableError"),
+            ErrorKind::Exit(_) => f.write_str("Exit"),
+            ErrorKind::Terminated => f.write_str("Terminated"),
+            ErrorKind::Native(name) => f.write_str(name)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuntimeError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub trace: Vec<String>
+}
+
+impl RuntimeError {
+
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> RuntimeError {
+        RuntimeError { kind, message: message.into(), trace: vec![] }
+    }
+
+    pub fn native(kind: &str, message: impl Into<String>) -> RuntimeError {
+        RuntimeError::new(ErrorKind::Native(kind.to_string()), message)
+    }
+
+    /// Appends a line to the trace, innermost call first.
+    pub fn trace(mut self, line: String) -> RuntimeError {
+        self.trace.push(line);
+        self
+    }
+}
+
+impl From<String> for RuntimeError {
+    fn from(message: String) -> Self {
+        RuntimeError::new(ErrorKind::Runtime, message)
+    }
+}
+
+impl From<&str> for RuntimeError {
+    fn from(message: &str) -> Self {
+        RuntimeError::new(ErrorKind::Runtime, message)
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{}: {}", self.kind, self.message))?;
+        for line in &self.trace {
+            f.write_fmt(format_args!("\n {}", line))?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for RuntimeError {}
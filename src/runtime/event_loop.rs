@@ -0,0 +1,232 @@
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::runtime::error::{ErrorKind, RuntimeError};
+use crate::runtime::handle::Handle;
+use crate::runtime::{Runtime, RuntimeObject};
+
+/*
+
+Event loop: timers and operations running on other threads, callbacks always run on the script thread
+
+ */
+
+/// Turns the result of an async operation into a value on the script thread, runtime values are not `Send`.
+pub type Completion = Box<dyn FnOnce(&mut Runtime) -> Result<RuntimeObject, RuntimeError> + Send>;
+
+struct Timer {
+    id: i64,
+    due: Instant,
+    interval: Option<Duration>,
+    callback: String
+}
+
+pub struct EventLoop {
+    timers: Vec<Timer>,
+    next_timer: i64,
+    //promises of operations still running on other threads
+    pending: HashMap<u64, Handle>,
+    next_operation: u64,
+    //settled promises whose callbacks did not run yet
+    ready: VecDeque<Handle>,
+    sender: Sender<(u64, Completion)>,
+    receiver: Receiver<(u64, Completion)>
+}
+
+impl Default for EventLoop {
+    fn default() -> Self {
+        EventLoop::new()
+    }
+}
+
+impl EventLoop {
+
+    pub fn new() -> EventLoop {
+        let (sender, receiver) = channel();
+        EventLoop { timers: vec![], next_timer: 0, pending: HashMap::new(), next_operation: 0, ready: VecDeque::new(), sender, receiver }
+    }
+
+    /// Whether timers, operations or callbacks are left, the loop is done once there are none.
+    pub fn has_work(&self) -> bool {
+        !self.timers.is_empty() || !self.pending.is_empty() || !self.ready.is_empty()
+    }
+}
+
+#[derive(Clone)]
+pub enum PromiseState {
+    Pending,
+    Resolved(RuntimeObject),
+    Rejected(RuntimeError)
+}
+
+/// Result of an async operation, scripts get it as a handle tagged `promise`.
+pub struct Promise {
+    state: RefCell<PromiseState>,
+    //callback signatures, the flag is true for `then` and false for `catch`
+    callbacks: RefCell<Vec<(String, bool)>>,
+    //a rejection is uncaught unless a catch callback or an await takes it
+    handled: Cell<bool>
+}
+
+impl Promise {
+
+    /// A pending promise wrapped in its handle.
+    pub fn pending() -> Handle {
+        Handle::new("promise", Promise { state: RefCell::new(PromiseState::Pending), callbacks: RefCell::new(vec![]), handled: Cell::new(false) })
+    }
+
+    pub fn state(&self) -> PromiseState {
+        self.state.borrow().clone()
+    }
+}
+
+fn promise(handle: &Handle) -> Result<&Promise, RuntimeError> {
+    Ok(handle.expect::<Promise>("promise")?)
+}
+
+impl Runtime {
+
+    /// Calls `callback` once after `delay`, or every `delay` if `repeat` is set. Returns the id for `clear_timer`.
+    pub fn set_timer(&mut self, callback: &str, delay: Duration, repeat: bool) -> i64 {
+        let event_loop = &mut self.event_loop;
+        event_loop.next_timer += 1;
+        event_loop.timers.push(Timer {
+            id: event_loop.next_timer,
+            due: Instant::now() + delay,
+            interval: repeat.then_some(delay),
+            callback: callback.to_string()
+        });
+        event_loop.next_timer
+    }
+
+    pub fn clear_timer(&mut self, id: i64) -> bool {
+        let before = self.event_loop.timers.len();
+        self.event_loop.timers.retain(|it| it.id != id);
+        before != self.event_loop.timers.len()
+    }
+
+    /// Runs `work` on a new thread, the returned promise settles with its completion on the script thread.
+    pub fn spawn_async<F>(&mut self, work: F) -> Handle where F: FnOnce() -> Completion + Send + 'static {
+        let handle = Promise::pending();
+        let event_loop = &mut self.event_loop;
+        event_loop.next_operation += 1;
+        let id = event_loop.next_operation;
+        event_loop.pending.insert(id, handle.clone());
+
+        let sender = event_loop.sender.clone();
+        thread::spawn(move || {
+            //the loop owns the receiver, so it only fails once the runtime is gone
+            let _ = sender.send((id, work()));
+        });
+        handle
+    }
+
+    /// Settles a pending promise, its callbacks run on the next turn of the loop.
+    pub fn settle(&mut self, handle: &Handle, result: Result<RuntimeObject, RuntimeError>) -> Result<(), RuntimeError> {
+        let promise = promise(handle)?;
+        if !matches!(*promise.state.borrow(), PromiseState::Pending) {
+            return Err(RuntimeError::new(ErrorKind::Runtime, "Promise is already settled".to_string()))
+        }
+        promise.state.replace(match result {
+            Ok(value) => PromiseState::Resolved(value),
+            Err(e) => PromiseState::Rejected(e)
+        });
+        self.event_loop.ready.push_back(handle.clone());
+        Ok(())
+    }
+
+    /// Registers a callback taking the value (`then`) or the error object (`catch`) of the promise.
+    pub fn on_settled(&mut self, handle: &Handle, callback: &str, then: bool) -> Result<(), RuntimeError> {
+        let promise = promise(handle)?;
+        promise.callbacks.borrow_mut().push((callback.to_string(), then));
+        if !then {
+            promise.handled.set(true);
+        }
+        if !matches!(*promise.state.borrow(), PromiseState::Pending) {
+            self.event_loop.ready.push_back(handle.clone());
+        }
+        Ok(())
+    }
+
+    /// Runs the loop until nothing is left, errors of callbacks and rejections nothing catches stop it.
+    pub fn run_event_loop(&mut self) -> Result<(), RuntimeError> {
+        while self.turn()? {}
+        Ok(())
+    }
+
+    /// Keeps the loop running until the promise settles, a rejection is returned as the error.
+    pub fn await_promise(&mut self, handle: &Handle) -> Result<RuntimeObject, RuntimeError> {
+        promise(handle)?.handled.set(true);
+        loop {
+            match promise(handle)?.state() {
+                PromiseState::Resolved(value) => return Ok(value),
+                PromiseState::Rejected(e) => return Err(e),
+                PromiseState::Pending => if !self.turn()? {
+                    return Err(RuntimeError::new(ErrorKind::Runtime, "Awaited promise can never settle, nothing is pending".to_string()))
+                }
+            }
+        }
+    }
+
+    //callbacks first, then due timers, then waits for the next operation or timer, false if there is nothing to do
+    fn turn(&mut self) -> Result<bool, RuntimeError> {
+        if let Some(handle) = self.event_loop.ready.pop_front() {
+            self.run_callbacks(&handle)?;
+            return Ok(true)
+        }
+
+        let now = Instant::now();
+        let next = self.event_loop.timers.iter().enumerate().min_by_key(|(_, it)| it.due).map(|(i, it)| (i, it.due));
+        if let Some((index, due)) = next {
+            if due <= now {
+                let timer = &mut self.event_loop.timers[index];
+                let callback = timer.callback.clone();
+                match timer.interval {
+                    Some(interval) => timer.due = now + interval,
+                    None => { self.event_loop.timers.remove(index); }
+                }
+                self.call(&callback, vec![])?;
+                return Ok(true)
+            }
+        }
+
+        if !self.event_loop.has_work() {
+            return Ok(false)
+        }
+        let wait = next.map(|(_, due)| due.saturating_duration_since(now));
+        if self.event_loop.pending.is_empty() {
+            thread::sleep(wait.unwrap_or_default());
+            return Ok(true)
+        }
+        let received = match wait {
+            Some(wait) => self.event_loop.receiver.recv_timeout(wait).ok(),
+            None => self.event_loop.receiver.recv().ok()
+        };
+        if let Some((id, completion)) = received {
+            if let Some(handle) = self.event_loop.pending.remove(&id) {
+                let result = completion(self);
+                self.settle(&handle, result)?;
+            }
+        }
+        Ok(true)
+    }
+
+    fn run_callbacks(&mut self, handle: &Handle) -> Result<(), RuntimeError> {
+        let promise = promise(handle)?;
+        let callbacks = promise.callbacks.take();
+        let (value, then) = match promise.state() {
+            PromiseState::Resolved(value) => (value, true),
+            //nobody would ever see the error otherwise
+            PromiseState::Rejected(e) if !promise.handled.get() => return Err(e.trace("in a promise without a catch callback".to_string())),
+            PromiseState::Rejected(e) => (self.error_object(&e), false),
+            PromiseState::Pending => return Ok(())
+        };
+        for (callback, _) in callbacks.iter().filter(|(_, it)| *it == then) {
+            self.call(callback, vec![value.clone()])?;
+        }
+        Ok(())
+    }
+}
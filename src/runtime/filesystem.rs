@@ -0,0 +1,203 @@
+pub mod memory;
+pub mod overlay;
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+pub use memory::MemoryFileSystem;
+pub use overlay::OverlayFileSystem;
+
+/*
+
+FileSystem: backend the file natives go through, so scripts can run against disk or memory
+
+ */
+
+/// Flags a file is opened with, parsed from the r/w/a/c/t mode letters.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OpenMode {
+    pub read: bool,
+    pub write: bool,
+    pub append: bool,
+    pub create: bool,
+    pub truncate: bool
+}
+
+impl OpenMode {
+
+    pub fn parse(mode: &str) -> Result<OpenMode, String> {
+        let mut result = OpenMode::default();
+        for c in mode.chars() {
+            match c {
+                'r' => result.read = true,
+                'w' => result.write = true,
+                'a' => result.append = true,
+                'c' => result.create = true,
+                't' => result.truncate = true,
+                _ => return Err(format!("Invalid file mode '{}' in '{}'", c, mode))
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn writes(&self) -> bool {
+        self.write || self.append || self.create || self.truncate
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileInfo {
+    pub is_file: bool,
+    pub is_dir: bool,
+    pub length: u64,
+    pub readonly: bool,
+    //seconds since the unix epoch, if the backend knows it
+    pub modified: Option<f64>
+}
+
+impl From<fs::Metadata> for FileInfo {
+    fn from(data: fs::Metadata) -> Self {
+        FileInfo {
+            is_file: data.is_file(),
+            is_dir: data.is_dir(),
+            length: data.len(),
+            readonly: data.permissions().readonly(),
+            modified: data.modified().ok()
+                .and_then(|it| it.duration_since(UNIX_EPOCH).ok())
+                .map(|it| it.as_secs_f64())
+        }
+    }
+}
+
+/// An open file of any backend.
+pub trait FileStream: Read + Write + Seek {
+    fn info(&self) -> io::Result<FileInfo>;
+}
+
+impl FileStream for File {
+    fn info(&self) -> io::Result<FileInfo> {
+        self.metadata().map(FileInfo::from)
+    }
+}
+
+/// Paths are passed as the script wrote them, backends decide how to resolve them.
+pub trait FileSystem: Send + Sync {
+    fn open(&self, path: &str, mode: OpenMode) -> io::Result<Box<dyn FileStream>>;
+    fn metadata(&self, path: &str) -> io::Result<FileInfo>;
+    fn remove(&self, path: &str) -> io::Result<()>;
+    fn rename(&self, from: &str, to: &str) -> io::Result<()>;
+    fn create_dir_all(&self, path: &str) -> io::Result<()>;
+    /// Names of the entries in a directory, sorted.
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>>;
+
+    /// Path the permission checks compare, `..` folded and links followed as far as the backend has them.
+    fn resolve(&self, path: &str) -> PathBuf {
+        normalize_path(&std::env::current_dir().unwrap_or_default().join(path))
+    }
+
+    fn exists(&self, path: &str) -> bool {
+        self.metadata(path).is_ok()
+    }
+}
+
+/// The real disk, via `std::fs`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DiskFileSystem;
+
+impl FileSystem for DiskFileSystem {
+    fn open(&self, path: &str, mode: OpenMode) -> io::Result<Box<dyn FileStream>> {
+        let file = OpenOptions::new()
+            .read(mode.read)
+            .write(mode.write)
+            .append(mode.append)
+            .create(mode.create)
+            .truncate(mode.truncate)
+            .open(path)?;
+        Ok(Box::new(file))
+    }
+
+    fn metadata(&self, path: &str) -> io::Result<FileInfo> {
+        fs::metadata(path).map(FileInfo::from)
+    }
+
+    fn remove(&self, path: &str) -> io::Result<()> {
+        match fs::metadata(path) {
+            Ok(data) if data.is_dir() => fs::remove_dir(path),
+            _ => fs::remove_file(path)
+        }
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn create_dir_all(&self, path: &str) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        let mut names = vec![];
+        for entry in fs::read_dir(path)? {
+            names.push(entry?.file_name().to_string_lossy().to_string());
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    fn resolve(&self, path: &str) -> PathBuf {
+        resolve_disk_path(Path::new(path))
+    }
+}
+
+/// Folds `.` and `..` without touching the disk, `..` never climbs above the start of the path.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {},
+            Component::ParentDir => {
+                result.pop();
+            },
+            other => result.push(other)
+        }
+    }
+    result
+}
+
+/// Makes a path absolute, follows symlinks of the part that exists on disk and folds `..` in the rest.
+fn resolve_disk_path(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir().unwrap_or_default().join(path)
+    };
+
+    //walk up until an existing ancestor can be canonicalized
+    let mut base = absolute.as_path();
+    let mut rest = vec![];
+    let mut resolved = loop {
+        if let Ok(canonical) = base.canonicalize() {
+            break canonical
+        }
+        let parent = base.parent().unwrap_or(base);
+        match base.components().next_back() {
+            Some(Component::Normal(name)) => rest.push(name.to_os_string()),
+            Some(Component::ParentDir) => {
+                let mut parent = resolve_disk_path(parent);
+                parent.pop();
+                break parent
+            }
+            Some(Component::CurDir) => {},
+            _ => break base.to_path_buf()
+        }
+        base = parent;
+    };
+
+    //the remaining components do not exist yet, so they cannot be symlinks
+    for name in rest.iter().rev() {
+        resolved.push(name);
+    }
+    resolved
+}
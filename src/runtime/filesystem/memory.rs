@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::runtime::filesystem::{normalize_path, FileInfo, FileStream, FileSystem, OpenMode};
+
+/*
+
+In-memory filesystem: clones share the same files, so a host can keep one to preload and inspect
+
+ */
+
+#[derive(Clone)]
+enum Entry {
+    File(Arc<Mutex<Vec<u8>>>),
+    Dir
+}
+
+#[derive(Clone, Default)]
+pub struct MemoryFileSystem {
+    entries: Arc<Mutex<HashMap<PathBuf, Entry>>>
+}
+
+fn key(path: &str) -> PathBuf {
+    normalize_path(Path::new(path))
+}
+
+fn is_root(path: &Path) -> bool {
+    path.as_os_str().is_empty() || path == Path::new("/")
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(ErrorKind::NotFound, format!("No such file or directory: {}", path.display()))
+}
+
+pub(crate) fn into_itself(from: &Path, to: &Path) -> io::Error {
+    io::Error::new(ErrorKind::InvalidInput, format!("Cannot move {} into itself at {}", from.display(), to.display()))
+}
+
+impl MemoryFileSystem {
+
+    pub fn new() -> MemoryFileSystem {
+        MemoryFileSystem::default()
+    }
+
+    /// Creates or replaces a file, creating missing parent directories.
+    pub fn write(&self, path: &str, content: impl Into<Vec<u8>>) {
+        let path = key(path);
+        let mut entries = self.lock();
+        if let Some(parent) = path.parent() {
+            insert_dirs(&mut entries, parent);
+        }
+        entries.insert(path, Entry::File(Arc::new(Mutex::new(content.into()))));
+    }
+
+    pub fn read(&self, path: &str) -> Option<Vec<u8>> {
+        match self.lock().get(&key(path)) {
+            Some(Entry::File(data)) => Some(data.lock().unwrap().clone()),
+            _ => None
+        }
+    }
+
+    pub fn read_to_string(&self, path: &str) -> Option<String> {
+        self.read(path).and_then(|it| String::from_utf8(it).ok())
+    }
+
+    /// Paths of all files, sorted.
+    pub fn files(&self) -> Vec<String> {
+        let mut files: Vec<String> = self.lock().iter()
+            .filter(|(_, entry)| matches!(entry, Entry::File(_)))
+            .map(|(path, _)| path.to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<PathBuf, Entry>> {
+        self.entries.lock().unwrap()
+    }
+
+    fn is_dir(entries: &HashMap<PathBuf, Entry>, path: &Path) -> bool {
+        is_root(path) || matches!(entries.get(path), Some(Entry::Dir))
+    }
+
+    fn check_parent(entries: &HashMap<PathBuf, Entry>, path: &Path) -> io::Result<()> {
+        match path.parent() {
+            Some(parent) if !MemoryFileSystem::is_dir(entries, parent) => Err(not_found(parent)),
+            _ => Ok(())
+        }
+    }
+}
+
+fn insert_dirs(entries: &mut HashMap<PathBuf, Entry>, path: &Path) {
+    for dir in path.ancestors().filter(|it| !is_root(it)) {
+        entries.entry(dir.to_path_buf()).or_insert(Entry::Dir);
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn open(&self, path: &str, mode: OpenMode) -> io::Result<Box<dyn FileStream>> {
+        if (mode.create || mode.truncate) && !(mode.write || mode.append) {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "create and truncate need write or append access"))
+        }
+        let path = key(path);
+        let mut entries = self.lock();
+        let data = match entries.get(&path) {
+            Some(Entry::File(data)) => data.clone(),
+            Some(Entry::Dir) => return Err(io::Error::other(format!("Is a directory: {}", path.display()))),
+            None if is_root(&path) => return Err(io::Error::other(format!("Is a directory: {}", path.display()))),
+            None if mode.create => {
+                MemoryFileSystem::check_parent(&entries, &path)?;
+                let data = Arc::new(Mutex::new(vec![]));
+                entries.insert(path, Entry::File(data.clone()));
+                data
+            },
+            None => return Err(not_found(&path))
+        };
+        if mode.truncate {
+            data.lock().unwrap().clear();
+        }
+        Ok(Box::new(MemoryFile { data, position: 0, mode }))
+    }
+
+    fn metadata(&self, path: &str) -> io::Result<FileInfo> {
+        let path = key(path);
+        let entries = self.lock();
+        let length = match entries.get(&path) {
+            Some(Entry::File(data)) => Some(data.lock().unwrap().len() as u64),
+            Some(Entry::Dir) => None,
+            None if is_root(&path) => None,
+            None => return Err(not_found(&path))
+        };
+        Ok(FileInfo { is_file: length.is_some(), is_dir: length.is_none(), length: length.unwrap_or(0), readonly: false, modified: None })
+    }
+
+    fn remove(&self, path: &str) -> io::Result<()> {
+        let path = key(path);
+        let mut entries = self.lock();
+        match entries.get(&path) {
+            Some(Entry::Dir) if entries.keys().any(|it| it.parent() == Some(path.as_path())) =>
+                Err(io::Error::other(format!("Directory not empty: {}", path.display()))),
+            Some(_) => {
+                entries.remove(&path);
+                Ok(())
+            },
+            None => Err(not_found(&path))
+        }
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let (from, to) = (key(from), key(to));
+        let mut entries = self.lock();
+        if !entries.contains_key(&from) {
+            return Err(not_found(&from))
+        }
+        if to == from {
+            return Ok(())
+        }
+        if to.starts_with(&from) {
+            return Err(into_itself(&from, &to))
+        }
+        MemoryFileSystem::check_parent(&entries, &to)?;
+        //a directory takes everything below it along
+        let moved: Vec<PathBuf> = entries.keys().filter(|it| it.starts_with(&from)).cloned().collect();
+        for old in moved {
+            let entry = entries.remove(&old).unwrap();
+            let new = to.join(old.strip_prefix(&from).unwrap());
+            entries.insert(normalize_path(&new), entry);
+        }
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &str) -> io::Result<()> {
+        let path = key(path);
+        let mut entries = self.lock();
+        if let Some(file) = path.ancestors().find(|it| matches!(entries.get(*it), Some(Entry::File(_)))) {
+            return Err(io::Error::new(ErrorKind::AlreadyExists, format!("File exists: {}", file.display())))
+        }
+        insert_dirs(&mut entries, &path);
+        Ok(())
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        let path = key(path);
+        let entries = self.lock();
+        if !MemoryFileSystem::is_dir(&entries, &path) {
+            return Err(not_found(&path))
+        }
+        let mut names: Vec<String> = entries.keys()
+            .filter(|it| it.parent() == Some(path.as_path()))
+            .filter_map(|it| it.file_name())
+            .map(|it| it.to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        Ok(names)
+    }
+
+    //files are keyed by the path as written, there are no links to follow
+    fn resolve(&self, path: &str) -> PathBuf {
+        key(path)
+    }
+}
+
+/*
+
+Memory file
+
+ */
+
+struct MemoryFile {
+    data: Arc<Mutex<Vec<u8>>>,
+    position: u64,
+    mode: OpenMode
+}
+
+fn bad_access(access: &str) -> io::Error {
+    io::Error::new(ErrorKind::PermissionDenied, format!("File was not opened for {}", access))
+}
+
+impl Read for MemoryFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.mode.read {
+            return Err(bad_access("reading"))
+        }
+        let data = self.data.lock().unwrap();
+        let start = (self.position as usize).min(data.len());
+        let count = buf.len().min(data.len() - start);
+        buf[..count].copy_from_slice(&data[start..start + count]);
+        self.position += count as u64;
+        Ok(count)
+    }
+}
+
+impl Write for MemoryFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !(self.mode.write || self.mode.append) {
+            return Err(bad_access("writing"))
+        }
+        let mut data = self.data.lock().unwrap();
+        if self.mode.append {
+            self.position = data.len() as u64;
+        }
+        let start = self.position as usize;
+        if data.len() < start + buf.len() {
+            data.resize(start + buf.len(), 0);
+        }
+        data[start..start + buf.len()].copy_from_slice(buf);
+        self.position += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for MemoryFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => (self.data.lock().unwrap().len() as u64).checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset)
+        };
+        match position {
+            Some(p) => {
+                self.position = p;
+                Ok(p)
+            },
+            None => Err(io::Error::new(ErrorKind::InvalidInput, "Seek before the start of the file"))
+        }
+    }
+}
+
+impl FileStream for MemoryFile {
+    fn info(&self) -> io::Result<FileInfo> {
+        let length = self.data.lock().unwrap().len() as u64;
+        Ok(FileInfo { is_file: true, is_dir: false, length, readonly: false, modified: None })
+    }
+}
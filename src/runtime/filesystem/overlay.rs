@@ -0,0 +1,185 @@
+use std::collections::HashSet;
+use std::io::{self, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::runtime::filesystem::memory::into_itself;
+use crate::runtime::filesystem::{normalize_path, DiskFileSystem, FileInfo, FileStream, FileSystem, MemoryFileSystem, OpenMode};
+
+/*
+
+Overlay filesystem: writes land in memory, reads fall through to the disk
+
+ */
+
+#[derive(Clone, Default)]
+pub struct OverlayFileSystem {
+    memory: MemoryFileSystem,
+    disk: DiskFileSystem,
+    //disk paths that were removed in the overlay
+    removed: Arc<Mutex<HashSet<PathBuf>>>
+}
+
+impl OverlayFileSystem {
+
+    pub fn new() -> OverlayFileSystem {
+        OverlayFileSystem::default()
+    }
+
+    /// The written layer, files are keyed by their absolute path.
+    pub fn memory(&self) -> &MemoryFileSystem {
+        &self.memory
+    }
+
+    //memory keys are absolute so `a.txt` and `./a.txt` and `/cwd/a.txt` hit the same file
+    fn key(path: &str) -> String {
+        let absolute = std::env::current_dir().unwrap_or_default().join(path);
+        normalize_path(&absolute).to_string_lossy().to_string()
+    }
+
+    fn in_memory(&self, key: &str) -> bool {
+        self.memory.exists(key)
+    }
+
+    fn is_removed(&self, key: &str) -> bool {
+        let removed = self.removed.lock().unwrap();
+        Path::new(key).ancestors().any(|it| removed.contains(it))
+    }
+
+    fn restore(&self, key: &str) {
+        self.removed.lock().unwrap().remove(Path::new(key));
+    }
+
+    fn not_found(path: &str) -> io::Error {
+        io::Error::new(ErrorKind::NotFound, format!("No such file or directory: {}", path))
+    }
+
+    /// Brings a disk file into memory so it can be changed there.
+    fn copy_up(&self, path: &str, key: &str) -> io::Result<()> {
+        let mut content = vec![];
+        self.disk.open(path, OpenMode { read: true, ..OpenMode::default() })?.read_to_end(&mut content)?;
+        self.memory.write(key, content);
+        Ok(())
+    }
+
+    fn parent_is_dir(&self, path: &str) -> bool {
+        match Path::new(&OverlayFileSystem::key(path)).parent() {
+            Some(parent) => self.metadata(&parent.to_string_lossy()).map(|it| it.is_dir).unwrap_or(false),
+            None => true
+        }
+    }
+}
+
+impl FileSystem for OverlayFileSystem {
+    fn open(&self, path: &str, mode: OpenMode) -> io::Result<Box<dyn FileStream>> {
+        let key = OverlayFileSystem::key(path);
+        if self.in_memory(&key) {
+            return self.memory.open(&key, mode)
+        }
+
+        let on_disk = !self.is_removed(&key) && self.disk.exists(path);
+        if !mode.writes() {
+            return if on_disk { self.disk.open(path, mode) } else { Err(OverlayFileSystem::not_found(path)) }
+        }
+
+        if on_disk {
+            if !mode.truncate {
+                self.copy_up(path, &key)?;
+            } else {
+                self.memory.write(&key, vec![]);
+            }
+        } else if mode.create && self.parent_is_dir(path) {
+            self.memory.write(&key, vec![]);
+        } else {
+            return Err(OverlayFileSystem::not_found(path))
+        }
+        self.restore(&key);
+        self.memory.open(&key, mode)
+    }
+
+    fn metadata(&self, path: &str) -> io::Result<FileInfo> {
+        let key = OverlayFileSystem::key(path);
+        if self.in_memory(&key) {
+            return self.memory.metadata(&key)
+        }
+        if self.is_removed(&key) {
+            return Err(OverlayFileSystem::not_found(path))
+        }
+        self.disk.metadata(path)
+    }
+
+    fn remove(&self, path: &str) -> io::Result<()> {
+        let info = self.metadata(path)?;
+        if info.is_dir && !self.read_dir(path)?.is_empty() {
+            return Err(io::Error::other(format!("Directory not empty: {}", path)))
+        }
+        let key = OverlayFileSystem::key(path);
+        if self.in_memory(&key) {
+            self.memory.remove(&key)?;
+        }
+        if self.disk.exists(path) {
+            self.removed.lock().unwrap().insert(PathBuf::from(key));
+        }
+        Ok(())
+    }
+
+    fn rename(&self, from: &str, to: &str) -> io::Result<()> {
+        let info = self.metadata(from)?;
+        if !self.parent_is_dir(to) {
+            return Err(OverlayFileSystem::not_found(to))
+        }
+        let target = OverlayFileSystem::key(to);
+        let source = OverlayFileSystem::key(from);
+        if target == source {
+            return Ok(())
+        }
+        if Path::new(&target).starts_with(&source) {
+            return Err(into_itself(Path::new(&source), Path::new(&target)))
+        }
+        if info.is_dir {
+            self.memory.create_dir_all(&target)?;
+            self.restore(&target);
+            for name in self.read_dir(from)? {
+                self.rename(&format!("{}/{}", from, name), &format!("{}/{}", to, name))?;
+            }
+        } else {
+            let mut content = vec![];
+            self.open(from, OpenMode { read: true, ..OpenMode::default() })?.read_to_end(&mut content)?;
+            self.memory.write(&target, content);
+            self.restore(&target);
+        }
+        self.remove(from)
+    }
+
+    fn create_dir_all(&self, path: &str) -> io::Result<()> {
+        let key = OverlayFileSystem::key(path);
+        self.memory.create_dir_all(&key)?;
+        let mut removed = self.removed.lock().unwrap();
+        for dir in Path::new(&key).ancestors() {
+            removed.remove(dir);
+        }
+        Ok(())
+    }
+
+    fn read_dir(&self, path: &str) -> io::Result<Vec<String>> {
+        let key = OverlayFileSystem::key(path);
+        let from_memory = self.memory.read_dir(&key).ok();
+        let from_disk = if self.is_removed(&key) { None } else { self.disk.read_dir(path).ok() };
+        if from_memory.is_none() && from_disk.is_none() {
+            return Err(OverlayFileSystem::not_found(path))
+        }
+
+        let mut names: Vec<String> = from_disk.unwrap_or_default().into_iter()
+            .filter(|name| !self.is_removed(&format!("{}/{}", key, name)))
+            .chain(from_memory.unwrap_or_default())
+            .collect();
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    //the written layer shares the directory tree of the disk, so links are the ones on disk
+    fn resolve(&self, path: &str) -> PathBuf {
+        self.disk.resolve(path)
+    }
+}
@@ -0,0 +1,66 @@
+use std::any::Any;
+use std::fmt::{Debug, Formatter};
+use std::rc::Rc;
+
+/*
+
+Handle: opaque host data that scripts can pass around but not inspect
+
+ */
+
+#[derive(Clone)]
+pub struct Handle {
+    tag: Rc<str>,
+    data: Rc<dyn Any>
+}
+
+impl Handle {
+
+    pub fn new<T: Any>(tag: &str, data: T) -> Handle {
+        Handle { tag: Rc::from(tag), data: Rc::new(data) }
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn is<T: Any>(&self) -> bool {
+        self.data.is::<T>()
+    }
+
+    pub fn downcast<T: Any>(&self) -> Option<&T> {
+        self.data.downcast_ref::<T>()
+    }
+
+    /// Like `downcast`, but fails with a message naming the expected tag.
+    pub fn expect<T: Any>(&self, tag: &str) -> Result<&T, String> {
+        if *self.tag != *tag {
+            return Err(format!("Expected handle of type {} but got {}", tag, self.tag))
+        }
+        match self.downcast::<T>() {
+            Some(data) => Ok(data),
+            None => Err(format!("Handle of type {} holds unexpected data", tag))
+        }
+    }
+
+    /// Identity of the shared data, equal for clones of the same handle.
+    pub fn address(&self) -> usize {
+        Rc::as_ptr(&self.data) as *const () as usize
+    }
+
+    pub fn get_signature(&self) -> String {
+        format!("Handle<{}>", self.tag)
+    }
+}
+
+impl PartialEq for Handle {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.data, &other.data)
+    }
+}
+
+impl Debug for Handle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.get_signature().as_str())
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::rc::Rc;
 use crate::runtime::{Object, RuntimeObject};
 
 pub struct ObjectStorage {
@@ -6,6 +7,12 @@ pub struct ObjectStorage {
     allocation_table: HashMap<usize, u32>
 }
 
+impl Default for ObjectStorage {
+    fn default() -> Self {
+        ObjectStorage::new()
+    }
+}
+
 impl ObjectStorage {
 
     pub fn new() -> ObjectStorage {
@@ -13,16 +20,27 @@ impl ObjectStorage {
     }
 
     pub fn allocate_object(&mut self) -> Object {
-        let obj = Object { id: self.get_space() };
+        let obj = Object { id: self.get_space(), type_name: None, variant: None };
+        self.inc_reference_count(&obj);
+        obj
+    }
+
+    /// Allocates an object that reports the struct `name` as its type.
+    pub fn allocate_typed_object(&mut self, name: &str) -> Object {
+        let obj = Object { id: self.get_space(), type_name: Some(Rc::from(name)), variant: None };
+        self.inc_reference_count(&obj);
+        obj
+    }
+
+    /// Allocates a value of the enum `name`, tagged with `variant`.
+    pub fn allocate_variant_object(&mut self, name: &str, variant: &str) -> Object {
+        let obj = Object { id: self.get_space(), type_name: Some(Rc::from(name)), variant: Some(Rc::from(variant)) };
         self.inc_reference_count(&obj);
         obj
     }
 
     pub fn get_field(&self, obj: &Object, name: String) -> Option<RuntimeObject> {
-        match self.object_storage[obj.id].get(name.as_str()) {
-            Some(a) => Some(a.clone()),
-            None => None
-        }
+        self.object_storage[obj.id].get(name.as_str()).cloned()
     }
 
     pub fn borow_fields(&self, obj: &Object) -> &HashMap<String, RuntimeObject> {
@@ -39,7 +57,7 @@ impl ObjectStorage {
     }
 
     fn check_free_spaces(&self) -> Option<usize> {
-        self.allocation_table.iter().filter(|(_, v)| v.clone().clone() == 0).map(|(k, _)| k.clone()).last()
+        self.allocation_table.iter().filter(|(_, v)| **v == 0).map(|(k, _)| *k).last()
     }
 
     pub fn inc_reference_count(&mut self, obj: &Object) {
@@ -55,6 +73,11 @@ impl ObjectStorage {
         }
     }
 
+    /// Objects that are still referenced, the rest are free to be reused.
+    pub fn live_objects(&self) -> usize {
+        self.allocation_table.values().filter(|it| **it > 0).count()
+    }
+
     fn get_space(&mut self) -> usize {
         match self.check_free_spaces() {
             Some(space) => space,
@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use crate::runtime::error::{ErrorKind, RuntimeError};
+use crate::runtime::filesystem::FileSystem;
+
+/*
+
+Permissions: capabilities a script gets for std library I/O
+
+ */
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Grant {
+    Denied,
+    All,
+    //as given, resolved together with the checked path by the filesystem the script uses
+    Paths(Vec<PathBuf>)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Permissions {
+    pub read: Grant,
+    pub write: Grant,
+    pub env: bool,
+    //network access, granted ahead of the natives that will check it
+    pub net: bool,
+    //starting child processes
+    pub run: bool
+}
+
+impl Permissions {
+
+    pub fn allow_all() -> Permissions {
+        Permissions { read: Grant::All, write: Grant::All, env: true, net: true, run: true }
+    }
+
+    pub fn none() -> Permissions {
+        Permissions { read: Grant::Denied, write: Grant::Denied, env: false, net: false, run: false }
+    }
+
+    pub fn allow_read(&mut self, path: Option<&str>) {
+        grant(&mut self.read, path)
+    }
+
+    pub fn allow_write(&mut self, path: Option<&str>) {
+        grant(&mut self.write, path)
+    }
+
+    pub fn check_read(&self, filesystem: &dyn FileSystem, path: &str) -> Result<(), RuntimeError> {
+        check_path(&self.read, filesystem, path, "read")
+    }
+
+    pub fn check_write(&self, filesystem: &dyn FileSystem, path: &str) -> Result<(), RuntimeError> {
+        check_path(&self.write, filesystem, path, "write")
+    }
+
+    pub fn check_env(&self) -> Result<(), RuntimeError> {
+        if self.env { Ok(()) } else { Err(denied("env access is not allowed (--allow-env)".to_string())) }
+    }
+
+    pub fn check_net(&self) -> Result<(), RuntimeError> {
+        if self.net { Ok(()) } else { Err(denied("net access is not allowed (--allow-net)".to_string())) }
+    }
+
+    pub fn check_run(&self, program: &str) -> Result<(), RuntimeError> {
+        if self.run { Ok(()) } else { Err(denied(format!("running {} is not allowed (--allow-run)", program))) }
+    }
+}
+
+impl Default for Permissions {
+    fn default() -> Self {
+        Permissions::allow_all()
+    }
+}
+
+fn denied(message: String) -> RuntimeError {
+    RuntimeError::new(ErrorKind::PermissionDenied, message)
+}
+
+fn grant(target: &mut Grant, path: Option<&str>) {
+    match path {
+        None => *target = Grant::All,
+        Some(p) => match target {
+            Grant::All => {},
+            Grant::Paths(paths) => paths.push(PathBuf::from(p)),
+            Grant::Denied => *target = Grant::Paths(vec![PathBuf::from(p)])
+        }
+    }
+}
+
+fn check_path(grant: &Grant, filesystem: &dyn FileSystem, path: &str, access: &str) -> Result<(), RuntimeError> {
+    match grant {
+        Grant::All => Ok(()),
+        Grant::Denied => Err(denied(format!("{} access to {} is not allowed (--allow-{})", access, path, access))),
+        Grant::Paths(paths) => {
+            let resolved = filesystem.resolve(path);
+            if paths.iter().any(|it| resolved.starts_with(filesystem.resolve(&it.to_string_lossy()))) {
+                Ok(())
+            } else {
+                Err(denied(format!("{} access to {} is not allowed (--allow-{})", access, path, access)))
+            }
+        }
+    }
+}
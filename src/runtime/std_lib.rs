@@ -1,39 +1,42 @@
-use std::str::FromStr;
-use crate::runtime::std_lib::io_functions::{io_print, io_read, io_open_file, file_read_to_string, file_write_string};
-use crate::runtime::std_lib::str_functions::{str_split, str_replace, str_to_lower, str_to_upper};
+use crate::runtime::std_lib::fs_functions::{
+    io_open_file, fs_open, fs_exists, fs_remove, fs_rename, fs_mkdir, fs_list_dir, fs_metadata,
+    file_read_line, file_read_bytes, file_read_to_string, file_write, file_flush, file_close, file_metadata
+};
+use crate::runtime::std_lib::io_functions::{io_print, io_read, io_inspect, io_inspect_with};
+use crate::runtime::std_lib::math_functions::{math_round_to, math_min, math_max, math_clamp, math_log, math_format, num_to_int, int_to_num};
+use crate::runtime::std_lib::str_functions::{str_split, str_replace, str_to_lower, str_to_upper, str_as_number, str_as_int};
+use crate::runtime::std_lib::map_functions::{map_get, map_set, map_remove, map_has, map_keys, map_values, map_len, value_hash};
+use crate::runtime::std_lib::result::result_enum;
+use crate::runtime::std_lib::coro_functions::coro_done;
+use crate::runtime::std_lib::process_functions::{
+    env_args, env_get, env_set, process_exit, process_run, process_spawn,
+    process_read_line, process_write, process_close_stdin, process_wait, process_kill
+};
+use crate::runtime::std_lib::worker_functions::{worker_spawn, worker_send, worker_receive, worker_join, channel_send, channel_receive};
+use crate::runtime::std_lib::event_functions::{
+    timer_set_timeout, timer_set_interval, timer_clear, promise_await, promise_then, promise_catch, fs_read_async, process_wait_async
+};
+use crate::runtime::value::ValueMap;
 use crate::runtime::{Function, RuntimeObject, Type};
-use crate::runtime::util::{library_function, dynamic_library_function};
-
-use super::Object;
+use crate::runtime::error::RuntimeError;
+use crate::runtime::util::{library_function, dynamic_library_function, typed_function, runtime_function};
 
 mod io_functions;
+mod fs_functions;
 mod str_functions;
+mod math_functions;
+mod map_functions;
 mod result;
+mod coro_functions;
+mod event_functions;
+mod worker_functions;
+mod process_functions;
 
-fn assert_arg_length(args: &Vec<RuntimeObject>, size: usize) -> Result<(), String> {
-    if args.len() == size {
-        Ok(())
-    } else {
-        Err(format!("Expected {} arg but got {}", size, args.len()))
-    }
-}
-
-fn get_as_string(args: &Vec<RuntimeObject>, index: usize) -> Result<String, String>{
-    match &args[0] {
-        RuntimeObject::Str(s) => Ok(s.to_string()),
-        _ => Err(format!("Expected String at arg {}", index))
-    }
-}
-
-fn get_as_object(args: &Vec<RuntimeObject>, index:usize) -> Result<Object, String> {
-    match &args[0] {
-        RuntimeObject::Object(o) => Ok(o.clone()),
-        _ => Err(format!("Expected String at arg {}", index))
-    }
-}
+/// The pretty printer of std/io/print and std/io/inspect, for hosts showing script values.
+pub use crate::runtime::std_lib::io_functions::{d_format, FormatOptions};
 
 pub fn get_std_library() -> Vec<Function> {
-    vec![
+    let mut functions = vec![
         //io functions
         dynamic_library_function(
             "std/io/print",
@@ -46,127 +49,142 @@ pub fn get_std_library() -> Vec<Function> {
             io_read,
             Type::Str
         ),
-        library_function(
-            "std/io/open_file",
-            vec![Type::Str],
-            io_open_file,
-            Type::Complex(vec![])
-        ),
-        library_function(
-            "file:read_to_string",
-            vec![Type::Complex(vec![])],
-            file_read_to_string,
-            Type::Str
-        ),
-        library_function(
-            "file:write_string",
-            vec![Type::Complex(vec![]), Type::Str],
-            file_write_string,
-            Type::Void
-        ),
-        
+        runtime_function("std/io/inspect", io_inspect),
+        runtime_function("std/io/inspect_with", io_inspect_with),
+
+        //file system functions
+        runtime_function("std/io/open_file", io_open_file),
+        runtime_function("std/fs/open", fs_open),
+        runtime_function("std/fs/exists", fs_exists),
+        runtime_function("std/fs/remove", fs_remove),
+        runtime_function("std/fs/rename", fs_rename),
+        runtime_function("std/fs/mkdir", fs_mkdir),
+        runtime_function("std/fs/list_dir", fs_list_dir),
+        runtime_function("std/fs/metadata", fs_metadata),
+        typed_function("file:read_line", file_read_line),
+        typed_function("file:read_bytes", file_read_bytes),
+        typed_function("file:read_to_string", file_read_to_string),
+        typed_function("file:write", file_write),
+        typed_function("file:write_string", file_write),
+        typed_function("file:flush", file_flush),
+        typed_function("file:close", file_close),
+        typed_function("file:metadata", file_metadata),
+
+        //errors
+        typed_function("std/error/raise", |kind: String, message: String| -> Result<(), RuntimeError> {
+            Err(RuntimeError::native(&kind, message))
+        }),
+
         //string functions
-        library_function(
-            "*:as_string",
-            vec![Type::Void],
-            |args, _| {
-                Ok(RuntimeObject::Str(format!("{}", args[0])))
-            },
-            Type::Str
-        ),
-        library_function(
-            "*:replace",
-            vec![Type::Str, Type::Str, Type::Str],
-            str_replace,
-            Type::Str
-        ),
-        library_function(
-            "str:to_lower",
-            vec![Type::Str],
-            str_to_lower,
-            Type::Str
-        ),
-        library_function(
-            "str:to_upper",
-            vec![Type::Str],
-            str_to_upper,
-            Type::Str
-        ),
-        library_function(
-            "str:as_number",
-            vec![Type::Str],
-            |args, _| {
-                match args[0].clone() {
-                    RuntimeObject::Str(s) => {
-                        match f64::from_str(s.as_str()) {
-                            Ok(x) => Ok(RuntimeObject::Num(x)),
-                            Err(_) => Err(format!("Cannot convert string '{}' into a number", s))
-                        }
-                    }
-                    _ => Err(format!("expected string on string function"))
-                }
-            },
-            Type::Num
-        ),
-        library_function(
-            "str:split",
-            vec![Type::Str, Type::Str],
-            str_split,
-            Type::List(Box::new(Type::Void))
-        ),
-        library_function(
-            "list:get",
-            vec![Type::List(Box::new(Type::Void)), Type::Num],
-            |args, _| {
-                let list = match &args[0] {
-                    RuntimeObject::List(v) => v,
-                    _ => return Err(format!("sub-function of list must be called on list"))
-                };
-
-                let index: usize = match &args[1] {
-                    RuntimeObject::Num(n) => { *n as usize },
-                    _ => return Err(format!("expected index as arg of list:get"))
-                };
-
-                Ok(list[index].clone())
-            },
-            Type::Void
-        ),
-        library_function(
-            "list:set",
-            vec![Type::List(Box::new(Type::Void)), Type::Num, Type::Void],
-            |args, _| {
-                let mut list = match &args[0] {
-                    RuntimeObject::List(v) => v,
-                    _ => return Err(format!("sub-function of list must be called on list"))
-                }.clone();
-
-                let index: usize = match &args[1] {
-                    RuntimeObject::Num(n) => { *n as usize },
-                    _ => return Err(format!("expected index as arg of list:get"))
-                };
-
-                list[index] = args[2].clone();
-
-                Ok(RuntimeObject::List(list))
-            },
-            Type::Void
-        ),
-        library_function(
-            "list:add",
-            vec![Type::List(Box::new(Type::Void)), Type::Void],
-            |args, _| {
-                let mut list = match &args[0] {
-                    RuntimeObject::List(v) => v,
-                    _ => return Err(format!("sub-function of list must be called on list"))
-                }.clone();
-
-                list.push(args[1].clone());
-
-                Ok(RuntimeObject::List(list))
-            },
-            Type::Void
-        )
+        typed_function("*:as_string", |value: RuntimeObject| format!("{}", value)),
+        typed_function("*:replace", str_replace),
+        typed_function("str:to_lower", str_to_lower),
+        typed_function("str:to_upper", str_to_upper),
+        typed_function("str:as_number", str_as_number),
+        typed_function("str:as_int", str_as_int),
+        typed_function("str:split", str_split),
+
+        //math functions
+        typed_function("std/math/pi", || std::f64::consts::PI),
+        typed_function("std/math/e", || std::f64::consts::E),
+        typed_function("std/math/floor", f64::floor),
+        typed_function("std/math/ceil", f64::ceil),
+        typed_function("std/math/round", f64::round),
+        typed_function("std/math/round_to", math_round_to),
+        typed_function("std/math/trunc", f64::trunc),
+        typed_function("std/math/abs", f64::abs),
+        typed_function("std/math/sign", f64::signum),
+        typed_function("std/math/min", math_min),
+        typed_function("std/math/max", math_max),
+        typed_function("std/math/clamp", math_clamp),
+        typed_function("std/math/sqrt", f64::sqrt),
+        typed_function("std/math/pow", f64::powf),
+        typed_function("std/math/exp", f64::exp),
+        typed_function("std/math/ln", f64::ln),
+        typed_function("std/math/log", math_log),
+        typed_function("std/math/log10", f64::log10),
+        typed_function("std/math/log2", f64::log2),
+        typed_function("std/math/sin", f64::sin),
+        typed_function("std/math/cos", f64::cos),
+        typed_function("std/math/tan", f64::tan),
+        typed_function("std/math/asin", f64::asin),
+        typed_function("std/math/acos", f64::acos),
+        typed_function("std/math/atan", f64::atan),
+        typed_function("std/math/atan2", f64::atan2),
+        typed_function("std/math/format", math_format),
+
+        //integer functions
+        typed_function("num:to_int", num_to_int),
+        typed_function("int:to_num", int_to_num),
+
+        //list functions
+        typed_function("list:get", |list: Vec<RuntimeObject>, index: usize| -> Result<RuntimeObject, String> {
+            match list.get(index) {
+                Some(value) => Ok(value.clone()),
+                None => Err(format!("Index {} out of bounds for list of length {}", index, list.len()))
+            }
+        }),
+        typed_function("list:set", |mut list: Vec<RuntimeObject>, index: usize, value: RuntimeObject| -> Result<Vec<RuntimeObject>, String> {
+            match list.get_mut(index) {
+                Some(slot) => *slot = value,
+                None => return Err(format!("Index {} out of bounds for list of length {}", index, list.len()))
+            }
+            Ok(list)
+        }),
+        typed_function("list:add", |mut list: Vec<RuntimeObject>, value: RuntimeObject| {
+            list.push(value);
+            list
+        }),
+
+        //map functions, keys are compared by content
+        typed_function("std/map/new", ValueMap::new),
+        runtime_function("map:get", map_get),
+        runtime_function("map:set", map_set),
+        runtime_function("map:remove", map_remove),
+        runtime_function("map:has", map_has),
+        typed_function("map:keys", map_keys),
+        typed_function("map:values", map_values),
+        typed_function("map:len", map_len),
 
-    ]
-}
\ No newline at end of file
+        //value functions
+        runtime_function("std/value/hash", value_hash),
+
+        //coroutine functions
+        typed_function("coro:done", coro_done),
+
+        //event loop functions, callbacks run after main returned or while a promise is awaited
+        runtime_function("std/timer/set_timeout", timer_set_timeout),
+        runtime_function("std/timer/set_interval", timer_set_interval),
+        runtime_function("std/timer/clear", timer_clear),
+        runtime_function("std/promise/await", promise_await),
+        runtime_function("promise:then", promise_then),
+        runtime_function("promise:catch", promise_catch),
+        runtime_function("std/fs/read_async", fs_read_async),
+        runtime_function("std/process/wait_async", process_wait_async),
+
+        //worker functions, values are deep copied between the runtimes
+        runtime_function("std/worker/spawn", worker_spawn),
+        runtime_function("worker:send", worker_send),
+        runtime_function("worker:receive", worker_receive),
+        runtime_function("worker:join", worker_join),
+        runtime_function("channel:send", channel_send),
+        runtime_function("channel:receive", channel_receive),
+
+        //environment and process functions
+        runtime_function("std/env/args", env_args),
+        runtime_function("std/env/get", env_get),
+        runtime_function("std/env/set", env_set),
+        typed_function("std/process/exit", process_exit),
+        runtime_function("std/process/run", process_run),
+        runtime_function("std/process/spawn", process_spawn),
+        typed_function("process:read_line", process_read_line),
+        typed_function("process:write", process_write),
+        typed_function("process:close_stdin", process_close_stdin),
+        typed_function("process:wait", process_wait),
+        typed_function("process:kill", process_kill)
+
+    ];
+    //result enum
+    functions.append(&mut result_enum());
+    functions
+}
@@ -0,0 +1,22 @@
+use crate::runtime::convert::FromRuntime;
+use crate::runtime::coroutine::Coroutine;
+use crate::runtime::handle::Handle;
+use crate::runtime::object_storage::ObjectStorage;
+use crate::runtime::{RuntimeObject, Type};
+
+/// Argument wrapper that only accepts handles tagged `coro`.
+pub struct CoroRef(Handle);
+
+impl FromRuntime for CoroRef {
+    fn runtime_type() -> Type { Type::Handle("coro".to_string()) }
+
+    fn from_runtime(value: &RuntimeObject, storage: &ObjectStorage) -> Result<Self, String> {
+        let handle = Handle::from_runtime(value, storage)?;
+        handle.expect::<Coroutine>("coro")?;
+        Ok(CoroRef(handle))
+    }
+}
+
+pub fn coro_done(coro: CoroRef) -> bool {
+    coro.0.downcast::<Coroutine>().is_some_and(|it| it.is_done())
+}
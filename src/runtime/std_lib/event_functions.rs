@@ -0,0 +1,119 @@
+use std::io::Read;
+use std::thread;
+use std::time::Duration;
+
+use crate::runtime::convert::FromRuntime;
+use crate::runtime::error::{ErrorKind, RuntimeError};
+use crate::runtime::event_loop::{Completion, Promise};
+use crate::runtime::filesystem::OpenMode;
+use crate::runtime::handle::Handle;
+use crate::runtime::object_storage::ObjectStorage;
+use crate::runtime::std_lib::process_functions::{process_error, ProcessRef};
+use crate::runtime::{Runtime, RuntimeObject, Type};
+
+/// Argument wrapper that only accepts handles tagged `promise`.
+pub struct PromiseRef(Handle);
+
+impl FromRuntime for PromiseRef {
+    fn runtime_type() -> Type { Type::Handle("promise".to_string()) }
+
+    fn from_runtime(value: &RuntimeObject, storage: &ObjectStorage) -> Result<Self, String> {
+        let handle = Handle::from_runtime(value, storage)?;
+        handle.expect::<Promise>("promise")?;
+        Ok(PromiseRef(handle))
+    }
+}
+
+const WAIT_POLL: Duration = Duration::from_millis(5);
+
+/*
+
+Timers, callbacks are function signatures called without arguments
+
+ */
+
+pub fn timer_set_timeout(runtime: &mut Runtime, callback: String, delay_ms: u64) -> Result<i64, RuntimeError> {
+    callback_exists(runtime, &callback)?;
+    Ok(runtime.set_timer(&callback, Duration::from_millis(delay_ms), false))
+}
+
+pub fn timer_set_interval(runtime: &mut Runtime, callback: String, interval_ms: u64) -> Result<i64, RuntimeError> {
+    callback_exists(runtime, &callback)?;
+    if interval_ms == 0 {
+        return Err(RuntimeError::new(ErrorKind::Runtime, "Interval has to be at least 1ms".to_string()))
+    }
+    Ok(runtime.set_timer(&callback, Duration::from_millis(interval_ms), true))
+}
+
+pub fn timer_clear(runtime: &mut Runtime, id: i64) -> bool {
+    runtime.clear_timer(id)
+}
+
+//fails when registering instead of when the loop gets to it
+fn callback_exists(runtime: &Runtime, callback: &str) -> Result<(), RuntimeError> {
+    match runtime.get_function(callback) {
+        Some(_) => Ok(()),
+        None => Err(RuntimeError::new(ErrorKind::NotFound, format!("No function found with name {}", callback)))
+    }
+}
+
+/*
+
+Promises
+
+ */
+
+pub fn promise_await(runtime: &mut Runtime, promise: PromiseRef) -> Result<RuntimeObject, RuntimeError> {
+    runtime.await_promise(&promise.0)
+}
+
+pub fn promise_then(runtime: &mut Runtime, promise: PromiseRef, callback: String) -> Result<(), RuntimeError> {
+    callback_exists(runtime, &callback)?;
+    runtime.on_settled(&promise.0, &callback, true)
+}
+
+pub fn promise_catch(runtime: &mut Runtime, promise: PromiseRef, callback: String) -> Result<(), RuntimeError> {
+    callback_exists(runtime, &callback)?;
+    runtime.on_settled(&promise.0, &callback, false)
+}
+
+/*
+
+Async operations, they run on their own thread and settle their promise on the script thread
+
+ */
+
+pub fn fs_read_async(runtime: &mut Runtime, path: String) -> Result<Handle, RuntimeError> {
+    runtime.permissions.check_read(runtime.filesystem.as_ref(), &path)?;
+    let filesystem = runtime.filesystem.clone();
+    Ok(runtime.spawn_async(move || {
+        let mut content = String::new();
+        let result = filesystem.open(&path, OpenMode { read: true, ..OpenMode::default() })
+            .and_then(|mut file| file.read_to_string(&mut content));
+        let completion: Completion = Box::new(move |_| match result {
+            Ok(_) => Ok(RuntimeObject::Str(content)),
+            Err(e) => Err(RuntimeError::new(ErrorKind::Io, format!("{} ({})", e, path)))
+        });
+        completion
+    }))
+}
+
+/// Closes stdin of a process from std/process/spawn and resolves with its exit code, processes killed by a signal report -1.
+pub fn process_wait_async(runtime: &mut Runtime, process: ProcessRef) -> Handle {
+    let (program, child) = process.close_for_wait();
+    runtime.spawn_async(move || {
+        //polls instead of blocking in wait so process:kill can still take the child
+        let status = loop {
+            match child.lock().unwrap().try_wait() {
+                Ok(None) => {}
+                done => break done
+            }
+            thread::sleep(WAIT_POLL);
+        };
+        let completion: Completion = Box::new(move |_| match status {
+            Ok(status) => Ok(RuntimeObject::Int(status.map_or(-1, |it| it.code().unwrap_or(-1)) as i64)),
+            Err(e) => Err(process_error(e, &program))
+        });
+        completion
+    })
+}
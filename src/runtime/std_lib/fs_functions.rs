@@ -0,0 +1,200 @@
+use std::cell::RefCell;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+
+use crate::runtime::convert::{FromRuntime, IntoRuntime};
+use crate::runtime::error::{ErrorKind, RuntimeError};
+use crate::runtime::handle::Handle;
+use crate::runtime::object_storage::ObjectStorage;
+use crate::runtime::filesystem::{FileInfo, FileStream, OpenMode};
+use crate::runtime::{Runtime, RuntimeObject, Type};
+
+/*
+
+File handles
+
+ */
+
+pub struct FileHandle {
+    path: String,
+    //None once the file was closed
+    reader: RefCell<Option<BufReader<Box<dyn FileStream>>>>
+}
+
+/// Argument wrapper that only accepts handles tagged `file`.
+pub struct FileRef(Handle);
+
+impl FromRuntime for FileRef {
+    fn runtime_type() -> Type { Type::Handle("file".to_string()) }
+
+    fn from_runtime(value: &RuntimeObject, storage: &ObjectStorage) -> Result<Self, String> {
+        let handle = Handle::from_runtime(value, storage)?;
+        handle.expect::<FileHandle>("file")?;
+        Ok(FileRef(handle))
+    }
+}
+
+impl FileRef {
+    fn with<T>(&self, consumer: impl FnOnce(&mut BufReader<Box<dyn FileStream>>) -> io::Result<T>) -> Result<T, RuntimeError> {
+        let file = self.0.expect::<FileHandle>("file")?;
+        let mut reader = file.reader.borrow_mut();
+        match reader.as_mut() {
+            Some(r) => consumer(r).map_err(|e| io_error(e, &file.path)),
+            None => Err(RuntimeError::new(ErrorKind::Io, format!("File {} is already closed", file.path)))
+        }
+    }
+}
+
+fn io_error(e: io::Error, path: &str) -> RuntimeError {
+    RuntimeError::new(ErrorKind::Io, format!("{} ({})", e, path))
+}
+
+/// Opens a file, `mode` combines r(ead), w(rite), a(ppend), c(reate) and t(runcate), e.g. "wct" to overwrite.
+pub fn open(runtime: &Runtime, path: &str, mode: &str) -> Result<Handle, RuntimeError> {
+    let mode = OpenMode::parse(mode)?;
+    if mode.read {
+        runtime.permissions.check_read(runtime.filesystem.as_ref(), path)?;
+    }
+    if mode.writes() {
+        runtime.permissions.check_write(runtime.filesystem.as_ref(), path)?;
+    }
+
+    match runtime.filesystem.open(path, mode) {
+        Ok(f) => Ok(Handle::new("file", FileHandle { path: path.to_string(), reader: RefCell::new(Some(BufReader::new(f))) })),
+        Err(e) => Err(io_error(e, path))
+    }
+}
+
+pub fn fs_open(runtime: &mut Runtime, path: String, mode: String) -> Result<Handle, RuntimeError> {
+    open(runtime, &path, &mode)
+}
+
+pub fn io_open_file(runtime: &mut Runtime, path: String) -> Result<Handle, RuntimeError> {
+    open(runtime, &path, "r")
+}
+
+pub fn file_read_line(file: FileRef) -> Result<Option<String>, RuntimeError> {
+    file.with(|r| {
+        let mut line = String::new();
+        if r.read_line(&mut line)? == 0 {
+            return Ok(None)
+        }
+        if line.ends_with('\n') {
+            line.pop();
+            if line.ends_with('\r') {
+                line.pop();
+            }
+        }
+        Ok(Some(line))
+    })
+}
+
+pub fn file_read_bytes(file: FileRef, count: usize) -> Result<Vec<u32>, RuntimeError> {
+    file.with(|r| {
+        let mut bytes = vec![];
+        r.by_ref().take(count as u64).read_to_end(&mut bytes)?;
+        Ok(bytes.into_iter().map(|it| it as u32).collect())
+    })
+}
+
+pub fn file_read_to_string(file: FileRef) -> Result<String, RuntimeError> {
+    file.with(|r| {
+        let mut buf = String::new();
+        r.rewind()?;
+        r.read_to_string(&mut buf)?;
+        Ok(buf)
+    })
+}
+
+pub fn file_write(file: FileRef, content: String) -> Result<(), RuntimeError> {
+    file.with(|r| {
+        //drop buffered read data so the write lands at the logical position
+        let position = r.stream_position()?;
+        r.seek(SeekFrom::Start(position))?;
+        r.get_mut().write_all(content.as_bytes())
+    })
+}
+
+pub fn file_flush(file: FileRef) -> Result<(), RuntimeError> {
+    file.with(|r| r.get_mut().flush())
+}
+
+pub fn file_close(file: FileRef) -> Result<(), RuntimeError> {
+    let handle = file.0.expect::<FileHandle>("file")?;
+    let closed = handle.reader.borrow_mut().take();
+    match closed {
+        Some(mut r) => r.get_mut().flush().map_err(|e| io_error(e, &handle.path)),
+        None => Err(RuntimeError::new(ErrorKind::Io, format!("File {} is already closed", handle.path)))
+    }
+}
+
+/*
+
+Paths and directories
+
+ */
+
+pub struct FileMetadata {
+    path: String,
+    data: FileInfo
+}
+
+impl IntoRuntime for FileMetadata {
+    fn runtime_type() -> Type { Type::Complex(vec![]) }
+
+    fn into_runtime(self, storage: &mut ObjectStorage) -> RuntimeObject {
+        let modified = self.data.modified.map(RuntimeObject::Num).unwrap_or(RuntimeObject::Void);
+
+        let object = storage.allocate_object();
+        for (key, value) in [
+                ("path".to_string(), RuntimeObject::Str(self.path)),
+                ("isFile".to_string(), RuntimeObject::Bool(self.data.is_file)),
+                ("isDir".to_string(), RuntimeObject::Bool(self.data.is_dir)),
+                ("length".to_string(), RuntimeObject::Int(self.data.length as i64)),
+                ("readonly".to_string(), RuntimeObject::Bool(self.data.readonly)),
+                ("modified".to_string(), modified),
+            ] {
+            storage.set_field(&object, key, value);
+        };
+        RuntimeObject::Object(object)
+    }
+}
+
+pub fn file_metadata(file: FileRef) -> Result<FileMetadata, RuntimeError> {
+    let path = file.0.expect::<FileHandle>("file")?.path.clone();
+    let data = file.with(|r| r.get_ref().info())?;
+    Ok(FileMetadata { path, data })
+}
+
+pub fn fs_metadata(runtime: &mut Runtime, path: String) -> Result<FileMetadata, RuntimeError> {
+    runtime.permissions.check_read(runtime.filesystem.as_ref(), &path)?;
+    match runtime.filesystem.metadata(&path) {
+        Ok(data) => Ok(FileMetadata { path, data }),
+        Err(e) => Err(io_error(e, &path))
+    }
+}
+
+pub fn fs_exists(runtime: &mut Runtime, path: String) -> Result<bool, RuntimeError> {
+    runtime.permissions.check_read(runtime.filesystem.as_ref(), &path)?;
+    Ok(runtime.filesystem.exists(&path))
+}
+
+pub fn fs_remove(runtime: &mut Runtime, path: String) -> Result<(), RuntimeError> {
+    runtime.permissions.check_write(runtime.filesystem.as_ref(), &path)?;
+    runtime.filesystem.remove(&path).map_err(|e| io_error(e, &path))
+}
+
+pub fn fs_rename(runtime: &mut Runtime, from: String, to: String) -> Result<(), RuntimeError> {
+    runtime.permissions.check_write(runtime.filesystem.as_ref(), &from)?;
+    runtime.permissions.check_write(runtime.filesystem.as_ref(), &to)?;
+    runtime.filesystem.rename(&from, &to).map_err(|e| io_error(e, &from))
+}
+
+pub fn fs_mkdir(runtime: &mut Runtime, path: String) -> Result<(), RuntimeError> {
+    runtime.permissions.check_write(runtime.filesystem.as_ref(), &path)?;
+    runtime.filesystem.create_dir_all(&path).map_err(|e| io_error(e, &path))
+}
+
+pub fn fs_list_dir(runtime: &mut Runtime, path: String) -> Result<Vec<String>, RuntimeError> {
+    runtime.permissions.check_read(runtime.filesystem.as_ref(), &path)?;
+    runtime.filesystem.read_dir(&path).map_err(|e| io_error(e, &path))
+}
@@ -1,115 +1,127 @@
-use std::{io::{stdin, Read, Write}, fs::File};
+use std::io::stdin;
 
-use crate::runtime::{RuntimeObject, object_storage::ObjectStorage};
+use crate::runtime::{Runtime, RuntimeObject, object_storage::ObjectStorage};
 
-use super::{get_as_string, get_as_object};
 
-
-pub fn io_print(args: &Vec<RuntimeObject>, storage: &mut ObjectStorage) -> Result<RuntimeObject, String> {
+pub fn io_print(args: &[RuntimeObject], storage: &mut ObjectStorage) -> Result<RuntimeObject, String> {
     for arg in args {
-        println!("{}", d_format(arg, storage))
+        println!("{}", d_format(arg, storage, &FormatOptions::default()))
     }
     Ok(RuntimeObject::Void)
 }
 
-pub fn io_read(_: &Vec<RuntimeObject>, _: &mut ObjectStorage) -> Result<RuntimeObject, String> {
-    let mut buffer = String::new();
-    stdin().read_line(&mut buffer).expect("");
-    Ok(RuntimeObject::Str(buffer))
+pub fn io_inspect(runtime: &mut Runtime, value: RuntimeObject) -> String {
+    d_format(&value, &runtime.storage, &FormatOptions::default())
 }
 
-pub fn io_open_file(args: &Vec<RuntimeObject>, storage: &mut ObjectStorage) -> Result<RuntimeObject, String> {
-    let path = get_as_string(args, 0)?;
-    let file = match File::open(&path) {
-        Err(_) => return Err(format!("file '{}' does not exist", &path)),
-        Ok(f) =>f
-    };
+/// Like `io_inspect` with a depth limit and optionally everything on one line.
+pub fn io_inspect_with(runtime: &mut Runtime, value: RuntimeObject, max_depth: usize, one_line: bool) -> String {
+    d_format(&value, &runtime.storage, &FormatOptions { max_depth, one_line })
+}
 
-    let data = match file.metadata() {
-        Ok(d) => d,
-        Err(_) => return Err(format!("Cannot access metadata of file {}", &path))
-    };
+pub fn io_read(_: &[RuntimeObject], _: &mut ObjectStorage) -> Result<RuntimeObject, String> {
+    let mut buffer = String::new();
+    match stdin().read_line(&mut buffer) {
+        Ok(_) => Ok(RuntimeObject::Str(buffer)),
+        Err(e) => Err(format!("Cannot read from stdin: {}", e))
+    }
+}
 
-    let file_object = storage.allocate_object();
+/*
 
-    for (key, value) in [
-            ("path".to_string(), RuntimeObject::Str(path)),
-            ("isFile".to_string(), RuntimeObject::Bool(data.is_file())),
-            ("isDir".to_string(), RuntimeObject::Bool(data.is_dir())),
-            ("length".to_string(), RuntimeObject::Num(data.len() as f64)),
-        ] {
-        storage.set_field(&file_object, key, value);
-    };
+Pretty printer
 
+ */
 
-    Ok(RuntimeObject::Object(file_object))
+pub struct FormatOptions {
+    //containers deeper than this are shortened to `[...]`
+    pub max_depth: usize,
+    pub one_line: bool
 }
 
-pub fn file_read_to_string(args: &Vec<RuntimeObject>, storage: &mut ObjectStorage) -> Result<RuntimeObject, String> {
-    let file_obj = get_as_object(args, 0)?;
-    let path = match storage.get_field(&file_obj, "path".to_string()) {
-        Some(f) => match f {
-            RuntimeObject::Str(s) => s,
-            _ => return Err(format!("Object Error Path must be string"))
-        }
-        None => return Err(format!("File Object must contain "))
-    };
-
-    let mut buf = String::new();
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions { max_depth: 8, one_line: false }
+    }
+}
 
-    match File::open(path) {
-        Ok( mut f) => {
-            f.read_to_string(&mut buf).expect("Cannot read file");
-        }
-        Err(_) => return Err(format!("File cannot be read"))
-    };
+//children of a container that only holds scalars are kept on one line up to this width
+const INLINE_WIDTH: usize = 72;
 
-    Ok(RuntimeObject::Str(buf))
+/// Top level strings are printed as they are, nested ones are quoted.
+pub fn d_format(obj: &RuntimeObject, storage: &ObjectStorage, options: &FormatOptions) -> String {
+    match obj {
+        RuntimeObject::Str(s) => s.to_string(),
+        _ => format_value(obj, storage, options, 0, &mut vec![])
+    }
 }
 
-pub fn file_write_string(args: &Vec<RuntimeObject>, storage: &mut ObjectStorage) -> Result<RuntimeObject, String> {
-    let file_obj = get_as_object(args, 0)?;
-    let path = match storage.get_field(&file_obj, "path".to_string()) {
-        Some(f) => match f {
-            RuntimeObject::Str(s) => s,
-            _ => return Err(format!("Object Error Path must be string"))
+fn format_value(obj: &RuntimeObject, storage: &ObjectStorage, options: &FormatOptions, depth: usize, path: &mut Vec<usize>) -> String {
+    match obj {
+        RuntimeObject::Num(n) => n.to_string(),
+        RuntimeObject::Int(n) => n.to_string(),
+        RuntimeObject::Str(s) => format!("{:?}", s),
+        RuntimeObject::Bool(b) => b.to_string(),
+        RuntimeObject::Handle(h) => h.get_signature(),
+        RuntimeObject::Void => "Void".to_string(),
+        RuntimeObject::List(list) => {
+            if depth >= options.max_depth && !list.is_empty() {
+                return "[...]".to_string()
+            }
+            let items: Vec<String> = list.iter().map(|it| format_value(it, storage, options, depth + 1, path)).collect();
+            wrap("[", &items, "]", list.iter().all(is_scalar), options, depth)
         }
-        None => return Err(format!("File Object must contain "))
-    };
-    let content = get_as_string(args, 1)?;
-
-    match File::open(path) {
-        Ok( mut f) => {
-            f.write_fmt(format_args!("{}", content)).expect("cannot write io error");
-            Ok(RuntimeObject::Void)
+        RuntimeObject::Map(map) => {
+            if depth >= options.max_depth && !map.is_empty() {
+                return "{...}".to_string()
+            }
+            let entries: Vec<String> = map.iter().map(|(key, value)| format!(
+                "{}: {}",
+                format_value(key, storage, options, depth + 1, path),
+                format_value(value, storage, options, depth + 1, path)
+            )).collect();
+            wrap("{", &entries, "}", map.iter().all(|(key, value)| is_scalar(key) && is_scalar(value)), options, depth)
+        }
+        RuntimeObject::Object(o) => {
+            let name = format!("{} #{}", o.display_name(), o.id());
+            if path.contains(&o.id()) {
+                return format!("<cycle #{}>", o.id())
+            }
+            let fields = storage.borow_fields(o);
+            if depth >= options.max_depth && !fields.is_empty() {
+                return format!("{} {{...}}", name)
+            }
+
+            path.push(o.id());
+            let mut keys: Vec<&String> = fields.keys().collect();
+            keys.sort();
+            let entries: Vec<String> = keys.iter().map(|key| format!(
+                "{}: {}",
+                key,
+                format_value(&fields[*key], storage, options, depth + 1, path)
+            )).collect();
+            path.pop();
+
+            wrap(&format!("{} {{", name), &entries, "}", fields.values().all(is_scalar), options, depth)
         }
-        Err(_) => Err(format!("File cannot be opend"))
     }
 }
 
+fn is_scalar(obj: &RuntimeObject) -> bool {
+    !matches!(obj, RuntimeObject::List(_) | RuntimeObject::Map(_) | RuntimeObject::Object(_))
+}
 
-fn d_format(obj: &RuntimeObject, storage: &mut ObjectStorage) -> String {
-    match obj {
-        /*
-        RuntimeObject::Object(o) => {
-            let mut str = "{\n".to_string();
-            for (_, (k,v)) in storage.borow_fields(o).iter().enumerate() {
-                     str+=format!("{}: {}\n", k, d_format(v, storage)).as_str();
-            }
-            str+"}"
-        },
-         */
-        RuntimeObject::Object(_) => format!("Object"),
-        RuntimeObject::Num(n) => n.to_string(),
-        RuntimeObject::List(list) => {
-            let mut start = "[".to_string();
-            list.iter().for_each(|it| {
-                start+= &d_format(it, storage);
-            });
-            start+"]"
-        }
-        RuntimeObject::Str(s) => s.to_string(),
-        RuntimeObject::Bool(b) => b.to_string(),
-        RuntimeObject::Void => format!("Any"),
+fn wrap(open: &str, items: &[String], close: &str, scalars: bool, options: &FormatOptions, depth: usize) -> String {
+    if items.is_empty() {
+        return format!("{}{}", open, close)
     }
-}
\ No newline at end of file
+    //objects get a space inside the braces, lists do not
+    let pad = if open.ends_with('[') { "" } else { " " };
+    let inline = format!("{}{}{}{}{}", open, pad, items.join(", "), pad, close);
+    if options.one_line || (scalars && inline.len() <= INLINE_WIDTH) {
+        return inline
+    }
+    let indent = "  ".repeat(depth + 1);
+    let body: Vec<String> = items.iter().map(|it| format!("{}{}", indent, it)).collect();
+    format!("{}\n{}\n{}{}", open, body.join(",\n"), "  ".repeat(depth), close)
+}
@@ -0,0 +1,39 @@
+use crate::runtime::value::{deep_hash, ValueMap};
+use crate::runtime::{Runtime, RuntimeObject};
+
+//maps are values like lists, so changing natives return the new map
+
+pub fn map_get(runtime: &mut Runtime, map: ValueMap, key: RuntimeObject) -> Option<RuntimeObject> {
+    map.get(&runtime.storage, &key).cloned()
+}
+
+pub fn map_set(runtime: &mut Runtime, mut map: ValueMap, key: RuntimeObject, value: RuntimeObject) -> ValueMap {
+    map.insert(&runtime.storage, key, value);
+    map
+}
+
+pub fn map_remove(runtime: &mut Runtime, mut map: ValueMap, key: RuntimeObject) -> ValueMap {
+    map.remove(&runtime.storage, &key);
+    map
+}
+
+pub fn map_has(runtime: &mut Runtime, map: ValueMap, key: RuntimeObject) -> bool {
+    map.contains_key(&runtime.storage, &key)
+}
+
+pub fn map_keys(map: ValueMap) -> Vec<RuntimeObject> {
+    map.iter().map(|(key, _)| key.clone()).collect()
+}
+
+pub fn map_values(map: ValueMap) -> Vec<RuntimeObject> {
+    map.iter().map(|(_, value)| value.clone()).collect()
+}
+
+pub fn map_len(map: ValueMap) -> i64 {
+    map.len() as i64
+}
+
+/// Deep hash as an integer, equal values by `deepEq` get equal hashes.
+pub fn value_hash(runtime: &mut Runtime, value: RuntimeObject) -> i64 {
+    deep_hash(&runtime.storage, &value) as i64
+}
@@ -0,0 +1,44 @@
+/// Rounds half away from zero to `digits` decimal places.
+pub fn math_round_to(value: f64, digits: i32) -> f64 {
+    let factor = 10f64.powi(digits);
+    (value * factor).round() / factor
+}
+
+pub fn math_min(a: f64, b: f64) -> f64 {
+    a.min(b)
+}
+
+pub fn math_max(a: f64, b: f64) -> f64 {
+    a.max(b)
+}
+
+pub fn math_clamp(value: f64, min: f64, max: f64) -> Result<f64, String> {
+    if min > max {
+        return Err(format!("Cannot clamp to {}..{}, min is greater than max", min, max))
+    }
+    Ok(value.clamp(min, max))
+}
+
+pub fn math_log(value: f64, base: f64) -> f64 {
+    value.log(base)
+}
+
+/// Formats with exactly `digits` decimal places, e.g. 3.14159 with 2 digits is "3.14".
+pub fn math_format(value: f64, digits: usize) -> String {
+    format!("{:.*}", digits, value)
+}
+
+/// Drops the fraction, fails for values an integer cannot hold instead of saturating.
+pub fn num_to_int(value: f64) -> Result<i64, String> {
+    let truncated = value.trunc();
+    //i64::MAX is not representable as f64, the bound is the next power of two
+    if truncated.is_nan() || truncated < i64::MIN as f64 || truncated >= i64::MAX as f64 {
+        return Err(format!("Number {} does not fit into an integer", value))
+    }
+    Ok(truncated as i64)
+}
+
+/// Large integers may lose precision as a Number.
+pub fn int_to_num(value: i64) -> f64 {
+    value as f64
+}
@@ -0,0 +1,171 @@
+use std::cell::RefCell;
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+use crate::runtime::convert::FromRuntime;
+use crate::runtime::error::{ErrorKind, RuntimeError};
+use crate::runtime::handle::Handle;
+use crate::runtime::object_storage::ObjectStorage;
+use crate::runtime::{Runtime, RuntimeObject, Type};
+use crate::runtime_struct;
+
+/*
+
+Environment
+
+ */
+
+/// Arguments after `--` on the command line.
+pub fn env_args(runtime: &mut Runtime) -> Vec<String> {
+    runtime.script_args.clone()
+}
+
+pub fn env_get(runtime: &mut Runtime, name: String) -> Result<Option<String>, RuntimeError> {
+    runtime.permissions.check_env()?;
+    match runtime.env.get(&name) {
+        Some(value) => Ok(Some(value.clone())),
+        None => Ok(std::env::var(name).ok())
+    }
+}
+
+pub fn env_set(runtime: &mut Runtime, name: String, value: String) -> Result<(), RuntimeError> {
+    runtime.permissions.check_env()?;
+    if name.is_empty() || name.contains('=') || name.contains('\0') || value.contains('\0') {
+        return Err(RuntimeError::new(ErrorKind::Runtime, format!("Invalid environment variable {}", name)))
+    }
+    //other runtimes may read the process environment from worker threads
+    runtime.env.insert(name, value);
+    Ok(())
+}
+
+/*
+
+Processes
+
+ */
+
+/// Unwinds the script with `ErrorKind::Exit`, timers and pending promises are dropped and the host picks up the code.
+/// Needs no permission, the host decides what the code means.
+pub fn process_exit(code: i32) -> Result<(), RuntimeError> {
+    Err(RuntimeError::new(ErrorKind::Exit(code), format!("Script exited with code {}", code)))
+}
+
+pub struct ProcessOutput {
+    stdout: String,
+    stderr: String,
+    //-1 if the process was killed by a signal
+    code: i64
+}
+
+runtime_struct!(ProcessOutput { stdout, stderr, code });
+
+pub(crate) fn process_error(e: io::Error, program: &str) -> RuntimeError {
+    RuntimeError::new(ErrorKind::Io, format!("Cannot run {}: {}", program, e))
+}
+
+/// Runs a program to completion and collects its output, stdin is empty.
+pub fn process_run(runtime: &mut Runtime, program: String, args: Vec<String>) -> Result<ProcessOutput, RuntimeError> {
+    runtime.permissions.check_run(&program)?;
+    let output = Command::new(&program).args(&args).envs(&runtime.env).stdin(Stdio::null()).output().map_err(|e| process_error(e, &program))?;
+    Ok(ProcessOutput {
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        code: output.status.code().unwrap_or(-1) as i64
+    })
+}
+
+pub struct ProcessHandle {
+    program: String,
+    //shared with the threads of std/process/wait_async
+    child: Arc<Mutex<Child>>,
+    stdout: RefCell<BufReader<ChildStdout>>,
+    //None once closed
+    stdin: RefCell<Option<ChildStdin>>
+}
+
+/// Argument wrapper that only accepts handles tagged `process`.
+pub struct ProcessRef(Handle);
+
+impl FromRuntime for ProcessRef {
+    fn runtime_type() -> Type { Type::Handle("process".to_string()) }
+
+    fn from_runtime(value: &RuntimeObject, storage: &ObjectStorage) -> Result<Self, String> {
+        let handle = Handle::from_runtime(value, storage)?;
+        handle.expect::<ProcessHandle>("process")?;
+        Ok(ProcessRef(handle))
+    }
+}
+
+impl ProcessRef {
+    fn process(&self) -> &ProcessHandle {
+        self.0.downcast::<ProcessHandle>().unwrap()
+    }
+
+    /// Closes stdin like `process:wait` and returns the program name and the child for waiting on another thread.
+    pub(crate) fn close_for_wait(&self) -> (String, Arc<Mutex<Child>>) {
+        let process = self.process();
+        process.stdin.borrow_mut().take();
+        (process.program.clone(), process.child.clone())
+    }
+}
+
+/// Starts a program with piped stdin and stdout, stderr goes to the terminal.
+pub fn process_spawn(runtime: &mut Runtime, program: String, args: Vec<String>) -> Result<Handle, RuntimeError> {
+    runtime.permissions.check_run(&program)?;
+    let mut child = Command::new(&program).args(&args).envs(&runtime.env)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| process_error(e, &program))?;
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    let stdin = child.stdin.take();
+    Ok(Handle::new("process", ProcessHandle { program, child: Arc::new(Mutex::new(child)), stdout: RefCell::new(stdout), stdin: RefCell::new(stdin) }))
+}
+
+/// Next line of stdout without the line break, none at the end of the output.
+pub fn process_read_line(process: ProcessRef) -> Result<Option<String>, RuntimeError> {
+    let process = process.process();
+    let mut line = String::new();
+    match process.stdout.borrow_mut().read_line(&mut line) {
+        Ok(0) => Ok(None),
+        Ok(_) => {
+            if line.ends_with('\n') {
+                line.pop();
+                if line.ends_with('\r') {
+                    line.pop();
+                }
+            }
+            Ok(Some(line))
+        }
+        Err(e) => Err(process_error(e, &process.program))
+    }
+}
+
+pub fn process_write(process: ProcessRef, text: String) -> Result<(), RuntimeError> {
+    let process = process.process();
+    match process.stdin.borrow_mut().as_mut() {
+        Some(stdin) => stdin.write_all(text.as_bytes()).and_then(|_| stdin.flush()).map_err(|e| process_error(e, &process.program)),
+        None => Err(RuntimeError::new(ErrorKind::Io, format!("Stdin of {} is already closed", process.program)))
+    }
+}
+
+/// Signals the end of the input, many programs only finish after it.
+pub fn process_close_stdin(process: ProcessRef) {
+    process.process().stdin.borrow_mut().take();
+}
+
+/// Closes stdin and waits for the exit code.
+pub fn process_wait(process: ProcessRef) -> Result<i64, RuntimeError> {
+    let process = process.process();
+    process.stdin.borrow_mut().take();
+    match process.child.lock().unwrap().wait() {
+        Ok(status) => Ok(status.code().unwrap_or(-1) as i64),
+        Err(e) => Err(process_error(e, &process.program))
+    }
+}
+
+pub fn process_kill(process: ProcessRef) -> Result<(), RuntimeError> {
+    let process = process.process();
+    process.child.lock().unwrap().kill().map_err(|e| process_error(e, &process.program))
+}
@@ -1,3 +1,9 @@
-fn create_result_obj() {
-    
-}
\ No newline at end of file
+use crate::runtime::{declare_enum, Function, Type, Variant};
+
+/// `std/result` with the variants `Ok value` and `Err error`, built with `std/result/Ok` and `std/result/Err`.
+pub fn result_enum() -> Vec<Function> {
+    declare_enum("std/result", vec![
+        Variant { name: "Ok".to_string(), fields: vec![("value".to_string(), Type::Any)] },
+        Variant { name: "Err".to_string(), fields: vec![("error".to_string(), Type::Any)] }
+    ])
+}
@@ -1,42 +1,26 @@
-use crate::runtime::{RuntimeObject, object_storage::ObjectStorage};
-
-use super::{assert_arg_length, get_as_string};
-
-
-
-
-pub fn str_split(args: &Vec<RuntimeObject>, _: &mut ObjectStorage) -> Result<RuntimeObject, String> {
-    assert_arg_length(args, 2)?;
-    let base_string = get_as_string(args, 0)?;
-    let split_string = get_as_string(args, 1)?;
-
-    Ok(RuntimeObject::List(
-        base_string
+pub fn str_split(base_string: String, split_string: String) -> Vec<String> {
+    base_string
         .split(split_string.as_str())
-        .map(|it| RuntimeObject::Str(it.to_string()))
-        .collect::<Vec<RuntimeObject>>()
-    ))
+        .map(|it| it.to_string())
+        .collect()
 }
 
-pub fn str_replace(args: &Vec<RuntimeObject>, _: &mut ObjectStorage) -> Result<RuntimeObject, String> {
-    assert_arg_length(args, 3)?;
-    let base_string = get_as_string(args, 0)?;
-    let target = get_as_string(args, 1)?;
-    let replacement = get_as_string(args, 2)?;
-
-    Ok(RuntimeObject::Str(base_string.replace(&target, &replacement)))
+pub fn str_replace(base_string: String, target: String, replacement: String) -> String {
+    base_string.replace(&target, &replacement)
 }
 
-pub fn str_to_upper(args: &Vec<RuntimeObject>, _: &mut ObjectStorage) -> Result<RuntimeObject, String> {
-    assert_arg_length(args, 1)?;
-    let base_string = get_as_string(args, 0)?;
+pub fn str_to_upper(base_string: String) -> String {
+    base_string.to_uppercase()
+}
 
-    Ok(RuntimeObject::Str(base_string.to_uppercase()))
+pub fn str_to_lower(base_string: String) -> String {
+    base_string.to_lowercase()
 }
 
-pub fn str_to_lower(args: &Vec<RuntimeObject>, _: &mut ObjectStorage) -> Result<RuntimeObject, String> {
-    assert_arg_length(args, 1)?;
-    let base_string = get_as_string(args, 0)?;
+pub fn str_as_number(base_string: String) -> Result<f64, String> {
+    base_string.parse::<f64>().map_err(|_| format!("Cannot convert string '{}' into a number", base_string))
+}
 
-    Ok(RuntimeObject::Str(base_string.to_lowercase()))
-}
\ No newline at end of file
+pub fn str_as_int(base_string: String) -> Result<i64, String> {
+    base_string.trim().parse::<i64>().map_err(|_| format!("Cannot convert string '{}' into an integer", base_string))
+}
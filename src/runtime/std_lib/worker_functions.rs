@@ -0,0 +1,68 @@
+use crate::runtime::convert::FromRuntime;
+use crate::runtime::error::RuntimeError;
+use crate::runtime::handle::Handle;
+use crate::runtime::object_storage::ObjectStorage;
+use crate::runtime::worker::{Port, Worker};
+use crate::runtime::{Runtime, RuntimeObject, Type};
+
+/// Argument wrapper that only accepts handles tagged `worker`.
+pub struct WorkerRef(Handle);
+
+impl FromRuntime for WorkerRef {
+    fn runtime_type() -> Type { Type::Handle("worker".to_string()) }
+
+    fn from_runtime(value: &RuntimeObject, storage: &ObjectStorage) -> Result<Self, String> {
+        let handle = Handle::from_runtime(value, storage)?;
+        handle.expect::<Worker>("worker")?;
+        Ok(WorkerRef(handle))
+    }
+}
+
+impl WorkerRef {
+    fn worker(&self) -> &Worker {
+        self.0.downcast::<Worker>().unwrap()
+    }
+}
+
+/// Argument wrapper that only accepts handles tagged `channel`, the worker side of a worker.
+pub struct ChannelRef(Handle);
+
+impl FromRuntime for ChannelRef {
+    fn runtime_type() -> Type { Type::Handle("channel".to_string()) }
+
+    fn from_runtime(value: &RuntimeObject, storage: &ObjectStorage) -> Result<Self, String> {
+        let handle = Handle::from_runtime(value, storage)?;
+        handle.expect::<Port>("channel")?;
+        Ok(ChannelRef(handle))
+    }
+}
+
+impl ChannelRef {
+    fn port(&self) -> &Port {
+        self.0.downcast::<Port>().unwrap()
+    }
+}
+
+pub fn worker_spawn(runtime: &mut Runtime, signature: String) -> Result<Handle, RuntimeError> {
+    runtime.spawn_worker(&signature)
+}
+
+pub fn worker_send(runtime: &mut Runtime, worker: WorkerRef, value: RuntimeObject) -> Result<(), RuntimeError> {
+    worker.worker().port.send(runtime, &value)
+}
+
+pub fn worker_receive(runtime: &mut Runtime, worker: WorkerRef) -> Result<RuntimeObject, RuntimeError> {
+    worker.worker().port.receive(runtime)
+}
+
+pub fn worker_join(runtime: &mut Runtime, worker: WorkerRef) -> Result<RuntimeObject, RuntimeError> {
+    worker.worker().join(runtime)
+}
+
+pub fn channel_send(runtime: &mut Runtime, channel: ChannelRef, value: RuntimeObject) -> Result<(), RuntimeError> {
+    channel.port().send(runtime, &value)
+}
+
+pub fn channel_receive(runtime: &mut Runtime, channel: ChannelRef) -> Result<RuntimeObject, RuntimeError> {
+    channel.port().receive(runtime)
+}
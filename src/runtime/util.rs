@@ -1,44 +1,68 @@
-use crate::{Function, Operation, Type};
-use crate::Operation::{Native, Return};
-use crate::runtime::object_storage::ObjectStorage;
-use crate::runtime::RuntimeObject;
+use std::sync::Arc;
 
-fn load_var(name: &str) -> Operation {
+use crate::runtime::{Function, LibraryCallback, Operation, Runtime, RuntimeObject, Type};
+use crate::runtime::convert::{NativeFn, RuntimeNativeFn};
+use crate::runtime::error::RuntimeError;
+use crate::runtime::Operation::{Native, Return};
+
+pub fn load_var(name: &str) -> Operation {
     Operation::LoadVar(name.to_string())
 }
 
-fn set_var(name: &str) -> Operation {
+pub fn set_var(name: &str) -> Operation {
     Operation::SetVar(name.to_string())
 }
 
 pub fn function(identifier: &str, args: Vec<Type>, instructions: Vec<Operation>, return_type: Type) -> Function {
-    Function {signature: identifier.to_string(), args: Some(args), instructions, return_type }
+    Function {signature: identifier.to_string(), args: Some(args), instructions, return_type, source: None }
 }
 
 pub fn dynamic_function(identifier: &str, instructions: Vec<Operation>, return_type: Type) -> Function {
-    Function {signature: identifier.to_string(), args: None, instructions, return_type }
+    Function {signature: identifier.to_string(), args: None, instructions, return_type, source: None }
 }
 
-pub fn library_function(identifier: &str, args: Vec<Type>, consumer: fn(&Vec<RuntimeObject>, &mut ObjectStorage) -> Result<RuntimeObject, String>, return_type: Type) -> Function {
+pub fn native_function<F>(identifier: &str, args: Vec<Type>, callback: F, return_type: Type) -> Function
+    where F: Fn(&mut Runtime, &[RuntimeObject]) -> Result<RuntimeObject, RuntimeError> + Send + Sync + 'static {
     Function {
         signature: identifier.to_string(),
         args: Some(args),
         instructions: vec![
-            Native {callback: consumer},
+            Native {callback: Arc::new(callback)},
             Return
         ],
-        return_type
+        return_type,
+        source: None
     }
 }
 
-pub fn dynamic_library_function( identifier: &str, consumer: fn(&Vec<RuntimeObject>, &mut ObjectStorage) -> Result<RuntimeObject, String>, return_type: Type) -> Function {
+pub fn dynamic_native_function<F>(identifier: &str, callback: F, return_type: Type) -> Function
+    where F: Fn(&mut Runtime, &[RuntimeObject]) -> Result<RuntimeObject, RuntimeError> + Send + Sync + 'static {
     Function {
         signature: identifier.to_string(),
         args: None,
         instructions: vec![
-            Native {callback: consumer},
+            Native {callback: Arc::new(callback)},
             Return
         ],
-        return_type
+        return_type,
+        source: None
     }
-}
\ No newline at end of file
+}
+
+/// Registers a rust function as native, argument checks and types are derived from its signature.
+pub fn typed_function<Args, F: NativeFn<Args>>(identifier: &str, callback: F) -> Function {
+    native_function(identifier, F::arg_types(), move |runtime, args| callback.invoke(&mut runtime.storage, args), F::return_type())
+}
+
+/// Like `typed_function`, the function additionally receives the calling runtime as first parameter.
+pub fn runtime_function<Args, F: RuntimeNativeFn<Args>>(identifier: &str, callback: F) -> Function {
+    native_function(identifier, F::arg_types(), move |runtime, args| callback.invoke(runtime, args), F::return_type())
+}
+
+pub fn library_function(identifier: &str, args: Vec<Type>, consumer: LibraryCallback, return_type: Type) -> Function {
+    native_function(identifier, args, move |runtime, args| Ok(consumer(args, &mut runtime.storage)?), return_type)
+}
+
+pub fn dynamic_library_function( identifier: &str, consumer: LibraryCallback, return_type: Type) -> Function {
+    dynamic_native_function(identifier, move |runtime, args| Ok(consumer(args, &mut runtime.storage)?), return_type)
+}
@@ -0,0 +1,189 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use crate::runtime::object_storage::ObjectStorage;
+use crate::runtime::RuntimeObject;
+
+/*
+
+Structural equality and hashing, objects are compared by their fields instead of their storage id
+
+ */
+
+/// Compares by content, two objects are equal if they are of the same struct or enum variant and their fields are deep equal.
+/// Unlike `==` NaN equals NaN, so it can be used as a map key.
+pub fn deep_eq(storage: &ObjectStorage, a: &RuntimeObject, b: &RuntimeObject) -> bool {
+    deep_eq_inner(storage, a, b, &mut HashSet::new())
+}
+
+//pairs already being compared count as equal, so cyclic objects terminate
+fn deep_eq_inner(storage: &ObjectStorage, a: &RuntimeObject, b: &RuntimeObject, visiting: &mut HashSet<(usize, usize)>) -> bool {
+    match (a, b) {
+        (RuntimeObject::Object(a), RuntimeObject::Object(b)) => {
+            if a == b || !visiting.insert((a.id(), b.id())) {
+                return true
+            }
+            let (fields, other) = (storage.borow_fields(a), storage.borow_fields(b));
+            a.type_name() == b.type_name()
+                && a.variant() == b.variant()
+                && fields.len() == other.len()
+                && fields.iter().all(|(key, value)| match other.get(key) {
+                    Some(value2) => deep_eq_inner(storage, value, value2, visiting),
+                    None => false
+                })
+        }
+        (RuntimeObject::List(a), RuntimeObject::List(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| deep_eq_inner(storage, a, b, visiting))
+        }
+        (RuntimeObject::Map(a), RuntimeObject::Map(b)) => {
+            a.len() == b.len() && a.iter().all(|(key, value)| match b.get(storage, key) {
+                Some(value2) => deep_eq_inner(storage, value, value2, visiting),
+                None => false
+            })
+        }
+        (RuntimeObject::Num(a), RuntimeObject::Num(b)) => a == b || (a.is_nan() && b.is_nan()),
+        (a, b) => a == b
+    }
+}
+
+//objects nested deeper than this do not contribute to the hash
+const HASH_DEPTH: usize = 4;
+
+/// Hash that agrees with `deep_eq`, equal values always hash the same.
+///
+/// Only objects up to `HASH_DEPTH` levels deep are hashed. `deep_eq` treats cycles that unfold
+/// into the same fields as equal even if they have a different length, e.g. an object pointing
+/// to itself and two objects pointing to each other, so no hash may depend on where a cycle closes.
+pub fn deep_hash(storage: &ObjectStorage, value: &RuntimeObject) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hash_inner(storage, value, &mut hasher, HASH_DEPTH);
+    hasher.finish()
+}
+
+fn hash_inner(storage: &ObjectStorage, value: &RuntimeObject, hasher: &mut DefaultHasher, depth: usize) {
+    std::mem::discriminant(value).hash(hasher);
+    match value {
+        RuntimeObject::Num(n) => {
+            //0.0 == -0.0 and NaN deep equals NaN, so they need the same hash
+            let n = if *n == 0.0 { 0.0 } else if n.is_nan() { f64::NAN } else { *n };
+            n.to_bits().hash(hasher)
+        }
+        RuntimeObject::Int(n) => n.hash(hasher),
+        RuntimeObject::Str(s) => s.hash(hasher),
+        RuntimeObject::Bool(b) => b.hash(hasher),
+        RuntimeObject::Void => {},
+        RuntimeObject::Handle(h) => h.address().hash(hasher),
+        RuntimeObject::List(items) => {
+            items.len().hash(hasher);
+            items.iter().for_each(|it| hash_inner(storage, it, hasher, depth));
+        }
+        RuntimeObject::Map(map) => {
+            //entry order does not matter for equality, so combine the entry hashes order independently
+            let combined = map.iter().fold(0u64, |acc, (key, value)| {
+                let mut entry = DefaultHasher::new();
+                hash_inner(storage, key, &mut entry, depth);
+                hash_inner(storage, value, &mut entry, depth);
+                acc.wrapping_add(entry.finish())
+            });
+            combined.hash(hasher)
+        }
+        RuntimeObject::Object(o) => {
+            o.type_name().hash(hasher);
+            o.variant().hash(hasher);
+            if depth == 0 {
+                return
+            }
+            let fields = storage.borow_fields(o);
+            let mut keys: Vec<&String> = fields.keys().collect();
+            keys.sort();
+            for key in keys {
+                key.hash(hasher);
+                hash_inner(storage, &fields[key], hasher, depth - 1);
+            }
+        }
+    }
+}
+
+/*
+
+Value map: keys are compared with deep_eq, hashed when they are inserted
+
+An object key is hashed by its fields at that time, changing them afterwards leaves the entry
+under the old hash where lookups of the changed object do not find it, like keys of a HashMap
+
+ */
+
+#[derive(Clone, Debug, Default)]
+pub struct ValueMap {
+    entries: Vec<(RuntimeObject, RuntimeObject)>,
+    //deep hash to positions in entries
+    index: HashMap<u64, Vec<usize>>
+}
+
+impl ValueMap {
+
+    pub fn new() -> ValueMap {
+        ValueMap::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Entries in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = (&RuntimeObject, &RuntimeObject)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+
+    fn position(&self, storage: &ObjectStorage, key: &RuntimeObject) -> Option<usize> {
+        self.index.get(&deep_hash(storage, key))?
+            .iter()
+            .copied()
+            .find(|i| deep_eq(storage, &self.entries[*i].0, key))
+    }
+
+    pub fn get(&self, storage: &ObjectStorage, key: &RuntimeObject) -> Option<&RuntimeObject> {
+        self.position(storage, key).map(|i| &self.entries[i].1)
+    }
+
+    pub fn contains_key(&self, storage: &ObjectStorage, key: &RuntimeObject) -> bool {
+        self.position(storage, key).is_some()
+    }
+
+    /// Replaces the value of an equal key, otherwise appends a new entry.
+    pub fn insert(&mut self, storage: &ObjectStorage, key: RuntimeObject, value: RuntimeObject) {
+        match self.position(storage, &key) {
+            Some(i) => self.entries[i].1 = value,
+            None => {
+                self.index.entry(deep_hash(storage, &key)).or_default().push(self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    pub fn remove(&mut self, storage: &ObjectStorage, key: &RuntimeObject) -> Option<RuntimeObject> {
+        let i = self.position(storage, key)?;
+        let (_, value) = self.entries.remove(i);
+        //positions after the removed entry shifted, rebuild instead of patching
+        self.index.clear();
+        for (i, (key, _)) in self.entries.iter().enumerate() {
+            self.index.entry(deep_hash(storage, key)).or_default().push(i);
+        }
+        Some(value)
+    }
+}
+
+/// Same entries in any order, keys and values compared with `==`, so objects by identity.
+impl PartialEq for ValueMap {
+    fn eq(&self, other: &Self) -> bool {
+        //equal keys were hashed alike, so only the entries under the same hash need to be compared
+        self.len() == other.len() && self.index.iter().all(|(hash, positions)| positions.iter().all(|i| {
+            other.index.get(hash).is_some_and(|candidates| candidates.iter().any(|j| other.entries[*j] == self.entries[*i]))
+        }))
+    }
+}
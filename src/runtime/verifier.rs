@@ -0,0 +1,200 @@
+use crate::runtime::error::{ErrorKind, RuntimeError};
+use crate::runtime::{type_conforms, Function, MatchCase, MethodSignature, Operation, Runtime, Type};
+
+/*
+
+Verifier: static checks over loaded functions before anything runs
+
+ */
+
+/// Checks every registered function, the first problem found is returned as a type error.
+pub fn verify(runtime: &Runtime) -> Result<(), RuntimeError> {
+    let mut functions: Vec<&Function> = runtime.functions().collect();
+    //stable order, so the same program always reports the same error
+    functions.sort_by(|a, b| a.signature.cmp(&b.signature));
+    for function in functions {
+        verify_function(runtime, function)?;
+    }
+    Ok(())
+}
+
+pub fn verify_function(runtime: &Runtime, function: &Function) -> Result<(), RuntimeError> {
+    verify_signature_types(runtime, function)?;
+    verify_operator(runtime, function)?;
+    verify_conformance(runtime, function)?;
+    verify_matches(runtime, function, &function.instructions)?;
+    verify_return_type(runtime, function)
+}
+
+/// Comparison overloads of structs are read by the runtime, so their result type is fixed.
+fn verify_operator(runtime: &Runtime, function: &Function) -> Result<(), RuntimeError> {
+    let (name, op) = match function.signature.split_once(':') {
+        Some((name, op)) if runtime.struct_fields(name).is_some() => (name, op),
+        _ => return Ok(())
+    };
+    let allowed = match op {
+        "eq" => matches!(function.return_type, Type::Bool | Type::Any),
+        "cmp" => matches!(function.return_type, Type::Int | Type::Num | Type::Any),
+        _ => true
+    };
+    if !allowed {
+        return Err(RuntimeError::new(
+            ErrorKind::Type,
+            format!("Operator {}:{} has to return {}, not {}", name, op, if op == "eq" { "a Boolean" } else { "a Number or Integer" }, function.return_type)
+        ))
+    }
+    Ok(())
+}
+
+/// Struct names in a signature have to be declared somewhere in the program.
+fn verify_signature_types(runtime: &Runtime, function: &Function) -> Result<(), RuntimeError> {
+    let args = function.args.iter().flatten();
+    for tp in args.chain([&function.return_type]) {
+        if let Some(name) = unknown_struct(runtime, tp) {
+            return Err(RuntimeError::new(ErrorKind::Type, format!("Unknown type {} in signature of {}", name, function.signature)))
+        }
+    }
+    Ok(())
+}
+
+fn unknown_struct<'a>(runtime: &Runtime, tp: &'a Type) -> Option<&'a str> {
+    match tp {
+        Type::Struct(name) if !runtime.declares_type(name) => Some(name),
+        Type::List(element) => unknown_struct(runtime, element),
+        _ => None
+    }
+}
+
+/// A struct has to define every method of the interfaces it declares, with the same signature.
+fn verify_conformance(runtime: &Runtime, function: &Function) -> Result<(), RuntimeError> {
+    let (name, interfaces) = match function.instructions.first() {
+        Some(Operation::InitStruct { name, interfaces, .. }) => (name, interfaces),
+        _ => return Ok(())
+    };
+    for interface in interfaces {
+        let methods = match runtime.interface_methods(interface) {
+            Some(methods) => methods,
+            None => return Err(RuntimeError::new(ErrorKind::Type, format!("Struct {} implements unknown interface {}", name, interface)))
+        };
+        for method in methods {
+            let signature = format!("{}:{}", name, method.name);
+            let implementation = match runtime.get_function(&signature) {
+                Some(f) => f,
+                None => return Err(RuntimeError::new(ErrorKind::Type, format!("Struct {} does not implement {}, {} is missing", name, interface, signature)))
+            };
+            if !matches_method(name, interface, &implementation, method) {
+                return Err(RuntimeError::new(
+                    ErrorKind::Type,
+                    format!("{} does not match {} required by {}", signature, method, interface)
+                ))
+            }
+        }
+    }
+    Ok(())
+}
+
+//the receiver may be declared as the struct, the interface or any, the rest has to be exact
+fn matches_method(name: &str, interface: &str, implementation: &Function, method: &MethodSignature) -> bool {
+    let args = match &implementation.args {
+        Some(args) => args,
+        None => return true
+    };
+    let receiver = match args.first() {
+        Some(Type::Struct(receiver)) => receiver == name || receiver == interface,
+        Some(Type::Any) => true,
+        _ => false
+    };
+    receiver && args[1..] == method.args[..] && implementation.return_type == method.return_type
+}
+
+/// Every `match` has to name a declared enum, cover all of its variants and bind at most the payload fields.
+fn verify_matches(runtime: &Runtime, function: &Function, instructions: &[Operation]) -> Result<(), RuntimeError> {
+    for instruction in instructions {
+        let blocks: Vec<&[Operation]> = match instruction {
+            Operation::If(content) | Operation::Else(content) | Operation::AndThen(content) | Operation::OrElse(content) => vec![content],
+            Operation::While { condition, content } => vec![condition, content],
+            Operation::Try { content, catch, .. } => vec![content, catch],
+            Operation::Match { name, cases } => {
+                verify_match(runtime, function, name, cases)?;
+                cases.iter().map(|it| it.content.as_slice()).collect()
+            }
+            _ => vec![]
+        };
+        for block in blocks {
+            verify_matches(runtime, function, block)?;
+        }
+    }
+    Ok(())
+}
+
+fn verify_match(runtime: &Runtime, function: &Function, name: &str, cases: &[MatchCase]) -> Result<(), RuntimeError> {
+    let error = |message: String| Err(RuntimeError::new(ErrorKind::Type, format!("{} in match on {} in {}", message, name, function.signature)));
+    let variants = match runtime.enum_variants(name) {
+        Some(variants) => variants,
+        None => return error(format!("Unknown enum {}", name))
+    };
+
+    let mut covered: Vec<&str> = vec![];
+    for case in cases {
+        let variant = match &case.variant {
+            Some(variant) => variant,
+            None => continue
+        };
+        let fields = match variants.iter().find(|it| it.name == *variant) {
+            Some(it) => it.fields.len(),
+            None => return error(format!("Unknown variant {}", variant))
+        };
+        if covered.contains(&variant.as_str()) {
+            return error(format!("Duplicate case {}", variant))
+        }
+        if case.bindings.len() > fields {
+            return error(format!("Case {} binds {} values but the variant has {} fields", variant, case.bindings.len(), fields))
+        }
+        covered.push(variant);
+    }
+
+    let missing: Vec<&str> = variants.iter().map(|it| it.name.as_str()).filter(|it| !covered.contains(it)).collect();
+    if !missing.is_empty() && !cases.iter().any(|it| it.variant.is_none()) {
+        return error(format!("Missing cases {}", missing.join(", ")))
+    }
+    Ok(())
+}
+
+/// Only returns on the top level of a function leave it, returns in blocks produce the block value.
+fn verify_return_type(runtime: &Runtime, function: &Function) -> Result<(), RuntimeError> {
+    for (i, instruction) in function.instructions.iter().enumerate() {
+        if !matches!(instruction, Operation::Return) || i == 0 {
+            continue
+        }
+        let returned = match static_type(runtime, &function.instructions[i - 1]) {
+            Some(tp) => tp,
+            None => continue
+        };
+        if !type_conforms(&function.return_type, &returned, &|name, interface| runtime.implements(name, interface)) {
+            return Err(RuntimeError::new(
+                ErrorKind::Type,
+                format!("Function {} is declared to return {} but returns {} (instruction {})", function.signature, function.return_type, returned, i)
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// Type an instruction pushes, if it is known without running it.
+fn static_type(runtime: &Runtime, instruction: &Operation) -> Option<Type> {
+    match instruction {
+        Operation::LoadConstNum(_) => Some(Type::Num),
+        Operation::LoadConstInt(_) => Some(Type::Int),
+        Operation::LoadConstString(_) => Some(Type::Str),
+        Operation::LoadConstBool(_) => Some(Type::Bool),
+        Operation::EqualityCheck(_) => Some(Type::Bool),
+        Operation::CallFunction { signature, argc: _ } => {
+            let function = runtime.get_function(signature)?;
+            match function.checked_type(&function.return_type) {
+                Type::Any => None,
+                tp => Some(tp)
+            }
+        }
+        _ => None
+    }
+}
@@ -0,0 +1,213 @@
+use std::cell::RefCell;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use crate::runtime::error::{ErrorKind, RuntimeError};
+use crate::runtime::handle::Handle;
+use crate::runtime::object_storage::ObjectStorage;
+use crate::runtime::value::ValueMap;
+use crate::runtime::{Runtime, RuntimeObject};
+
+/*
+
+Workers: script functions running in their own runtime on another thread, values are copied between them
+
+ */
+
+/// Deep copy of a runtime value that can cross threads, objects lose their identity.
+pub enum SendValue {
+    Num(f64),
+    Int(i64),
+    Str(String),
+    Bool(bool),
+    Void,
+    List(Vec<SendValue>),
+    Map(Vec<(SendValue, SendValue)>),
+    Object { type_name: Option<String>, variant: Option<String>, fields: Vec<(String, SendValue)> }
+}
+
+impl SendValue {
+
+    /// Copies a value, handles are bound to their thread and cyclic objects cannot be copied.
+    pub fn copy(storage: &ObjectStorage, value: &RuntimeObject) -> Result<SendValue, RuntimeError> {
+        SendValue::copy_inner(storage, value, &mut vec![])
+    }
+
+    fn copy_inner(storage: &ObjectStorage, value: &RuntimeObject, path: &mut Vec<usize>) -> Result<SendValue, RuntimeError> {
+        Ok(match value {
+            RuntimeObject::Num(n) => SendValue::Num(*n),
+            RuntimeObject::Int(n) => SendValue::Int(*n),
+            RuntimeObject::Str(s) => SendValue::Str(s.clone()),
+            RuntimeObject::Bool(b) => SendValue::Bool(*b),
+            RuntimeObject::Void => SendValue::Void,
+            RuntimeObject::List(items) => SendValue::List(
+                items.iter().map(|it| SendValue::copy_inner(storage, it, path)).collect::<Result<_, _>>()?
+            ),
+            RuntimeObject::Map(map) => SendValue::Map(
+                map.iter().map(|(key, value)| Ok((SendValue::copy_inner(storage, key, path)?, SendValue::copy_inner(storage, value, path)?))).collect::<Result<_, RuntimeError>>()?
+            ),
+            RuntimeObject::Handle(h) => return Err(RuntimeError::new(
+                ErrorKind::Type,
+                format!("Cannot send {} to another worker, handles are bound to their thread", h.get_signature())
+            )),
+            RuntimeObject::Object(o) => {
+                if path.contains(&o.id()) {
+                    return Err(RuntimeError::new(ErrorKind::Type, format!("Cannot send {}, it contains itself", o.display_name())))
+                }
+                path.push(o.id());
+                let mut fields = vec![];
+                for (key, value) in storage.borow_fields(o) {
+                    fields.push((key.clone(), SendValue::copy_inner(storage, value, path)?));
+                }
+                path.pop();
+                SendValue::Object { type_name: o.type_name().map(str::to_string), variant: o.variant().map(str::to_string), fields }
+            }
+        })
+    }
+
+    /// Rebuilds the value in `storage`, objects are allocated fresh.
+    pub fn restore(self, storage: &mut ObjectStorage) -> RuntimeObject {
+        match self {
+            SendValue::Num(n) => RuntimeObject::Num(n),
+            SendValue::Int(n) => RuntimeObject::Int(n),
+            SendValue::Str(s) => RuntimeObject::Str(s),
+            SendValue::Bool(b) => RuntimeObject::Bool(b),
+            SendValue::Void => RuntimeObject::Void,
+            SendValue::List(items) => RuntimeObject::List(items.into_iter().map(|it| it.restore(storage)).collect()),
+            SendValue::Map(entries) => {
+                let mut map = ValueMap::new();
+                for (key, value) in entries {
+                    let (key, value) = (key.restore(storage), value.restore(storage));
+                    map.insert(storage, key, value);
+                }
+                RuntimeObject::Map(map)
+            }
+            SendValue::Object { type_name, variant, fields } => {
+                let object = match (type_name, variant) {
+                    (Some(name), Some(variant)) => storage.allocate_variant_object(&name, &variant),
+                    (Some(name), None) => storage.allocate_typed_object(&name),
+                    _ => storage.allocate_object()
+                };
+                for (key, value) in fields {
+                    let value = value.restore(storage);
+                    storage.set_field(&object, key, value);
+                }
+                RuntimeObject::Object(object)
+            }
+        }
+    }
+}
+
+/// One end of the channel between a worker and its parent.
+pub struct Port {
+    //None once closed
+    sender: RefCell<Option<Sender<SendValue>>>,
+    receiver: Receiver<SendValue>
+}
+
+impl Port {
+
+    fn pair() -> (Port, Port) {
+        let (to_worker, from_parent) = channel();
+        let (to_parent, from_worker) = channel();
+        (
+            Port { sender: RefCell::new(Some(to_worker)), receiver: from_worker },
+            Port { sender: RefCell::new(Some(to_parent)), receiver: from_parent }
+        )
+    }
+
+    pub fn send(&self, runtime: &Runtime, value: &RuntimeObject) -> Result<(), RuntimeError> {
+        let value = SendValue::copy(&runtime.storage, value)?;
+        match self.sender.borrow().as_ref() {
+            Some(sender) => sender.send(value).map_err(|_| closed()),
+            None => Err(closed())
+        }
+    }
+
+    /// Stops sending, the other side fails to receive once it took what was sent before.
+    pub fn close(&self) {
+        self.sender.borrow_mut().take();
+    }
+
+    /// Blocks until a value arrives, fails once the other side is gone and nothing is left.
+    pub fn receive(&self, runtime: &mut Runtime) -> Result<RuntimeObject, RuntimeError> {
+        match self.receiver.recv() {
+            Ok(value) => Ok(value.restore(&mut runtime.storage)),
+            Err(_) => Err(closed())
+        }
+    }
+}
+
+fn closed() -> RuntimeError {
+    RuntimeError::new(ErrorKind::Runtime, "Channel is closed, the other side is gone".to_string())
+}
+
+/// Parent side of a worker, scripts get it as a handle tagged `worker`.
+pub struct Worker {
+    pub signature: String,
+    pub port: Port,
+    //None once joined
+    thread: RefCell<Option<JoinHandle<Result<SendValue, RuntimeError>>>>
+}
+
+impl Worker {
+
+    /// Waits for the worker function and its event loop, its error is returned with the worker in the trace.
+    /// Closes the channel to the worker first, a worker waiting in `channel:receive` would wait forever otherwise.
+    pub fn join(&self, runtime: &mut Runtime) -> Result<RuntimeObject, RuntimeError> {
+        let thread = match self.thread.borrow_mut().take() {
+            Some(thread) => thread,
+            None => return Err(RuntimeError::new(ErrorKind::Runtime, format!("Worker {} was already joined", self.signature)))
+        };
+        self.port.close();
+        match thread.join() {
+            Ok(Ok(value)) => Ok(value.restore(&mut runtime.storage)),
+            Ok(Err(e)) => Err(e.trace(format!("Error in worker {}", self.signature))),
+            Err(_) => Err(RuntimeError::new(ErrorKind::Runtime, format!("Worker {} panicked", self.signature)))
+        }
+    }
+}
+
+impl Runtime {
+
+    /// Runs `signature` in a new runtime with the same functions, permissions, filesystem, arguments and environment on its own thread.
+    /// A function taking one argument gets the worker side of the channel as a handle tagged `channel`.
+    pub fn spawn_worker(&self, signature: &str) -> Result<Handle, RuntimeError> {
+        let function = match self.get_function(signature) {
+            Some(f) => f,
+            None => return Err(RuntimeError::new(ErrorKind::NotFound, format!("No function found with name {}", signature)))
+        };
+        let takes_port = match &function.args {
+            Some(args) if args.len() > 1 => return Err(RuntimeError::new(
+                ErrorKind::Type,
+                format!("Worker function {} has to take no arguments or the channel", signature)
+            )),
+            Some(args) => args.len() == 1,
+            None => false
+        };
+
+        let (parent, worker) = Port::pair();
+        let functions = self.functions.clone();
+        let permissions = self.permissions.clone();
+        let filesystem = self.filesystem.clone();
+        let script_args = self.script_args.clone();
+        let env = self.env.clone();
+        let name = signature.to_string();
+        let thread = thread::Builder::new().name(format!("worker {}", signature)).spawn(move || {
+            let mut runtime = Runtime::new();
+            runtime.functions = functions;
+            runtime.permissions = permissions;
+            runtime.filesystem = filesystem;
+            runtime.script_args = script_args;
+            runtime.env = env;
+            let args = if takes_port { vec![RuntimeObject::Handle(Handle::new("channel", worker))] } else { vec![] };
+            let value = runtime.call(&name, args).and_then(|value| {
+                runtime.run_event_loop()?;
+                Ok(value)
+            })?;
+            SendValue::copy(&runtime.storage, &value)
+        }).map_err(|e| RuntimeError::new(ErrorKind::Runtime, format!("Cannot start worker {}: {}", signature, e)))?;
+
+        Ok(Handle::new("worker", Worker { signature: signature.to_string(), port: parent, thread: RefCell::new(Some(thread)) }))
+    }
+}
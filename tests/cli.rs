@@ -0,0 +1,292 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn write_program(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("dscript_cli_{}_{}.dbc", name, std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    path
+}
+
+/// Runs `source` as the only script file with the given command line around it.
+fn run(name: &str, source: &str, flags: &[&str], script_args: &[&str]) -> Output {
+    let program = write_program(name, source);
+    let output = Command::new(env!("CARGO_BIN_EXE_DScriptRuntime"))
+        .args(flags)
+        .arg(&program)
+        .arg("--")
+        .args(script_args)
+        .output()
+        .expect("Cannot start the runtime");
+    let _ = std::fs::remove_file(program);
+    output
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn script_arguments_reach_main_and_env_args() {
+    let source = "
+func main std/list endArgs std/int
+loadArg 0
+call std/io/print 1
+call std/env/args 0
+call std/io/print 1
+loadInt 0
+return
+end
+";
+    let output = run("args", source, &[], &["a", "b c"]);
+    assert_eq!(output.status.code(), Some(0));
+    let printed = stdout(&output);
+    let lines: Vec<&str> = printed.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0], lines[1]);
+    assert!(lines[0].contains('a') && lines[0].contains("b c"), "{}", lines[0]);
+}
+
+#[test]
+fn entry_return_value_is_the_exit_code() {
+    let source = "
+func main endArgs std/int
+loadInt 3
+return
+end
+func other endArgs std/int
+loadInt 4
+return
+end
+";
+    assert_eq!(run("return", source, &[], &[]).status.code(), Some(3));
+    assert_eq!(run("entry", source, &["--entry", "other"], &[]).status.code(), Some(4));
+    assert_eq!(run("missing", source, &["--entry", "nothing"], &[]).status.code(), Some(65));
+}
+
+#[test]
+fn exit_codes_outside_a_byte_become_1() {
+    for (code, expected) in [("255", 255), ("256", 1), ("-1", 1), ("4294967296", 1)] {
+        let source = format!("func main endArgs std/int\nloadInt {}\nreturn\nend\n", code);
+        assert_eq!(run("range", &source, &[], &[]).status.code(), Some(expected), "returning {}", code);
+    }
+    let source = "
+func main endArgs std/int
+loadInt 300
+call std/process/exit 1
+loadInt 0
+return
+end
+";
+    assert_eq!(run("exit_range", source, &[], &[]).status.code(), Some(1));
+}
+
+#[test]
+fn uncaught_error_exits_with_70() {
+    let source = "
+func main endArgs std/int
+loadInt 1
+getProp missing
+return
+end
+";
+    let output = run("error", source, &[], &[]);
+    assert_eq!(output.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Uncaught"));
+}
+
+const EXIT: &str = "
+func main endArgs std/int
+try do
+loadInt 3
+call std/process/exit 1
+loadInt 1
+return
+end catch e do
+loadString caught'
+call std/io/print 1
+loadInt 0
+return
+end
+return
+end
+";
+
+#[test]
+fn process_exit_unwinds_past_catch() {
+    //exiting needs no permission, even with the deny-by-default command line
+    let output = run("exit", EXIT, &[], &[]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(!stdout(&output).contains("caught"));
+}
+
+#[test]
+fn env_set_only_changes_the_script_environment() {
+    let source = "
+func main endArgs std/int
+loadString value'
+loadString DSCRIPT_CLI_TEST'
+call std/env/set 2
+loadString DSCRIPT_CLI_TEST'
+call std/env/get 1
+call std/io/print 1
+loadString echo $DSCRIPT_CLI_TEST'
+loadString -c'
+@List 2
+loadString sh'
+call std/process/run 2
+getProp stdout
+call std/io/print 1
+loadInt 0
+return
+end
+";
+    let output = run("env", source, &["--allow-env", "--allow-run"], &[]);
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(stdout(&output).lines().filter(|it| *it == "value").count(), 2, "{}", stdout(&output));
+}
+
+#[test]
+fn process_run_collects_output() {
+    let source = "
+func main endArgs std/int
+loadString hello'
+@List 1
+loadString echo'
+call std/process/run 2
+set out
+load out
+getProp stdout
+call std/io/print 1
+load out
+getProp code
+return
+end
+";
+    let output = run("run", source, &["--allow-run"], &[]);
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout(&output).contains("hello"));
+
+    //running programs is denied by default
+    assert_eq!(run("run_denied", source, &[], &[]).status.code(), Some(70));
+}
+
+#[test]
+fn spawned_process_talks_over_its_pipes() {
+    let source = "
+func main endArgs std/int
+@List 0
+loadString cat'
+call std/process/spawn 2
+set p
+loadString ping\n'
+load p
+call process:write 2
+load p
+call process:close_stdin 1
+load p
+call process:read_line 1
+call std/io/print 1
+load p
+call process:wait 1
+return
+end
+";
+    let output = run("spawn", source, &["--allow-run"], &[]);
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout(&output).contains("ping"));
+}
+
+#[test]
+fn scripts_get_no_file_access_by_default() {
+    let source = "
+func main endArgs std/int
+loadString Cargo.toml'
+call std/io/open_file 1
+loadInt 0
+return
+end
+";
+    let output = run("denied", source, &[], &[]);
+    assert_eq!(output.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--allow-read"));
+    assert_eq!(run("allowed", source, &["--allow-read"], &[]).status.code(), Some(0));
+}
+
+const REJECTED: &str = "
+func on_error std/any endArgs std/any
+loadString caught'
+call std/io/print 1
+loadInt 0
+return
+end
+func main endArgs std/int
+loadString /nonexistent/dscript_cli_file'
+call std/fs/read_async 1
+set promise
+CATCH
+loadInt 0
+return
+end
+";
+
+#[test]
+fn rejected_promise_without_catch_is_uncaught() {
+    let output = run("unhandled", &REJECTED.replace("CATCH", ""), &["--allow-read"], &[]);
+    assert_eq!(output.status.code(), Some(70));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Uncaught IoError"), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let output = run("handled", &REJECTED.replace("CATCH", "loadString on_error'\nload promise\ncall promise:catch 2"), &["--allow-read"], &[]);
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    assert!(stdout(&output).contains("caught"));
+}
+
+#[test]
+fn wait_async_waits_for_a_spawned_process() {
+    let source = "
+func main endArgs std/int
+loadString exit 4'
+loadString -c'
+@List 2
+loadString sh'
+call std/process/spawn 2
+call std/process/wait_async 1
+call std/promise/await 1
+return
+end
+";
+    let output = run("wait_async", source, &["--allow-run"], &[]);
+    assert_eq!(output.status.code(), Some(4), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn debugger_quit_stops_the_program_and_returns() {
+    let program = write_program("debug_quit", "
+func main endArgs std/int
+loadInt 1
+@Object x #
+set o
+loadString after'
+call std/io/print 1
+loadInt 3
+return
+end
+");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_DScriptRuntime"))
+        .arg("--debug")
+        .arg(&program)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .expect("Cannot start the runtime");
+    child.stdin.take().unwrap().write_all(b"break main 3\nrun\nprint load o\nquit\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    let _ = std::fs::remove_file(program);
+
+    //the shell returns instead of exiting the process, the rest of main never runs
+    assert_eq!(output.status.code(), Some(0));
+    let printed = stdout(&output);
+    assert!(printed.contains("Object #") && printed.contains("x: 1"), "{}", printed);
+    assert!(printed.contains("main was stopped"), "{}", printed);
+    assert!(!printed.lines().any(|it| it == "after"), "{}", printed);
+}
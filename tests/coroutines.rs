@@ -0,0 +1,36 @@
+use dscript_runtime::{Engine, RuntimeObject};
+
+#[test]
+fn finished_coroutine_keeps_the_callers_argument_alive() {
+    let mut engine = Engine::new();
+    engine.load_source("
+        func gen std/any endArgs std/int
+        mapArg 0 x
+        loadInt 1
+        yield
+        loadInt 2
+        return
+        end
+        func main endArgs std/str
+        loadString original'
+        @Object f #
+        set o
+        load o
+        coroutine gen 1
+        set c
+        load c
+        resume
+        set first
+        load c
+        resume
+        set second
+        loadString hello'
+        @Object f #
+        set other
+        load o
+        getProp f
+        return
+        end
+    ").unwrap();
+    assert_eq!(engine.call("main", vec![]).unwrap(), RuntimeObject::Str("original".to_string()));
+}
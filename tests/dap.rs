@@ -0,0 +1,364 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+use dscript_runtime::dap::json::Json;
+
+const PROGRAM: &str = "
+struct Point x std/int y std/int end
+func add std/int std/int endArgs std/int
+mapArg 0 a
+mapArg 1 b
+load a
+load b
+binary add
+return
+end
+func main endArgs std/int
+loadInt 2
+loadInt 1
+call Point 2
+set p
+loadInt 5
+loadInt 4
+call add 2
+set s
+load s
+call std/io/print 1
+loadInt 3
+return
+end
+";
+
+/// Scripted client talking to `DScriptRuntime --dap` over its stdio.
+struct Client {
+    child: Child,
+    input: ChildStdin,
+    output: BufReader<ChildStdout>,
+    seq: i64,
+    //events that arrived while waiting for a response
+    events: Vec<Json>
+}
+
+impl Client {
+
+    fn start() -> Client {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_DScriptRuntime"))
+            .arg("--dap")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Cannot start the debug adapter");
+        let input = child.stdin.take().unwrap();
+        let output = BufReader::new(child.stdout.take().unwrap());
+        Client { child, input, output, seq: 0, events: vec![] }
+    }
+
+    fn read(&mut self) -> Json {
+        read_message(&mut self.output)
+    }
+
+    fn request(&mut self, command: &str, arguments: Json) -> Json {
+        self.seq += 1;
+        let body = Json::object(vec![("seq", self.seq.into()), ("type", "request".into()), ("command", command.into()), ("arguments", arguments)]).to_string();
+        write!(self.input, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        self.input.flush().unwrap();
+        loop {
+            let message = self.read();
+            match message.get("type").and_then(|it| it.as_str()) {
+                Some("response") => {
+                    assert_eq!(message.get("request_seq").and_then(|it| it.as_i64()), Some(self.seq));
+                    return message
+                }
+                _ => self.events.push(message)
+            }
+        }
+    }
+
+    /// Body of a successful response.
+    fn success(&mut self, command: &str, arguments: Json) -> Json {
+        let response = self.request(command, arguments);
+        assert_eq!(response.get("success"), Some(&Json::Bool(true)), "{} failed: {}", command, response);
+        response.get("body").cloned().unwrap_or(Json::Null)
+    }
+
+    fn event(&mut self, name: &str) -> Json {
+        loop {
+            let message = match self.events.is_empty() {
+                true => self.read(),
+                false => self.events.remove(0)
+            };
+            if message.get("event").and_then(|it| it.as_str()) == Some(name) {
+                return message.get("body").cloned().unwrap_or(Json::Null)
+            }
+        }
+    }
+
+    fn launch(&mut self, program: &Path, stop_on_entry: bool) {
+        self.success("initialize", Json::object(vec![("adapterID", "dscript".into())]));
+        self.event("initialized");
+        self.success("launch", Json::object(vec![
+            ("program", program.to_str().unwrap().into()),
+            ("stopOnEntry", stop_on_entry.into())
+        ]));
+    }
+
+    fn frames(&mut self) -> Vec<Json> {
+        let body = self.success("stackTrace", Json::object(vec![("threadId", 1i64.into())]));
+        body.get("stackFrames").and_then(|it| it.as_array()).unwrap().to_vec()
+    }
+
+    //name to value of the variables behind a reference
+    fn variables(&mut self, reference: &Json) -> Vec<(String, Json)> {
+        let body = self.success("variables", Json::object(vec![("variablesReference", reference.clone())]));
+        body.get("variables").and_then(|it| it.as_array()).unwrap().iter()
+            .map(|it| (it.get("name").and_then(|it| it.as_str()).unwrap().to_string(), it.clone()))
+            .collect()
+    }
+
+    fn locals(&mut self, frame: &Json) -> Vec<(String, Json)> {
+        let body = self.success("scopes", Json::object(vec![("frameId", frame.get("id").cloned().unwrap())]));
+        let scopes = body.get("scopes").and_then(|it| it.as_array()).unwrap().to_vec();
+        let locals = scopes.iter().find(|it| it.get("name").and_then(|it| it.as_str()) == Some("Locals")).unwrap();
+        self.variables(locals.get("variablesReference").unwrap())
+    }
+}
+
+fn read_message(output: &mut BufReader<ChildStdout>) -> Json {
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        assert_ne!(output.read_line(&mut line).unwrap(), 0, "The adapter closed its output");
+        let line = line.trim_end();
+        if line.is_empty() {
+            break
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse().unwrap();
+        }
+    }
+    let mut body = vec![0; length];
+    output.read_exact(&mut body).unwrap();
+    Json::parse(&String::from_utf8(body).unwrap()).unwrap()
+}
+
+fn write_program(name: &str) -> PathBuf {
+    write_source(name, PROGRAM)
+}
+
+fn write_source(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("dscript_dap_{}_{}.dbc", name, std::process::id()));
+    std::fs::write(&path, source).unwrap();
+    path
+}
+
+fn value<'a>(variables: &'a [(String, Json)], name: &str) -> &'a Json {
+    &variables.iter().find(|(n, _)| n == name).unwrap_or_else(|| panic!("No variable {}", name)).1
+}
+
+fn text(json: &Json, key: &str) -> String {
+    json.get(key).and_then(|it| it.as_str()).unwrap_or_default().to_string()
+}
+
+#[test]
+fn breakpoints_stepping_and_variables() {
+    let program = write_program("breakpoints");
+    let mut client = Client::start();
+    client.launch(&program, false);
+
+    //line 8 of the program is the `binary add` of add
+    let body = client.success("setBreakpoints", Json::object(vec![
+        ("source", Json::object(vec![("path", program.to_str().unwrap().into())])),
+        ("breakpoints", vec![Json::object(vec![("line", 8i64.into())]), Json::object(vec![("line", 99i64.into())])].into())
+    ]));
+    let results = body.get("breakpoints").and_then(|it| it.as_array()).unwrap();
+    assert_eq!(results[0].get("verified"), Some(&Json::Bool(true)));
+    assert_eq!(results[0].get("line").and_then(|it| it.as_i64()), Some(8));
+    assert_eq!(results[1].get("verified"), Some(&Json::Bool(false)));
+    client.success("configurationDone", Json::Null);
+
+    assert_eq!(text(&client.event("stopped"), "reason"), "breakpoint");
+    let threads = client.success("threads", Json::Null);
+    assert_eq!(threads.get("threads").and_then(|it| it.as_array()).map(|it| it.len()), Some(1));
+
+    let frames = client.frames();
+    assert_eq!(text(&frames[0], "name"), "add");
+    assert_eq!(frames[0].get("line").and_then(|it| it.as_i64()), Some(8));
+    assert_eq!(text(frames[0].get("source").unwrap(), "path"), program.to_str().unwrap());
+    assert_eq!(text(&frames[1], "name"), "main");
+    assert_eq!(frames[1].get("line").and_then(|it| it.as_i64()), Some(18));
+
+    let locals = client.locals(&frames[0]);
+    assert_eq!(text(value(&locals, "a"), "value"), "4");
+    assert_eq!(text(value(&locals, "b"), "value"), "5");
+
+    //objects expand to their fields
+    let locals = client.locals(&frames[1]);
+    let point = value(&locals, "p");
+    assert_eq!(text(point, "type"), "Point");
+    let fields = client.variables(point.get("variablesReference").unwrap());
+    assert_eq!(text(value(&fields, "x"), "value"), "1");
+    assert_eq!(text(value(&fields, "y"), "value"), "2");
+
+    let evaluated = client.success("evaluate", Json::object(vec![("expression", "load p getProp y".into()), ("frameId", frames[1].get("id").cloned().unwrap())]));
+    assert_eq!(text(&evaluated, "result"), "2");
+
+    client.success("next", Json::object(vec![("threadId", 1i64.into())]));
+    assert_eq!(text(&client.event("stopped"), "reason"), "step");
+    assert_eq!(client.frames()[0].get("line").and_then(|it| it.as_i64()), Some(9));
+
+    client.success("stepOut", Json::object(vec![("threadId", 1i64.into())]));
+    client.event("stopped");
+    let frames = client.frames();
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].get("line").and_then(|it| it.as_i64()), Some(19));
+
+    client.success("continue", Json::object(vec![("threadId", 1i64.into())]));
+    assert_eq!(text(&client.event("output"), "output"), "9\n");
+    assert_eq!(client.event("exited").get("exitCode").and_then(|it| it.as_i64()), Some(3));
+    client.event("terminated");
+
+    client.success("disconnect", Json::Null);
+    assert!(client.child.wait().unwrap().success());
+    let _ = std::fs::remove_file(program);
+}
+
+#[test]
+fn stop_on_entry_and_step_in() {
+    let program = write_program("entry");
+    let mut client = Client::start();
+    client.success("initialize", Json::Null);
+
+    let response = client.request("launch", Json::object(vec![("program", "/nonexistent/program.dbc".into())]));
+    assert_eq!(response.get("success"), Some(&Json::Bool(false)));
+
+    client.launch(&program, true);
+    client.success("configurationDone", Json::Null);
+    assert_eq!(text(&client.event("stopped"), "reason"), "entry");
+    let frames = client.frames();
+    assert_eq!(text(&frames[0], "name"), "main");
+    assert_eq!(frames[0].get("line").and_then(|it| it.as_i64()), Some(12));
+
+    //a breakpoint on a line without an instruction moves to the next one, the constructor Point on line 2
+    let body = client.success("setBreakpoints", Json::object(vec![
+        ("source", Json::object(vec![("path", program.to_str().unwrap().into())])),
+        ("breakpoints", vec![Json::object(vec![("line", 1i64.into())])].into())
+    ]));
+    assert_eq!(body.get("breakpoints").and_then(|it| it.as_array()).unwrap()[0].get("line").and_then(|it| it.as_i64()), Some(2));
+
+    //naming a function gives its listing as source, one instruction per line
+    let body = client.success("setBreakpoints", Json::object(vec![
+        ("source", Json::object(vec![("name", "main".into())])),
+        ("breakpoints", vec![Json::object(vec![("line", 1i64.into())])].into())
+    ]));
+    let reference = body.get("breakpoints").and_then(|it| it.as_array()).unwrap()[0].get("source").and_then(|it| it.get("sourceReference")).cloned().unwrap();
+    let source = client.success("source", Json::object(vec![("sourceReference", reference)]));
+    assert_eq!(text(&source, "content").lines().count(), 12);
+
+    for _ in 0..3 {
+        client.success("stepIn", Json::object(vec![("threadId", 1i64.into())]));
+        client.event("stopped");
+    }
+    let frames = client.frames();
+    assert_eq!(text(&frames[0], "name"), "Point");
+    assert_eq!(frames.len(), 2);
+
+    //disconnecting while paused ends the program
+    client.success("disconnect", Json::Null);
+    assert!(client.child.wait().unwrap().success());
+    let _ = std::fs::remove_file(program);
+}
+
+#[test]
+fn failed_launch_registers_nothing() {
+    //helper does not verify, it would break every later launch if it stayed registered
+    let broken = write_source("broken", "
+func helper endArgs std/int
+loadString text'
+return
+end
+");
+    let program = write_program("after_broken");
+    let mut client = Client::start();
+    client.success("initialize", Json::Null);
+
+    let response = client.request("launch", Json::object(vec![("program", broken.to_str().unwrap().into())]));
+    assert_eq!(response.get("success"), Some(&Json::Bool(false)));
+
+    client.launch(&program, false);
+    client.success("configurationDone", Json::Null);
+    assert_eq!(client.event("exited").get("exitCode").and_then(|it| it.as_i64()), Some(3));
+    client.success("disconnect", Json::Null);
+    assert!(client.child.wait().unwrap().success());
+    let _ = std::fs::remove_file(broken);
+    let _ = std::fs::remove_file(program);
+}
+
+#[test]
+fn terminate_unwinds_past_catch() {
+    let program = write_source("terminate", "
+func main endArgs std/int
+try do
+loadString inside'
+call std/io/print 1
+loadInt 1
+return
+end catch e do
+loadString caught'
+call std/io/print 1
+loadInt 2
+return
+end
+return
+end
+");
+    let mut client = Client::start();
+    client.launch(&program, false);
+    client.success("setBreakpoints", Json::object(vec![
+        ("source", Json::object(vec![("name", "main".into())])),
+        ("breakpoints", vec![Json::object(vec![("line", 3i64.into())])].into())
+    ]));
+    client.success("configurationDone", Json::Null);
+    client.event("stopped");
+
+    //the program ends without an exit code and the adapter keeps serving until the client disconnects
+    client.success("terminate", Json::Null);
+    client.event("terminated");
+    assert!(client.events.iter().all(|it| it.get("event").and_then(|it| it.as_str()) != Some("exited")));
+    assert!(client.events.iter().all(|it| !text(it.get("body").unwrap_or(&Json::Null), "output").contains("caught")));
+    client.success("threads", Json::Null);
+
+    client.success("disconnect", Json::Null);
+    assert!(client.child.wait().unwrap().success());
+    let _ = std::fs::remove_file(program);
+}
+
+#[test]
+fn invalid_messages_are_skipped() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_DScriptRuntime"))
+        .arg("--dap")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Cannot start the debug adapter");
+    let mut input = child.stdin.take().unwrap();
+    let mut output = BufReader::new(child.stdout.take().unwrap());
+    //written from another thread, the adapter answers each with an output event that has to be read meanwhile
+    let writer = std::thread::spawn(move || {
+        let bad = "Content-Length: 1\r\n\r\nx".repeat(100_000);
+        input.write_all(bad.as_bytes()).unwrap();
+        let body = Json::object(vec![("seq", 1i64.into()), ("type", "request".into()), ("command", "threads".into())]).to_string();
+        write!(input, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        //a length nobody could allocate is skipped up to the end of the stream
+        write!(input, "Content-Length: 18446744073709551615\r\n\r\n").unwrap();
+    });
+    let response = loop {
+        let message = read_message(&mut output);
+        if message.get("type").and_then(|it| it.as_str()) == Some("response") {
+            break message
+        }
+    };
+    assert_eq!(response.get("success"), Some(&Json::Bool(true)));
+    writer.join().unwrap();
+    assert!(child.wait().unwrap().success());
+}
@@ -0,0 +1,380 @@
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, Ordering};
+use dscript_runtime::{Engine, EngineError, ErrorKind, Handle, RuntimeObject, Type};
+
+#[test]
+fn failed_load_leaves_the_engine_unchanged() {
+    let mut engine = Engine::new();
+    engine.load_source("
+        func answer endArgs std/int
+        loadInt 42
+        return
+        end
+    ").unwrap();
+
+    //answer would be replaced and broken would be added, but broken does not verify
+    let result = engine.load_source("
+        func answer endArgs std/int
+        loadInt 0
+        return
+        end
+        func broken endArgs std/int
+        loadString text'
+        return
+        end
+    ");
+    assert!(result.is_err());
+    assert!(!engine.has_function("broken"));
+    assert_eq!(engine.call("answer", vec![]), Ok(RuntimeObject::Int(42)));
+
+    //later loads verify against the old functions only
+    engine.load_source("
+        func other endArgs std/int
+        loadInt 1
+        return
+        end
+    ").unwrap();
+    assert_eq!(engine.call("other", vec![]), Ok(RuntimeObject::Int(1)));
+}
+
+#[test]
+fn natives_declaring_void_accept_any_value() {
+    let mut engine = Engine::new();
+    //Void was the wildcard of host natives before std/any existed
+    engine.register_native("host/echo", vec![Type::Void], |_, args| Ok(args[0].clone()), Type::Void);
+    engine.load_source("
+        func main endArgs std/int
+        loadInt 7
+        call host/echo 1
+        return
+        end
+    ").unwrap();
+    assert_eq!(engine.call("main", vec![]), Ok(RuntimeObject::Int(7)));
+    assert_eq!(engine.call("host/echo", vec![RuntimeObject::Str("text".to_string())]), Ok(RuntimeObject::Str("text".to_string())));
+}
+
+#[test]
+fn cut_off_sources_are_parse_errors() {
+    let sources = [
+        "func",
+        "func main",
+        "func main endArgs",
+        "func main endArgs std/int",
+        "func main endArgs std/int loadInt",
+        "func main endArgs std/int loadString unterminated",
+        "func main endArgs std/int call std/io/print",
+        "func main endArgs std/int @Object a b",
+        "func main endArgs std/int if",
+        "func main endArgs std/int if end",
+        "func main endArgs std/int if do end",
+        "func main endArgs std/int try do loadInt 1 return end",
+        "func main endArgs std/int try do loadInt 1 return end catch",
+        "func main endArgs std/int match",
+        "func main endArgs std/int while do loadBool true return end",
+        "struct",
+        "interface Shape area",
+    ];
+    for source in sources {
+        let mut engine = Engine::new();
+        assert!(matches!(engine.load_source(source), Err(EngineError::Parse(_))), "{}", source);
+    }
+    let error = Engine::new().load_source("func main endArgs std/int loadInt").unwrap_err();
+    assert!(error.to_string().contains("unexpected end of input"), "{}", error);
+}
+
+#[test]
+fn malformed_instructions_are_errors_not_panics() {
+    let cases = [
+        ("return", ErrorKind::StackUnderflow),
+        ("loadInt 1 binary add return", ErrorKind::StackUnderflow),
+        ("loadInt 1 equality eq return", ErrorKind::StackUnderflow),
+        ("call std/math/abs 1 return", ErrorKind::StackUnderflow),
+        ("@Object a b # return", ErrorKind::StackUnderflow),
+        ("load missing return", ErrorKind::UnknownVariable),
+        ("loadArg 2 return", ErrorKind::UnknownVariable),
+        ("mapArg 0 x loadInt 1 return", ErrorKind::UnknownVariable),
+    ];
+    for (body, kind) in cases {
+        let mut engine = Engine::new();
+        engine.load_source(&format!("func main endArgs std/any {} end", body)).unwrap();
+        match engine.call("main", vec![]) {
+            Err(EngineError::Runtime(e)) => assert_eq!(e.kind, kind, "{}", body),
+            other => panic!("{} gave {:?}", body, other)
+        }
+    }
+}
+
+#[test]
+fn natives_keep_captured_state_and_call_back_into_scripts() {
+    let counter = Arc::new(AtomicI64::new(0));
+    let mut engine = Engine::new();
+    let calls = counter.clone();
+    engine.register_native("host/count", vec![], move |_, _| Ok(RuntimeObject::Int(calls.fetch_add(1, Ordering::SeqCst) + 1)), Type::Int);
+    engine.register_native("host/twice", vec![Type::Int], |runtime, args| {
+        let once = runtime.call("double", vec![args[0].clone()])?;
+        runtime.call("double", vec![once])
+    }, Type::Int);
+    engine.load_source("
+        func double std/int endArgs std/int
+        loadArg 0
+        loadArg 0
+        binary add
+        return
+        end
+        func main endArgs std/int
+        call host/count 0
+        call host/count 0
+        binary add
+        call host/twice 1
+        return
+        end
+    ").unwrap();
+
+    //1 + 2, doubled twice by the script
+    assert_eq!(engine.call("main", vec![]), Ok(RuntimeObject::Int(12)));
+    assert_eq!(counter.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn typed_natives_check_their_arguments() {
+    let mut engine = Engine::new();
+    engine.register_fn("host/sum", |numbers: Vec<i64>| numbers.iter().sum::<i64>());
+    let numbers = RuntimeObject::List(vec![RuntimeObject::Int(1), RuntimeObject::Int(2)]);
+    assert_eq!(engine.call("host/sum", vec![numbers]), Ok(RuntimeObject::Int(3)));
+    match engine.call("host/sum", vec![RuntimeObject::Str("1 2".to_string())]) {
+        Err(EngineError::Runtime(e)) => assert_eq!(e.kind, ErrorKind::Type),
+        other => panic!("Expected a type error but got {:?}", other)
+    }
+}
+
+struct Counter(Rc<()>);
+
+#[test]
+fn handles_are_checked_by_their_tag_and_released() {
+    let alive = Rc::new(());
+    let tracker = Rc::downgrade(&alive);
+    let mut engine = Engine::new();
+    engine.register_fn("host/read", |counter: Handle| counter.expect::<Counter>("counter").map(|c| Rc::strong_count(&c.0) as i64));
+    engine.load_source("
+        func step endArgs std/void
+        yield
+        return
+        end
+        func main endArgs std/int
+        coroutine step 0
+        call host/read 1
+        return
+        end
+    ").unwrap();
+
+    let counter = RuntimeObject::Handle(Handle::new("counter", Counter(alive)));
+    assert_eq!(engine.call("host/read", vec![counter]), Ok(RuntimeObject::Int(1)));
+    match engine.call("main", vec![]) {
+        Err(EngineError::Runtime(e)) => assert!(e.message.contains("Expected handle of type counter but got coro"), "{}", e.message),
+        other => panic!("Expected a type error but got {:?}", other)
+    }
+    //a script cannot pass a plain object as a handle
+    engine.load_source("
+        func forged endArgs std/int
+        loadString file'
+        @Object path #
+        call host/read 1
+        return
+        end
+    ").unwrap();
+    match engine.call("forged", vec![]) {
+        Err(EngineError::Runtime(e)) => assert_eq!(e.kind, ErrorKind::Type),
+        other => panic!("Expected a type error but got {:?}", other)
+    }
+
+    drop(engine);
+    assert!(tracker.upgrade().is_none());
+}
+
+#[test]
+fn methods_dispatch_on_the_receiver_type() {
+    let mut engine = Engine::new();
+    engine.load_source("
+        struct Point x std/int end
+        func Point:describe Point endArgs std/str
+        loadString point'
+        return
+        end
+        func text endArgs std/str
+        loadString shout'
+        callMethod to_upper 1
+        return
+        end
+        func number endArgs std/str
+        loadInt 5
+        callMethod as_string 1
+        return
+        end
+        func point endArgs std/str
+        loadInt 1
+        call Point 1
+        callMethod describe 1
+        return
+        end
+        func missing endArgs std/str
+        loadInt 1
+        callMethod describe 1
+        return
+        end
+    ").unwrap();
+
+    assert_eq!(engine.call("text", vec![]), Ok(RuntimeObject::Str("SHOUT".to_string())));
+    //no int:as_string, so *:as_string is called
+    assert_eq!(engine.call("number", vec![]), Ok(RuntimeObject::Str("5".to_string())));
+    assert_eq!(engine.call("point", vec![]), Ok(RuntimeObject::Str("point".to_string())));
+    match engine.call("missing", vec![]) {
+        Err(EngineError::Runtime(e)) => {
+            assert_eq!(e.kind, ErrorKind::NotFound);
+            assert!(e.message.contains("int:describe"), "{}", e.message);
+        }
+        other => panic!("Expected a missing method error but got {:?}", other)
+    }
+}
+
+fn load_error(source: &str) -> String {
+    match Engine::new().load_source(source) {
+        Err(EngineError::Runtime(e)) => e.message,
+        other => panic!("Expected a verify error but got {:?}", other)
+    }
+}
+
+const SHAPES: &str = "
+interface Shape area endArgs std/num end
+struct Circle radius std/num implements Shape end
+struct Square side std/num end
+func total Shape endArgs std/num
+loadArg 0
+callMethod area 1
+return
+end
+";
+
+#[test]
+fn structs_are_checked_against_their_interfaces() {
+    let missing = load_error(SHAPES);
+    assert!(missing.contains("Struct Circle does not implement Shape, Circle:area is missing"), "{}", missing);
+    let mismatch = load_error(&format!("{}
+func Circle:area Circle endArgs std/int
+loadInt 3
+return
+end", SHAPES));
+    assert!(mismatch.contains("Circle:area does not match"), "{}", mismatch);
+
+    let mut engine = Engine::new();
+    engine.load_source(&format!("{}
+func Circle:area Circle endArgs std/num
+loadArg 0
+getProp radius
+return
+end
+func Square:area Square endArgs std/num
+loadArg 0
+getProp side
+return
+end
+func circle endArgs std/num
+loadNum 2
+call Circle 1
+call total 1
+return
+end
+func square endArgs std/num
+loadNum 2
+call Square 1
+call total 1
+return
+end", SHAPES)).unwrap();
+    assert_eq!(engine.call("circle", vec![]), Ok(RuntimeObject::Num(2.0)));
+    //having the methods is not enough, the struct has to declare the interface
+    match engine.call("square", vec![]) {
+        Err(EngineError::Runtime(e)) => assert_eq!(e.kind, ErrorKind::Type),
+        other => panic!("Expected a type error but got {:?}", other)
+    }
+}
+
+const FIGURE: &str = "
+enum Figure variant Circle radius std/num variant Empty end
+";
+
+fn area(cases: &str) -> String {
+    format!("{}
+func area Figure endArgs std/num
+loadArg 0
+match Figure {} end
+return
+end", FIGURE, cases)
+}
+
+#[test]
+fn matches_have_to_cover_every_variant() {
+    let missing = load_error(&area("case Circle r do load r return end"));
+    assert!(missing.contains("Missing cases Empty in match on Figure in area"), "{}", missing);
+    let unknown = load_error(&area("case Square do loadNum 0 return end case _ do loadNum 1 return end"));
+    assert!(unknown.contains("Unknown variant Square"), "{}", unknown);
+    let bindings = load_error(&area("case Circle r extra do load r return end case Empty do loadNum 0 return end"));
+    assert!(bindings.contains("Case Circle binds 2 values but the variant has 1 fields"), "{}", bindings);
+
+    let mut engine = Engine::new();
+    engine.load_source(&area("case Circle r do load r return end case _ do loadNum 0 return end")).unwrap();
+    engine.load_source("
+        func circle endArgs std/num
+        loadNum 3
+        call Figure/Circle 1
+        call area 1
+        return
+        end
+        func empty endArgs std/num
+        call Figure/Empty 0
+        call area 1
+        return
+        end
+    ").unwrap();
+    assert_eq!(engine.call("circle", vec![]), Ok(RuntimeObject::Num(3.0)));
+    assert_eq!(engine.call("empty", vec![]), Ok(RuntimeObject::Num(0.0)));
+}
+
+#[test]
+fn struct_names_resolve_across_sources() {
+    let mut engine = Engine::new();
+    engine.load_source("struct Point x std/int end").unwrap();
+    engine.load_source("
+        func x Point endArgs std/int
+        loadArg 0
+        getProp x
+        return
+        end
+        func any Elsewhere endArgs std/int
+        loadInt 1
+        return
+        end
+        func point endArgs std/int
+        loadInt 4
+        call Point 1
+        call x 1
+        return
+        end
+        func object endArgs std/int
+        loadInt 4
+        @Object x #
+        call x 1
+        return
+        end
+    ").unwrap();
+
+    assert_eq!(engine.call("point", vec![]), Ok(RuntimeObject::Int(4)));
+    //Point was declared by the first source, so a plain object does not fit
+    match engine.call("object", vec![]) {
+        Err(EngineError::Runtime(e)) => assert_eq!(e.kind, ErrorKind::Type),
+        other => panic!("Expected a type error but got {:?}", other)
+    }
+    //no source declares Elsewhere, it accepts any object
+    let object = RuntimeObject::Object(engine.runtime().storage.allocate_object());
+    assert_eq!(engine.call("any", vec![object]), Ok(RuntimeObject::Int(1)));
+    assert!(engine.call("any", vec![RuntimeObject::Int(0)]).is_err());
+}
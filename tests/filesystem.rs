@@ -0,0 +1,220 @@
+use std::io::{ErrorKind, Read, Write};
+use std::path::PathBuf;
+
+use dscript_runtime::runtime::filesystem::{FileSystem, MemoryFileSystem, OpenMode, OverlayFileSystem};
+use dscript_runtime::{Engine, RuntimeObject};
+
+fn mode(letters: &str) -> OpenMode {
+    OpenMode::parse(letters).unwrap()
+}
+
+fn read(filesystem: &dyn FileSystem, path: &str) -> String {
+    let mut content = String::new();
+    filesystem.open(path, mode("r")).unwrap().read_to_string(&mut content).unwrap();
+    content
+}
+
+fn write(filesystem: &dyn FileSystem, path: &str, letters: &str, content: &str) {
+    filesystem.open(path, mode(letters)).unwrap().write_all(content.as_bytes()).unwrap();
+}
+
+//a directory on disk for the overlay to read through to, removed when dropped
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(name: &str) -> TempDir {
+        let path = std::env::temp_dir().join(format!("dscript_fs_{}_{}", name, std::process::id()));
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    fn path(&self, name: &str) -> String {
+        self.0.join(name).to_string_lossy().to_string()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn preloaded_files_are_readable_and_listed() {
+    let filesystem = MemoryFileSystem::new();
+    filesystem.write("data/b.txt", "b");
+    filesystem.write("./data/a.txt", "a");
+    filesystem.write("top.txt", vec![0u8, 1]);
+
+    assert_eq!(filesystem.files(), vec!["data/a.txt", "data/b.txt", "top.txt"]);
+    assert_eq!(filesystem.read_to_string("data/../data/a.txt").as_deref(), Some("a"));
+    assert_eq!(filesystem.read("top.txt"), Some(vec![0, 1]));
+    assert_eq!(filesystem.read("data"), None);
+    assert!(filesystem.metadata("data").unwrap().is_dir);
+    assert_eq!(filesystem.read_dir("data").unwrap(), vec!["a.txt", "b.txt"]);
+    assert_eq!(read(&filesystem, "data/b.txt"), "b");
+}
+
+#[test]
+fn open_modes() {
+    let filesystem = MemoryFileSystem::new();
+    assert_eq!(filesystem.open("new.txt", mode("r")).err().map(|e| e.kind()), Some(ErrorKind::NotFound));
+    //creating or truncating needs write access
+    assert_eq!(filesystem.open("new.txt", mode("c")).err().map(|e| e.kind()), Some(ErrorKind::InvalidInput));
+    assert_eq!(filesystem.open("new.txt", mode("rt")).err().map(|e| e.kind()), Some(ErrorKind::InvalidInput));
+    assert_eq!(filesystem.open("missing/new.txt", mode("wc")).err().map(|e| e.kind()), Some(ErrorKind::NotFound));
+
+    write(&filesystem, "new.txt", "wc", "hello");
+    write(&filesystem, "new.txt", "a", " world");
+    assert_eq!(read(&filesystem, "new.txt"), "hello world");
+    write(&filesystem, "new.txt", "w", "J");
+    assert_eq!(read(&filesystem, "new.txt"), "Jello world");
+    write(&filesystem, "new.txt", "wt", "bye");
+    assert_eq!(read(&filesystem, "new.txt"), "bye");
+
+    let mut buffer = [0u8; 4];
+    assert!(filesystem.open("new.txt", mode("w")).unwrap().read(&mut buffer).is_err());
+    assert!(filesystem.open("new.txt", mode("r")).unwrap().write(b"x").is_err());
+}
+
+#[test]
+fn renaming_a_directory_moves_its_contents() {
+    let filesystem = MemoryFileSystem::new();
+    filesystem.write("a/x.txt", "x");
+    filesystem.write("a/sub/y.txt", "y");
+    filesystem.write("ab.txt", "not below a");
+
+    filesystem.rename("a", "b").unwrap();
+    assert_eq!(filesystem.files(), vec!["ab.txt", "b/sub/y.txt", "b/x.txt"]);
+    assert!(!filesystem.exists("a"));
+    assert!(filesystem.metadata("b/sub").unwrap().is_dir);
+
+    assert_eq!(filesystem.rename("b", "b/inside").err().map(|e| e.kind()), Some(ErrorKind::InvalidInput));
+    assert_eq!(filesystem.files(), vec!["ab.txt", "b/sub/y.txt", "b/x.txt"]);
+    filesystem.rename("b", "b").unwrap();
+}
+
+#[test]
+fn only_empty_directories_can_be_removed() {
+    let filesystem = MemoryFileSystem::new();
+    filesystem.write("dir/file.txt", "content");
+    assert!(filesystem.remove("dir").is_err());
+    filesystem.remove("dir/file.txt").unwrap();
+    filesystem.remove("dir").unwrap();
+    assert!(!filesystem.exists("dir"));
+    assert_eq!(filesystem.remove("dir").err().map(|e| e.kind()), Some(ErrorKind::NotFound));
+}
+
+#[test]
+fn overlay_reads_through_to_the_disk() {
+    let dir = TempDir::new("read");
+    std::fs::write(dir.path("disk.txt"), "from disk").unwrap();
+    std::fs::create_dir(dir.path("sub")).unwrap();
+    let filesystem = OverlayFileSystem::new();
+
+    assert_eq!(read(&filesystem, &dir.path("disk.txt")), "from disk");
+    assert!(filesystem.metadata(&dir.path("sub")).unwrap().is_dir);
+    write(&filesystem, &dir.path("memory.txt"), "wc", "from memory");
+    assert_eq!(filesystem.read_dir(&dir.path("")).unwrap(), vec!["disk.txt", "memory.txt", "sub"]);
+}
+
+#[test]
+fn overlay_writes_do_not_reach_the_disk() {
+    let dir = TempDir::new("write");
+    std::fs::write(dir.path("disk.txt"), "original").unwrap();
+    std::fs::create_dir(dir.path("sub")).unwrap();
+    let filesystem = OverlayFileSystem::new();
+
+    write(&filesystem, &dir.path("disk.txt"), "a", " changed");
+    write(&filesystem, &dir.path("new.txt"), "wc", "new");
+    filesystem.rename(&dir.path("sub"), &dir.path("moved")).unwrap();
+    filesystem.create_dir_all(&dir.path("made/deep")).unwrap();
+    assert_eq!(read(&filesystem, &dir.path("disk.txt")), "original changed");
+    assert_eq!(read(&filesystem, &dir.path("new.txt")), "new");
+    assert!(!filesystem.exists(&dir.path("sub")));
+
+    assert_eq!(std::fs::read_to_string(dir.path("disk.txt")).unwrap(), "original");
+    assert!(!std::path::Path::new(&dir.path("new.txt")).exists());
+    assert!(std::path::Path::new(&dir.path("sub")).is_dir());
+    assert!(!std::path::Path::new(&dir.path("moved")).exists());
+    assert!(!std::path::Path::new(&dir.path("made")).exists());
+    assert!(filesystem.memory().files().contains(&dir.path("new.txt")));
+
+    filesystem.remove(&dir.path("disk.txt")).unwrap();
+    assert!(!filesystem.exists(&dir.path("disk.txt")));
+    assert!(std::path::Path::new(&dir.path("disk.txt")).exists());
+
+    assert_eq!(filesystem.rename(&dir.path("moved"), &dir.path("moved/again")).err().map(|e| e.kind()), Some(ErrorKind::InvalidInput));
+}
+
+const FILES: &str = "
+func lines endArgs std/any
+loadString r'
+loadString data.txt'
+call std/fs/open 2
+set f
+load f
+call file:read_line 1
+load f
+call file:read_line 1
+load f
+call file:read_line 1
+@List 3
+return
+end
+func bytes endArgs std/any
+loadString r'
+loadString data.txt'
+call std/fs/open 2
+set f
+loadInt 2
+load f
+call file:read_bytes 2
+loadInt 100
+load f
+call file:read_bytes 2
+@List 2
+return
+end
+func failure std/str std/str endArgs std/str
+try do
+loadArg 1
+loadArg 0
+call std/fs/open 2
+set f
+loadString text'
+load f
+call file:write 2
+loadString written'
+return
+end catch e do
+load e
+getProp kind
+return
+end
+return
+end
+";
+
+fn string(text: &str) -> RuntimeObject {
+    RuntimeObject::Str(text.to_string())
+}
+
+#[test]
+fn scripts_read_lines_and_bytes_and_catch_io_errors() {
+    let filesystem = MemoryFileSystem::new();
+    filesystem.write("data.txt", "one\r\ntwo\n");
+    let mut engine = Engine::new();
+    engine.set_filesystem(filesystem.clone());
+    engine.load_source(FILES).unwrap();
+
+    //lines lose their line ending and the end of the file reads as void
+    assert_eq!(engine.call("lines", vec![]), Ok(RuntimeObject::List(vec![RuntimeObject::Void, string("two"), string("one")])));
+    let bytes = |text: &str| RuntimeObject::List(text.bytes().map(|b| RuntimeObject::Int(b as i64)).collect());
+    assert_eq!(engine.call("bytes", vec![]), Ok(RuntimeObject::List(vec![bytes("e\r\ntwo\n"), bytes("on")])));
+
+    assert_eq!(engine.call("failure", vec![string("missing.txt"), string("r")]), Ok(string("IoError")));
+    assert_eq!(engine.call("failure", vec![string("data.txt"), string("r")]), Ok(string("IoError")));
+    assert_eq!(engine.call("failure", vec![string("new.txt"), string("wc")]), Ok(string("written")));
+    assert_eq!(filesystem.read_to_string("new.txt").as_deref(), Some("text"));
+}
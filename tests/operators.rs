@@ -0,0 +1,224 @@
+use dscript_runtime::{Engine, EngineError, ErrorKind, RuntimeObject};
+
+fn run(source: &str) -> RuntimeObject {
+    let mut engine = Engine::new();
+    engine.load_source(source).unwrap();
+    engine.call("main", vec![]).unwrap()
+}
+
+fn run_error(source: &str) -> (ErrorKind, String) {
+    let mut engine = Engine::new();
+    engine.load_source(source).unwrap();
+    match engine.call("main", vec![]) {
+        Err(EngineError::Runtime(e)) => (e.kind, e.message),
+        other => panic!("Expected a runtime error from {} but got {:?}", source, other)
+    }
+}
+
+/// Runs `binary <op>` with `left` on top of the stack.
+fn int_operation(left: i64, op: &str, right: i64) -> String {
+    format!("func main endArgs std/int loadInt {} loadInt {} binary {} return end", right, left, op)
+}
+
+#[test]
+fn the_top_of_the_stack_is_the_left_operand() {
+    assert_eq!(run("func main endArgs std/int loadInt 3 loadInt 10 binary sub return end"), RuntimeObject::Int(7));
+    assert_eq!(run("func main endArgs std/num loadNum 4 loadNum 1 binary div return end"), RuntimeObject::Num(0.25));
+    assert_eq!(run("func main endArgs std/str loadString b' loadString a' binary add return end"), RuntimeObject::Str("ab".to_string()));
+    //comparisons take the value below the top on the left
+    assert_eq!(run("func main endArgs std/bool loadInt 10 loadInt 3 equality gt return end"), RuntimeObject::Bool(true));
+}
+
+const MONEY: &str = "
+struct Money cents std/int end
+func Money:sub Money Money endArgs Money
+loadArg 1
+getProp cents
+loadArg 0
+getProp cents
+binary sub
+call Money 1
+return
+end
+func Money:add Money Money endArgs Money
+mapArg 0 left
+mapArg 1 right
+load right
+getProp cents
+load left
+getProp cents
+binary add
+call Money 1
+return
+end
+";
+
+#[test]
+fn overloads_take_the_operands_in_order() {
+    let source = format!("{}
+func main endArgs std/int
+loadInt 3
+call Money 1
+loadInt 10
+call Money 1
+binary sub
+getProp cents
+return
+end", MONEY);
+    assert_eq!(run(&source), RuntimeObject::Int(7));
+}
+
+#[test]
+fn only_a_struct_on_the_left_is_overloaded() {
+    let source = format!("{}
+func main endArgs std/str
+loadInt 3
+call Money 1
+loadString total: '
+binary add
+return
+end", MONEY);
+    match run(&source) {
+        RuntimeObject::Str(s) => assert!(s.starts_with("total: "), "{}", s),
+        other => panic!("Expected a concatenated String but got {}", other)
+    }
+}
+
+#[test]
+fn overloads_release_their_operands() {
+    //the operands leave nothing behind, the engine ends up holding what building the sum alone holds
+    let overloaded = format!("{}
+func main endArgs std/int
+loadInt 1
+call Money 1
+set a
+loadInt 2
+call Money 1
+set b
+load b
+load a
+binary add
+set c
+load c
+getProp cents
+return
+end", MONEY);
+    let direct = format!("{}
+func main endArgs std/int
+loadInt 3
+call Money 1
+set c
+load c
+getProp cents
+return
+end", MONEY);
+
+    let mut live = vec![];
+    for source in [overloaded, direct] {
+        let mut engine = Engine::new();
+        engine.load_source(&source).unwrap();
+        assert_eq!(engine.call("main", vec![]), Ok(RuntimeObject::Int(3)));
+        live.push(engine.runtime().storage.live_objects());
+    }
+    assert_eq!(live[0], live[1]);
+}
+
+#[test]
+fn integer_arithmetic_is_checked() {
+    assert_eq!(run(&int_operation(i64::MAX - 1, "add", 1)), RuntimeObject::Int(i64::MAX));
+    assert_eq!(run(&int_operation(2, "pow", 62)), RuntimeObject::Int(1 << 62));
+    for (left, op, right) in [(i64::MAX, "add", 1), (i64::MIN, "sub", 1), (i64::MAX, "mul", 2), (2, "pow", 63), (i64::MIN, "div", -1)] {
+        let (kind, message) = run_error(&int_operation(left, op, right));
+        assert_eq!(kind, ErrorKind::Arithmetic);
+        assert!(message.contains("Integer overflow"), "{}", message);
+    }
+}
+
+#[test]
+fn integer_division_truncates_and_mod_is_never_negative() {
+    assert_eq!(run(&int_operation(7, "div", 2)), RuntimeObject::Int(3));
+    assert_eq!(run(&int_operation(-7, "div", 2)), RuntimeObject::Int(-3));
+    assert_eq!(run(&int_operation(-7, "mod", 3)), RuntimeObject::Int(2));
+    for op in ["div", "mod"] {
+        let (kind, message) = run_error(&int_operation(1, op, 0));
+        assert_eq!(kind, ErrorKind::Arithmetic);
+        assert!(message.contains("Division by zero"), "{}", message);
+    }
+}
+
+#[test]
+fn pow_and_shifts_report_invalid_operands() {
+    let (kind, message) = run_error(&int_operation(2, "pow", -1));
+    assert_eq!(kind, ErrorKind::Arithmetic);
+    assert!(message.contains("non negative exponent"), "{}", message);
+    for bits in [-1, 64] {
+        let (kind, message) = run_error(&int_operation(1, "shl", bits));
+        assert_eq!(kind, ErrorKind::Arithmetic);
+        assert!(message.contains("0 to 63 bits"), "{}", message);
+    }
+    assert_eq!(run(&int_operation(1, "shl", 63)), RuntimeObject::Int(i64::MIN));
+    assert_eq!(run(&int_operation(-8, "shr", 1)), RuntimeObject::Int(-4));
+    //huge exponents only fit for 0, 1 and -1
+    assert_eq!(run(&int_operation(-1, "pow", (1 << 40) + 1)), RuntimeObject::Int(-1));
+    assert_eq!(run(&int_operation(-1, "pow", 1 << 40)), RuntimeObject::Int(1));
+    assert_eq!(run(&int_operation(0, "pow", 1 << 40)), RuntimeObject::Int(0));
+    assert!(run_error(&int_operation(2, "pow", 1 << 40)).1.contains("Integer overflow"));
+}
+
+#[test]
+fn integers_convert_from_numbers_and_strings() {
+    assert_eq!(run("func main endArgs std/int loadNum -2.7 call num:to_int 1 return end"), RuntimeObject::Int(-2));
+    assert_eq!(run("func main endArgs std/num loadInt 5 call int:to_num 1 return end"), RuntimeObject::Num(5.0));
+    assert_eq!(run("func main endArgs std/int loadString 42' call str:as_int 1 return end"), RuntimeObject::Int(42));
+    let (_, message) = run_error("func main endArgs std/int loadNum 1e19 call num:to_int 1 return end");
+    assert!(message.contains("does not fit into an integer"), "{}", message);
+    let (_, message) = run_error("func main endArgs std/int loadString 4.2' call str:as_int 1 return end");
+    assert!(message.contains("Cannot convert string"), "{}", message);
+}
+
+fn num(body: &str) -> String {
+    format!("func main endArgs std/num {} return end", body)
+}
+
+#[test]
+fn math_library_and_number_operators() {
+    assert_eq!(run(&num("loadNum -7.5 call std/math/floor 1")), RuntimeObject::Num(-8.0));
+    assert_eq!(run(&num("loadInt 2 loadNum 2.71828 call std/math/round_to 2")), RuntimeObject::Num(2.72));
+    //clamp takes the value, then min and max
+    assert_eq!(run(&num("loadNum 5 loadNum 1 loadNum 9 call std/math/clamp 3")), RuntimeObject::Num(5.0));
+    assert_eq!(run(&num("call std/math/pi 0")), RuntimeObject::Num(std::f64::consts::PI));
+    assert_eq!(run("func main endArgs std/str loadInt 2 loadNum 2.5 call std/math/format 2 return end"), RuntimeObject::Str("2.50".to_string()));
+    let (_, message) = run_error(&num("loadNum 1 loadNum 5 loadNum 9 call std/math/clamp 3"));
+    assert!(message.contains("min is greater than max"), "{}", message);
+
+    //the modulo has the sign of the divisor
+    assert_eq!(run(&num("loadNum 3 loadNum -7 binary mod")), RuntimeObject::Num(2.0));
+    assert_eq!(run(&num("loadNum 0.5 loadNum 16 binary pow")), RuntimeObject::Num(4.0));
+}
+
+fn condition(body: &str) -> String {
+    format!("func main endArgs std/bool {} return end", body)
+}
+
+#[test]
+fn comparisons_take_the_value_below_the_top_on_the_left() {
+    assert_eq!(run(&condition("loadInt 3 loadInt 3 equality gte")), RuntimeObject::Bool(true));
+    assert_eq!(run(&condition("loadNum 2 loadNum 3 equality gte")), RuntimeObject::Bool(false));
+    assert_eq!(run(&condition("loadNum 2 loadNum 3 equality lte")), RuntimeObject::Bool(true));
+    //strings compare lexicographically
+    assert_eq!(run(&condition("loadString apple' loadString banana' equality st")), RuntimeObject::Bool(true));
+    assert_eq!(run(&condition("loadString b' loadString abc' equality gte")), RuntimeObject::Bool(true));
+    assert_eq!(run(&condition("loadBool true unary not")), RuntimeObject::Bool(false));
+    assert_eq!(run(&condition("loadBool true loadBool false binary xor")), RuntimeObject::Bool(true));
+}
+
+#[test]
+fn and_and_or_blocks_short_circuit() {
+    //the blocks would fail with a division by zero if they ran
+    let failing = "loadInt 0 loadInt 1 binary div set x loadBool true return end";
+    assert_eq!(run(&condition(&format!("loadBool false and do {}", failing))), RuntimeObject::Bool(false));
+    assert_eq!(run(&condition(&format!("loadBool true or do {}", failing))), RuntimeObject::Bool(true));
+    assert_eq!(run(&condition("loadBool true and do loadBool false return end")), RuntimeObject::Bool(false));
+    assert_eq!(run(&condition("loadBool false or do loadBool true return end")), RuntimeObject::Bool(true));
+    assert!(run_error(&condition(&format!("loadBool true and do {}", failing))).1.contains("Division by zero"));
+}
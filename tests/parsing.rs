@@ -0,0 +1,33 @@
+use dscript_runtime::parsing::parse_source;
+use dscript_runtime::Type;
+
+#[test]
+fn type_names_are_kept_for_the_runtime_to_resolve() {
+    let functions = parse_source("
+        struct Point x std/int end
+        func f Point Elsewhere std/unknown endArgs std/any
+        loadArg 0
+        return
+        end
+    ").unwrap();
+    let f = functions.iter().find(|it| it.signature == "f").unwrap();
+    //Elsewhere may be declared by another source, unknown std names accept any object
+    assert_eq!(f.args, Some(vec![Type::Struct("Point".to_string()), Type::Struct("Elsewhere".to_string()), Type::Complex(vec![])]));
+}
+
+#[test]
+fn instructions_keep_their_lines() {
+    let functions = parse_source("
+func main endArgs std/int
+loadBool true
+if do
+  loadInt 1
+  return
+end
+loadInt 2 return
+end").unwrap();
+    let source = functions[0].source.as_ref().unwrap();
+    assert_eq!(source.path, None);
+    //nested instructions follow the block that holds them, like in debug::flatten
+    assert_eq!(source.lines, vec![3, 4, 5, 6, 8, 8]);
+}
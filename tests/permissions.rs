@@ -0,0 +1,49 @@
+use dscript_runtime::runtime::filesystem::MemoryFileSystem;
+use dscript_runtime::runtime::permissions::Permissions;
+use dscript_runtime::ErrorKind;
+
+#[test]
+fn paths_resolve_through_the_memory_filesystem() {
+    let filesystem = MemoryFileSystem::new();
+    filesystem.write("data/a.txt", "a");
+    let mut permissions = Permissions::none();
+    permissions.allow_read(Some("data"));
+
+    assert!(permissions.check_read(&filesystem, "data/a.txt").is_ok());
+    assert!(permissions.check_read(&filesystem, "./data/new/../a.txt").is_ok());
+    let denied = permissions.check_read(&filesystem, "data/../secret.txt").unwrap_err();
+    assert_eq!(denied.kind, ErrorKind::PermissionDenied);
+    assert!(denied.message.contains("--allow-read"), "{}", denied.message);
+    assert!(permissions.check_write(&filesystem, "data/a.txt").is_err());
+}
+
+#[test]
+#[cfg(unix)]
+fn disk_paths_follow_symlinks_out_of_a_granted_directory() {
+    use dscript_runtime::runtime::filesystem::{DiskFileSystem, FileSystem, OverlayFileSystem};
+
+    let root = std::env::temp_dir().join(format!("dscript_permissions_{}", std::process::id()));
+    let granted = root.join("granted");
+    let outside = root.join("outside");
+    std::fs::create_dir_all(&granted).unwrap();
+    std::fs::create_dir_all(&outside).unwrap();
+    std::os::unix::fs::symlink(&outside, granted.join("link")).unwrap();
+
+    let mut permissions = Permissions::none();
+    permissions.allow_write(Some(granted.to_str().unwrap()));
+    for filesystem in [&DiskFileSystem as &dyn FileSystem, &OverlayFileSystem::new()] {
+        assert!(permissions.check_write(filesystem, granted.join("new.txt").to_str().unwrap()).is_ok());
+        assert!(permissions.check_write(filesystem, granted.join("link/new.txt").to_str().unwrap()).is_err());
+        assert!(permissions.check_write(filesystem, granted.join("../outside/new.txt").to_str().unwrap()).is_err());
+    }
+    let _ = std::fs::remove_dir_all(root);
+}
+
+#[test]
+fn net_is_granted_by_its_flag_only() {
+    let mut permissions = Permissions::none();
+    assert!(permissions.check_net().unwrap_err().message.contains("--allow-net"));
+    permissions.net = true;
+    assert!(permissions.check_net().is_ok());
+    assert!(Permissions::allow_all().check_net().is_ok());
+}
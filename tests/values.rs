@@ -0,0 +1,98 @@
+use dscript_runtime::runtime::convert::{FromRuntime, IntoRuntime};
+use dscript_runtime::runtime::std_lib::{d_format, FormatOptions};
+use dscript_runtime::runtime::value::{deep_eq, deep_hash, ValueMap};
+use dscript_runtime::{runtime_struct, ObjectStorage, RuntimeObject, Type};
+
+fn int(n: i64) -> RuntimeObject {
+    RuntimeObject::Int(n)
+}
+
+#[test]
+fn cycles_of_different_length_that_deep_equal_hash_alike() {
+    let mut storage = ObjectStorage::new();
+    //a points to itself, b and c point to each other
+    let a = storage.allocate_typed_object("Node");
+    let b = storage.allocate_typed_object("Node");
+    let c = storage.allocate_typed_object("Node");
+    storage.set_field(&a, "next".to_string(), RuntimeObject::Object(a.clone()));
+    storage.set_field(&b, "next".to_string(), RuntimeObject::Object(c.clone()));
+    storage.set_field(&c, "next".to_string(), RuntimeObject::Object(b.clone()));
+    let (a, b) = (RuntimeObject::Object(a), RuntimeObject::Object(b));
+
+    assert!(deep_eq(&storage, &a, &b));
+    assert_eq!(deep_hash(&storage, &a), deep_hash(&storage, &b));
+
+    let mut map = ValueMap::new();
+    map.insert(&storage, a, int(1));
+    assert_eq!(map.get(&storage, &b), Some(&int(1)));
+}
+
+#[test]
+fn nan_keys_can_be_found_again() {
+    let storage = ObjectStorage::new();
+    let mut map = ValueMap::new();
+    map.insert(&storage, RuntimeObject::Num(f64::NAN), int(1));
+    map.insert(&storage, RuntimeObject::Num(-f64::NAN), int(2));
+    assert_eq!(map.len(), 1);
+    assert_eq!(map.get(&storage, &RuntimeObject::Num(f64::NAN)), Some(&int(2)));
+    assert_eq!(map.remove(&storage, &RuntimeObject::Num(f64::NAN)), Some(int(2)));
+}
+
+#[test]
+fn map_equality_ignores_the_insertion_order() {
+    let storage = ObjectStorage::new();
+    let mut first = ValueMap::new();
+    let mut second = ValueMap::new();
+    for n in 0..10 {
+        first.insert(&storage, int(n), int(n * 2));
+        second.insert(&storage, int(9 - n), int((9 - n) * 2));
+    }
+    assert_eq!(first, second);
+    second.insert(&storage, int(3), int(0));
+    assert_ne!(first, second);
+}
+
+#[derive(Debug, PartialEq)]
+struct Point {
+    x: i64,
+    label: Option<String>
+}
+
+runtime_struct!(Point { x, label });
+
+#[test]
+fn rust_values_convert_to_and_from_runtime_objects() {
+    let mut storage = ObjectStorage::new();
+    let list = vec![1i64, 2, 3].into_runtime(&mut storage);
+    assert_eq!(list, RuntimeObject::List(vec![int(1), int(2), int(3)]));
+    assert_eq!(Vec::<i64>::from_runtime(&list, &storage), Ok(vec![1, 2, 3]));
+    assert_eq!(<Vec<i64> as FromRuntime>::runtime_type(), Type::List(Box::new(Type::Int)));
+
+    assert_eq!(Option::<String>::from_runtime(&RuntimeObject::Void, &storage), Ok(None));
+    assert_eq!(u32::from_runtime(&int(-1), &storage), Err("Expected integer in range of u32 but got -1".to_string()));
+    let mixed = RuntimeObject::List(vec![int(1), RuntimeObject::Bool(true)]);
+    assert!(Vec::<i64>::from_runtime(&mixed, &storage).unwrap_err().ends_with("at index 1"));
+
+    let point = Point { x: 4, label: Some("corner".to_string()) }.into_runtime(&mut storage);
+    assert_eq!(Point::from_runtime(&point, &storage), Ok(Point { x: 4, label: Some("corner".to_string()) }));
+    assert!(Point::from_runtime(&int(4), &storage).is_err());
+}
+
+#[test]
+fn pretty_printer_marks_cycles_and_shortens_deep_values() {
+    let mut storage = ObjectStorage::new();
+    let node = storage.allocate_object();
+    let id = node.id();
+    storage.set_field(&node, "name".to_string(), RuntimeObject::Str("root".to_string()));
+    storage.set_field(&node, "next".to_string(), RuntimeObject::Object(node.clone()));
+    let value = RuntimeObject::List(vec![int(1), RuntimeObject::Object(node)]);
+    assert_eq!(
+        d_format(&value, &storage, &FormatOptions::default()),
+        format!("[\n  1,\n  Object #{id} {{\n    name: \"root\",\n    next: <cycle #{id}>\n  }}\n]")
+    );
+    assert_eq!(d_format(&value, &storage, &FormatOptions { max_depth: 1, one_line: true }), format!("[1, Object #{id} {{...}}]"));
+    let nested = RuntimeObject::List(vec![RuntimeObject::List(vec![RuntimeObject::List(vec![int(1), int(2)])])]);
+    assert_eq!(d_format(&nested, &storage, &FormatOptions { max_depth: 2, one_line: true }), "[[[...]]]");
+    //top level strings are not quoted
+    assert_eq!(d_format(&RuntimeObject::Str("text".to_string()), &storage, &FormatOptions::default()), "text");
+}
@@ -0,0 +1,92 @@
+use dscript_runtime::runtime::handle::Handle;
+use dscript_runtime::runtime::value::{deep_eq, ValueMap};
+use dscript_runtime::runtime::worker::SendValue;
+use dscript_runtime::{Engine, EngineError, ObjectStorage, RuntimeObject};
+
+#[test]
+fn join_ends_a_worker_waiting_for_a_message() {
+    let mut engine = Engine::new();
+    engine.load_source("
+        func listen std/handle endArgs std/any
+        loadArg 0
+        call channel:receive 1
+        return
+        end
+        func main endArgs std/any
+        loadString listen'
+        call std/worker/spawn 1
+        call worker:join 1
+        return
+        end
+    ").unwrap();
+    //nothing was sent, so the receive fails instead of waiting for a parent that is itself waiting
+    match engine.call("main", vec![]) {
+        Err(EngineError::Runtime(e)) => assert!(e.message.contains("Channel is closed"), "{}", e),
+        other => panic!("Expected the worker to fail but got {:?}", other)
+    }
+}
+
+#[test]
+fn messages_are_copied_both_ways() {
+    let mut engine = Engine::new();
+    engine.load_source("
+        func echo std/handle endArgs std/int
+        loadArg 0
+        call channel:receive 1
+        loadArg 0
+        call channel:send 2
+        loadInt 1
+        return
+        end
+        func main endArgs std/str
+        loadString echo'
+        call std/worker/spawn 1
+        set worker
+        loadString hello'
+        @Object text #
+        load worker
+        call worker:send 2
+        load worker
+        call worker:receive 1
+        load worker
+        call worker:join 1
+        set joined
+        getProp text
+        return
+        end
+    ").unwrap();
+    assert_eq!(engine.call("main", vec![]), Ok(RuntimeObject::Str("hello".to_string())));
+}
+
+#[test]
+fn send_values_round_trip_between_storages() {
+    let mut storage = ObjectStorage::new();
+    let point = storage.allocate_typed_object("Point");
+    storage.set_field(&point, "x".to_string(), RuntimeObject::Int(1));
+    let mut map = ValueMap::new();
+    map.insert(&storage, RuntimeObject::Str("key".to_string()), RuntimeObject::Num(2.5));
+    let outer = storage.allocate_object();
+    storage.set_field(&outer, "point".to_string(), RuntimeObject::Object(point.clone()));
+    storage.set_field(&outer, "items".to_string(), RuntimeObject::List(vec![RuntimeObject::Bool(true), RuntimeObject::Void]));
+    storage.set_field(&outer, "map".to_string(), RuntimeObject::Map(map));
+    let value = RuntimeObject::Object(outer);
+
+    let mut other = ObjectStorage::new();
+    let copied = SendValue::copy(&storage, &value).unwrap().restore(&mut other);
+    let back = SendValue::copy(&other, &copied).unwrap().restore(&mut storage);
+    //equal content in fresh objects, struct types survive the copy
+    assert!(deep_eq(&storage, &value, &back));
+    assert_ne!(value, back);
+    match &back {
+        RuntimeObject::Object(o) => match storage.get_field(o, "point".to_string()) {
+            Some(RuntimeObject::Object(p)) => assert_eq!(p.type_name(), Some("Point")),
+            other => panic!("Expected the point but got {:?}", other)
+        },
+        other => panic!("Expected an object but got {}", other)
+    }
+
+    //cycles and handles cannot be copied
+    storage.set_field(&point, "self".to_string(), RuntimeObject::Object(point.clone()));
+    assert!(SendValue::copy(&storage, &value).is_err());
+    assert!(SendValue::copy(&storage, &RuntimeObject::Handle(Handle::new("file", 0))).is_err());
+}